@@ -0,0 +1,398 @@
+// indexer/src/budget_pack.rs
+//! Budgeted priority packing: fit the most valuable files into one fixed token
+//! budget (e.g. 100k) instead of splitting everything into capped chunks.
+//!
+//! - Entries are ranked by weighted signals: role, public export count, churn
+//!   (sha1 changes across archived snapshots) and size (a penalty).
+//! - Greedy fill in rank order, degrading per file: full source → indexed
+//!   snippet → signatures. Anything that still doesn't fit is dropped.
+//! - The budget covers the rendered pack: the chunk header is reserved up
+//!   front and each file is charged with its header, fences and escaping.
+//! - Every decision is reported (included detail / dropped reason).
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    fs,
+    path::Path,
+};
+use syn::visit::Visit;
+
+use crate::{
+    chunker::{self, ChunkFormat, Part},
+    file_intent_entry::{FileIntentEntry, Role},
+    functions_view::{FnCollector, Kind},
+    redact::Redactor,
+    scan,
+    types_view::{Decl, TypeCollector},
+};
+
+/* ================================= Config ================================= */
+
+/// Ranking weights. Each signal is normalized to 0..=1 before weighting.
+#[derive(Clone, Debug)]
+pub struct PackWeights {
+    /// Role priority (Bin/Lib/Core first, Test/Doc last).
+    pub role: f64,
+    /// Public export count.
+    pub exports: f64,
+    /// Churn: number of distinct sha1s seen across history snapshots.
+    pub churn: f64,
+    /// Size penalty (subtracted): bigger files cost more of the budget.
+    pub size: f64,
+}
+
+impl Default for PackWeights {
+    fn default() -> Self {
+        Self { role: 1.0, exports: 0.5, churn: 0.5, size: 0.25 }
+    }
+}
+
+impl PackWeights {
+    /// Parse `role=1,exports=0.5,churn=0.5,size=0.25` (any subset; rest default).
+    ///
+    /// # Errors
+    ///
+    /// Fails on an entry without `=`, a non-numeric value or an unknown key.
+    pub fn parse(spec: &str) -> Result<Self> {
+        let mut w = Self::default();
+        for kv in spec.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let (k, v) = kv
+                .split_once('=')
+                .with_context(|| format!("bad weight `{kv}` (expected key=value)"))?;
+            let v: f64 = v.trim().parse().with_context(|| format!("bad weight value in `{kv}`"))?;
+            match k.trim().to_ascii_lowercase().as_str() {
+                "role" => w.role = v,
+                "exports" => w.exports = v,
+                "churn" => w.churn = v,
+                "size" => w.size = v,
+                other => anyhow::bail!("unknown weight `{other}` (expected role|exports|churn|size)"),
+            }
+        }
+        Ok(w)
+    }
+}
+
+/// Budgeted packing knobs.
+#[derive(Clone, Debug)]
+pub struct PackOptions {
+    /// Total token budget for the whole pack.
+    pub budget: usize,
+    pub weights: PackWeights,
+    pub format: ChunkFormat,
+}
+
+impl Default for PackOptions {
+    fn default() -> Self {
+        Self { budget: 100_000, weights: PackWeights::default(), format: ChunkFormat::Md }
+    }
+}
+
+/* ================================= Report ================================= */
+
+#[derive(Debug, Serialize)]
+pub struct PackReport {
+    pub budget: usize,
+    /// Estimated tokens of the rendered pack, framing included.
+    pub used_tokens: usize,
+    pub included: Vec<Included>,
+    pub dropped: Vec<Dropped>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Included {
+    pub path: String,
+    pub score: f64,
+    /// "full" | "snippet" | "signatures"
+    pub detail: &'static str,
+    /// Tokens charged: the body plus its rendered framing.
+    pub tokens: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Dropped {
+    pub path: String,
+    pub score: f64,
+    pub reason: String,
+}
+
+/* ============================= Public API ============================== */
+
+/// Reserve for the chunk header's file/part/token counts growing past the
+/// empty-chunk rendering.
+const HEADER_SLACK_TOKENS: usize = 8;
+
+/// Rank + greedily pack `index_path` into one file at `out_path`.
+/// `project_root` resolves full sources; `history_dir` holds archived
+/// `<slug>_<ts>.jsonl` snapshots used for churn (may be missing).
+///
+/// # Errors
+///
+/// Fails when the index or `.gptredact` cannot be read, a chunk cannot be
+/// rendered, or `out_path` cannot be written.
+pub fn build_budget_pack(
+    index_path: &Path,
+    project_root: &Path,
+    history_dir: &Path,
+    out_path: &Path,
+    opts: &PackOptions,
+) -> Result<PackReport> {
+    let entries = scan::read_index(index_path)?;
+    let churn = churn_from_history(history_dir);
+    // Snippets are already scrubbed by the scanner; full sources come from disk.
+    let redactor = Redactor::load(project_root)?;
+
+    let mut ranked = rank_entries(&entries, &churn, &opts.weights);
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.path.cmp(&b.0.path)));
+
+    // Charge rendered bytes, not bodies. Counts in the chunk header grow as
+    // files are added, hence the slack.
+    let rendered_len = |parts: &[Part]| -> Result<usize> { Ok(chunker::render_chunk(1, parts, opts.format, None)?.len()) };
+    let header_len = rendered_len(&[])?;
+    let mut left = opts.budget.saturating_sub(header_len.div_ceil(4) + HEADER_SLACK_TOKENS);
+    let mut parts: Vec<Part> = Vec::new();
+    let mut report = PackReport { budget: opts.budget, used_tokens: 0, included: Vec::new(), dropped: Vec::new() };
+
+    for (e, score) in ranked {
+        if e.is_probably_noise() {
+            report.dropped.push(Dropped { path: e.path.clone(), score, reason: "noise path".into() });
+            continue;
+        }
+        let mut smallest_need = None;
+        let mut picked = None;
+        for (detail, body) in candidate_bodies(e, project_root, &redactor) {
+            let part = Part {
+                path: e.path.clone(),
+                lang: e.lang.clone(),
+                sha1: e.sha1.clone(),
+                size: e.size.to_string(),
+                last_modified: e.last_modified.clone(),
+                summary: e.summary.clone(),
+                part_idx: 1,
+                part_total: 1,
+                token_estimate: chunker::estimate_tokens_fallback(&body),
+                body,
+                detail: Some(detail),
+                see_also: Vec::new(),
+            };
+            let tokens = rendered_len(std::slice::from_ref(&part))?.saturating_sub(header_len).div_ceil(4);
+            smallest_need = Some(tokens);
+            if tokens <= left {
+                picked = Some((part, tokens));
+                break;
+            }
+        }
+        match picked {
+            Some((part, tokens)) => {
+                left -= tokens;
+                report.included.push(Included { path: e.path.clone(), score, detail: part.detail.unwrap_or_default(), tokens });
+                parts.push(part);
+            }
+            None => {
+                let need = smallest_need.unwrap_or(0);
+                report.dropped.push(Dropped {
+                    path: e.path.clone(),
+                    score,
+                    reason: format!("over budget: smallest form needs ~{need} tokens, {left} left"),
+                });
+            }
+        }
+    }
+    // Keep rank order in the output: most important content first.
+    let rendered = chunker::render_chunk(1, &parts, opts.format, None)?;
+    report.used_tokens = chunker::estimate_tokens_fallback(&rendered);
+    crate::util::safe_write(out_path, rendered)
+        .with_context(|| format!("writing {}", out_path.display()))?;
+    Ok(report)
+}
+
+/* ============================== Ranking =============================== */
+
+fn rank_entries<'a>(
+    entries: &'a [FileIntentEntry],
+    churn: &HashMap<String, usize>,
+    w: &PackWeights,
+) -> Vec<(&'a FileIntentEntry, f64)> {
+    let max_exports = entries.iter().map(|e| e.exports.len()).max().unwrap_or(0).max(1) as f64;
+    let max_churn = churn.values().copied().max().unwrap_or(0).max(1) as f64;
+    let max_size = entries.iter().map(|e| e.size).max().unwrap_or(0).max(1) as f64;
+
+    entries
+        .iter()
+        .map(|e| {
+            let role = role_priority(e.role_enum());
+            let exports = e.exports.len() as f64 / max_exports;
+            let churn = churn.get(&e.path).copied().unwrap_or(0) as f64 / max_churn;
+            let size = e.size as f64 / max_size;
+            let score = w.role * role + w.exports * exports + w.churn * churn - w.size * size;
+            (e, (score * 1000.0).round() / 1000.0)
+        })
+        .collect()
+}
+
+/// Bin/Lib/Core first, Test/Doc last.
+fn role_priority(r: Role) -> f64 {
+    match r {
+        Role::Bin => 1.0,
+        Role::Lib => 0.95,
+        Role::Core => 0.9,
+        Role::Ui => 0.6,
+        Role::Other => 0.5,
+        Role::Script => 0.4,
+        Role::Config => 0.35,
+        Role::Test => 0.2,
+        Role::Doc => 0.1,
+    }
+}
+
+/// Distinct sha1 values per path across archived snapshots (1 = never changed).
+fn churn_from_history(history_dir: &Path) -> HashMap<String, usize> {
+    let mut seen: HashMap<String, Vec<String>> = HashMap::new();
+    let Ok(rd) = fs::read_dir(history_dir) else { return HashMap::new() };
+    let mut snaps: Vec<_> = rd
+        .filter_map(|d| d.ok().map(|d| d.path()))
+        .filter(|p| p.extension().and_then(|e| e.to_str()) == Some("jsonl"))
+        .collect();
+    snaps.sort();
+    for snap in snaps {
+        let Ok(entries) = scan::read_index(&snap) else { continue };
+        for e in entries {
+            let shas = seen.entry(e.path).or_default();
+            if !shas.contains(&e.sha1) {
+                shas.push(e.sha1);
+            }
+        }
+    }
+    seen.into_iter().map(|(p, v)| (p, v.len())).collect()
+}
+
+/* ========================== Degrading bodies ========================== */
+
+/// Candidate bodies from richest to leanest. The first that fits wins.
+fn candidate_bodies(e: &FileIntentEntry, root: &Path, redactor: &Redactor) -> Vec<(&'static str, String)> {
+    let mut out = Vec::with_capacity(3);
+    // Virtual entries (`index.html#script-1`) have no file of their own.
+    let full = if e.path.contains('#') { None } else { fs::read_to_string(root.join(&e.path)).ok() };
+    if let Some(src) = &full {
+        out.push(("full", redactor.redact(src.trim_end()).0));
+    }
+    if !e.snippet.trim().is_empty() {
+        out.push(("snippet", e.snippet.clone()));
+    }
+    let sigs = full
+        .as_deref()
+        .filter(|_| e.lang.eq_ignore_ascii_case("rust"))
+        .and_then(rust_signatures)
+        .unwrap_or_else(|| e.exports.join("\n"));
+    if !sigs.trim().is_empty() {
+        out.push(("signatures", sigs));
+    }
+    out
+}
+
+/// Non-test fn signatures + type names via the functions/types collectors.
+fn rust_signatures(src: &str) -> Option<String> {
+    let ast = syn::parse_file(src).ok()?;
+    let mut types = TypeCollector::default();
+    types.visit_file(&ast);
+    let mut fns = FnCollector::default();
+    fns.visit_file(&ast);
+
+    let mut lines = Vec::new();
+    for d in types.out {
+        match d {
+            Decl::Struct(s) => lines.push(format!("{}struct {}", if s.public { "pub " } else { "" }, s.name)),
+            Decl::Enum(e) => lines.push(format!("{}enum {}", if e.public { "pub " } else { "" }, e.name)),
+        }
+    }
+    for (kind, sig) in fns.out {
+        let sig = sig.trim_end_matches(" {");
+        match kind {
+            Kind::Test => {}
+            // Free fns come back without visibility; methods carry `Type::pub fn`.
+            Kind::Public if !sig.contains("pub fn") => lines.push(format!("pub {sig}")),
+            _ => lines.push(sig.to_string()),
+        }
+    }
+    if lines.is_empty() { None } else { Some(lines.join("\n")) }
+}
+
+/* ================================== Tests ================================== */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(path: &str, role: Role, exports: usize, size: usize) -> FileIntentEntry {
+        let mut e = FileIntentEntry {
+            path: path.into(),
+            size,
+            exports: (0..exports).map(|i| format!("f{i}")).collect(),
+            ..Default::default()
+        };
+        e.set_role_enum(role);
+        e
+    }
+
+    #[test]
+    fn weights_parse_subset() {
+        let w = PackWeights::parse("churn=2, size=0").unwrap();
+        assert!((w.churn - 2.0).abs() < f64::EPSILON);
+        assert!(w.size.abs() < f64::EPSILON);
+        assert!((w.role - 1.0).abs() < f64::EPSILON);
+        assert!(PackWeights::parse("speed=1").is_err());
+    }
+
+    #[test]
+    fn lib_outranks_docs_and_tests() {
+        let entries = vec![
+            entry("docs/guide.md", Role::Doc, 0, 100),
+            entry("tests/it.rs", Role::Test, 3, 100),
+            entry("src/lib.rs", Role::Lib, 3, 100),
+        ];
+        let mut ranked = rank_entries(&entries, &HashMap::new(), &PackWeights::default());
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+        assert_eq!(ranked[0].0.path, "src/lib.rs");
+        assert_eq!(ranked[2].0.path, "docs/guide.md");
+    }
+
+    #[test]
+    fn rendered_pack_stays_within_budget() {
+        let dir = std::env::temp_dir().join(format!("indexer-budget-test-{}", std::process::id()));
+        fs::create_dir_all(dir.join("src")).unwrap();
+        let mut entries = Vec::new();
+        for i in 0..40 {
+            let path = format!("src/m{i}.rs");
+            // Short bodies: framing is most of what gets rendered.
+            fs::write(dir.join(&path), format!("pub fn f{i}() {{}}\n")).unwrap();
+            let mut e = entry(&path, Role::Core, 1, 20);
+            e.lang = "rust".into();
+            e.sha1 = format!("{i:040}");
+            e.summary = Some("Core module with a summary line.".into());
+            entries.push(e);
+        }
+        let index = dir.join("index.jsonl");
+        fs::write(&index, entries.iter().map(|e| serde_json::to_string(e).unwrap() + "\n").collect::<String>()).unwrap();
+
+        for format in [ChunkFormat::Md, ChunkFormat::Xml, ChunkFormat::Jsonl, ChunkFormat::Txt] {
+            let out = dir.join(format!("pack.{}", format.ext()));
+            let opts = PackOptions { budget: 600, format, ..PackOptions::default() };
+            let r = build_budget_pack(&index, &dir, &dir.join("history"), &out, &opts).unwrap();
+            let rendered = chunker::estimate_tokens_fallback(&fs::read_to_string(&out).unwrap());
+            assert!(rendered <= 600, "{format:?}: {rendered} tokens rendered");
+            assert_eq!(r.used_tokens, rendered);
+            assert!(!r.included.is_empty() && !r.dropped.is_empty(), "{format:?}: {r:?}");
+        }
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn signatures_skip_tests() {
+        let src = "pub struct A;\npub fn go(x: u8) -> u8 { x }\n#[cfg(test)] mod tests { #[test] fn t() {} }\n";
+        let sigs = rust_signatures(src).unwrap();
+        assert!(sigs.contains("pub struct A"));
+        assert!(sigs.contains("pub fn go"), "{sigs}");
+        assert!(!sigs.contains("fn t"));
+    }
+}
@@ -0,0 +1,285 @@
+// indexer/src/changelog.rs
+//! Markdown changelog for a `diff::diff_indexes` payload.
+//!
+//! - Files are grouped by package (dir holding a `Cargo.toml` / `package.json` /
+//!   `pyproject.toml`) or, outside packages, by top-level dir.
+//! - Each group lists added / removed / renamed / copied / modified files with
+//!   size and LOC deltas; role and module changes are called out inline.
+//! - A closing section summarises tag churn across modified files.
+
+use serde_json::Value;
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+};
+
+use crate::file_intent_entry::FileIntentEntry;
+use crate::util;
+
+/// Manifest file names that mark a package root.
+const PACKAGE_MANIFESTS: [&str; 3] = ["Cargo.toml", "package.json", "pyproject.toml"];
+
+/// Package root dirs (`crates/core/`) found among the indexed manifests. The
+/// repo root itself is not a package root: its files group by top-level dir.
+pub fn package_roots<'a>(entries: impl IntoIterator<Item = &'a FileIntentEntry>) -> Vec<String> {
+    package_roots_of(entries.into_iter().map(|e| e.path.as_str()))
+}
+
+/// [`package_roots`] over bare paths.
+pub(crate) fn package_roots_of<'a>(paths: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    let mut roots: Vec<String> = paths
+        .into_iter()
+        .filter_map(|path| {
+            let (dir, file) = path.rsplit_once('/')?;
+            PACKAGE_MANIFESTS.contains(&file).then(|| format!("{dir}/"))
+        })
+        .collect();
+    roots.sort();
+    roots.dedup();
+    roots
+}
+
+/// Render `diff` as a Markdown changelog. `title` names the two snapshots;
+/// `packages` comes from [`package_roots`] (empty = group by top-level dir).
+pub fn changelog_markdown(diff: &Value, title: &str, packages: &[String]) -> String {
+    let mut groups: BTreeMap<String, Group> = BTreeMap::new();
+
+    let mut size_delta: i64 = 0;
+    let mut loc_delta: i64 = 0;
+    let mut tag_churn: BTreeMap<String, (usize, usize)> = BTreeMap::new();
+
+    for f in items(diff, "added") {
+        size_delta += int(&f["size"]);
+        loc_delta += int(&f["lines_total"]);
+        group_of(&mut groups, packages, str_of(&f["path"])).added.push(file_line(f));
+    }
+    for f in items(diff, "removed") {
+        size_delta -= int(&f["size"]);
+        loc_delta -= int(&f["lines_total"]);
+        group_of(&mut groups, packages, str_of(&f["path"])).removed.push(file_line(f));
+    }
+    for f in items(diff, "renamed") {
+        group_of(&mut groups, packages, str_of(&f["to"])).renamed.push(format!(
+            "`{}` → `{}` (identical)",
+            str_of(&f["from"]),
+            str_of(&f["to"])
+        ));
+    }
+    for f in items(diff, "renamed_with_changes") {
+        group_of(&mut groups, packages, str_of(&f["to"])).renamed.push(format!(
+            "`{}` → `{}` (similarity {})",
+            str_of(&f["from"]),
+            str_of(&f["to"]),
+            f["similarity"]
+        ));
+    }
+    for f in items(diff, "copied") {
+        size_delta += int(&f["size"]);
+        group_of(&mut groups, packages, str_of(&f["to"])).copied.push(format!(
+            "`{}` → `{}` (similarity {})",
+            str_of(&f["from"]),
+            str_of(&f["to"]),
+            f["similarity"]
+        ));
+    }
+    for f in items(diff, "modified") {
+        let d = &f["deltas"];
+        size_delta += int(&d["size"]);
+        loc_delta += int(&d["lines_total"]);
+        for t in items(d, "tags_added") {
+            tag_churn.entry(str_of(t).to_string()).or_default().0 += 1;
+        }
+        for t in items(d, "tags_removed") {
+            tag_churn.entry(str_of(t).to_string()).or_default().1 += 1;
+        }
+        group_of(&mut groups, packages, str_of(&f["path"])).modified.push(modified_line(f));
+    }
+
+    let mut out = String::new();
+    let _ = writeln!(out, "# Changelog — {title}\n");
+    let s = &diff["summary"];
+    let _ = writeln!(
+        out,
+        "> +{} added • −{} removed • ~{} modified • {} renamed ({} with changes) • {} copied • size {} • LOC {}\n",
+        s["added"],
+        s["removed"],
+        s["modified"],
+        int(&s["renamed"]) + int(&s["renamed_with_changes"]),
+        int(&s["renamed_with_changes"]),
+        int(&s["copied"]),
+        signed_bytes(size_delta),
+        signed(loc_delta)
+    );
+
+    if groups.is_empty() {
+        let _ = writeln!(out, "_No file changes._");
+        return out;
+    }
+
+    for (key, g) in &groups {
+        let _ = writeln!(out, "## `{key}`\n");
+        for (label, lines) in [
+            ("Added", &g.added),
+            ("Removed", &g.removed),
+            ("Renamed", &g.renamed),
+            ("Copied", &g.copied),
+            ("Modified", &g.modified),
+        ] {
+            if lines.is_empty() {
+                continue;
+            }
+            let _ = writeln!(out, "**{label}**\n");
+            for l in lines {
+                let _ = writeln!(out, "- {l}");
+            }
+            let _ = writeln!(out);
+        }
+    }
+
+    if !tag_churn.is_empty() {
+        let _ = writeln!(out, "## Tag churn\n");
+        for (tag, (added, removed)) in &tag_churn {
+            let _ = writeln!(out, "- `{tag}`: +{added} −{removed}");
+        }
+        let _ = writeln!(out);
+    }
+    out
+}
+
+/* ================================ Helpers ================================ */
+
+#[derive(Default)]
+struct Group {
+    added: Vec<String>,
+    removed: Vec<String>,
+    renamed: Vec<String>,
+    copied: Vec<String>,
+    modified: Vec<String>,
+}
+
+fn group_of<'g>(groups: &'g mut BTreeMap<String, Group>, packages: &[String], path: &str) -> &'g mut Group {
+    groups.entry(group_key(path, packages)).or_default()
+}
+
+/// Deepest package root containing `path`, else its top-level dir (`./` for root files).
+pub(crate) fn group_key(path: &str, packages: &[String]) -> String {
+    if let Some(pkg) = packages.iter().filter(|p| path.starts_with(p.as_str())).max_by_key(|p| p.len()) {
+        return pkg.clone();
+    }
+    match path.split_once('/') {
+        Some((top, _)) => format!("{top}/"),
+        None => "./".to_string(),
+    }
+}
+
+fn items<'a>(v: &'a Value, key: &str) -> impl Iterator<Item = &'a Value> {
+    v[key].as_array().into_iter().flatten()
+}
+
+fn str_of(v: &Value) -> &str {
+    v.as_str().unwrap_or_default()
+}
+
+fn int(v: &Value) -> i64 {
+    v.as_i64().unwrap_or(0)
+}
+
+fn signed(n: i64) -> String {
+    if n > 0 { format!("+{n}") } else { n.to_string() }
+}
+
+fn signed_bytes(n: i64) -> String {
+    let sign = match n.signum() {
+        1 => "+",
+        -1 => "−",
+        _ => "±",
+    };
+    format!("{sign}{}", util::humanize_bytes(n.unsigned_abs()))
+}
+
+/// `` `src/a.rs` (rust, 1.2 KB, 40 lines) `` for added/removed files.
+fn file_line(f: &Value) -> String {
+    format!(
+        "`{}` ({}, {}, {} lines)",
+        str_of(&f["path"]),
+        str_of(&f["lang"]),
+        util::humanize_bytes(f["size"].as_u64().unwrap_or(0)),
+        int(&f["lines_total"])
+    )
+}
+
+/// `` `src/a.rs`: +120 B, +4 lines · role lib → core · tags +x −y ``
+fn modified_line(f: &Value) -> String {
+    let (b, a, d) = (&f["before"], &f["after"], &f["deltas"]);
+    let mut parts = vec![format!("{}, {} lines", signed_bytes(int(&d["size"])), signed(int(&d["lines_total"])))];
+    if d["role_changed"].as_bool().unwrap_or(false) {
+        parts.push(format!("**role {} → {}**", str_of(&b["role"]), str_of(&a["role"])));
+    }
+    if d["module_changed"].as_bool().unwrap_or(false) {
+        parts.push(format!("**module `{}` → `{}`**", str_of(&b["module"]), str_of(&a["module"])));
+    }
+    if d["lang_changed"].as_bool().unwrap_or(false) {
+        parts.push(format!("lang {} → {}", str_of(&b["lang"]), str_of(&a["lang"])));
+    }
+    let tags: Vec<String> = items(d, "tags_added")
+        .map(|t| format!("+{}", str_of(t)))
+        .chain(items(d, "tags_removed").map(|t| format!("−{}", str_of(t))))
+        .collect();
+    if !tags.is_empty() {
+        parts.push(format!("tags {}", tags.join(" ")));
+    }
+    format!("`{}`: {}", str_of(&f["path"]), parts.join(" · "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diff::diff_indexes;
+
+    fn entry(path: &str, sha1: &str, size: usize, lines: usize) -> FileIntentEntry {
+        FileIntentEntry {
+            path: path.into(),
+            lang: "rust".into(),
+            sha1: sha1.into(),
+            size,
+            lines_total: lines,
+            role: "lib".into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn groups_by_package_and_top_level_dir() {
+        let mut changed = entry("crates/core/src/lib.rs", "2", 1500, 50);
+        changed.role = "core".into();
+        changed.tags = vec!["async".into()];
+        let old = vec![
+            entry("crates/core/Cargo.toml", "m", 10, 1),
+            entry("crates/core/src/lib.rs", "1", 1000, 40),
+            entry("docs/old.md", "3", 300, 9),
+        ];
+        let new = vec![
+            entry("crates/core/Cargo.toml", "m", 10, 1),
+            changed,
+            entry("README.md", "4", 200, 5),
+        ];
+        let md = changelog_markdown(&diff_indexes(&old, &new), "a → b", &package_roots(&new));
+
+        assert!(md.starts_with("# Changelog — a → b"), "{md}");
+        assert!(md.contains("## `crates/core/`"), "{md}");
+        assert!(
+            md.contains("- `crates/core/src/lib.rs`: +500 B, +10 lines · **role lib → core** · tags +async"),
+            "{md}"
+        );
+        assert!(md.contains("## `./`\n\n**Added**\n\n- `README.md` (rust, 200 B, 5 lines)"), "{md}");
+        assert!(md.contains("## `docs/`\n\n**Removed**"), "{md}");
+        assert!(md.contains("- `async`: +1 −0"), "{md}");
+    }
+
+    #[test]
+    fn empty_diff_says_so() {
+        let e = vec![entry("src/a.rs", "1", 1, 1)];
+        let md = changelog_markdown(&diff_indexes(&e, &e), "x", &[]);
+        assert!(md.contains("_No file changes._"), "{md}");
+    }
+}
@@ -1,14 +1,24 @@
 // indexer/src/chunker.rs
 //! Chunk builder: converts a JSONL index (FileIntentEntry per line) into
 //! GPT-ready paste chunks, enforcing token caps and splitting large files.
+//! Output framing is pluggable (Markdown, XML-tagged, JSONL, plain text);
+//! every format shares the same split + packing logic.
+//! An optional preamble (project one-liner, module list, chunk TOC, "chunk N
+//! of M" navigation) makes each chunk self-describing when pasted on its own.
+//! Every run records which file parts went into which chunk in
+//! `chunks/manifest.json`.
 
 use anyhow::{Context, Result};
 use chrono::Utc;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+
+use crate::dep_graph::{DepGraph, DepNode};
 use std::{
     cmp,
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt::Write as _,
     fs::{self, File},
-    io::{BufRead, BufReader, Write},
+    io::{BufRead, BufReader, Write as _},
     path::Path,
 };
 
@@ -23,17 +33,122 @@ struct FileIntentEntry {
     pub snippet: String,
     #[serde(default)]
     pub token_estimate: usize,
+    #[serde(default)]
+    pub imports: Vec<String>,
+}
+
+/// Output framing for paste chunks. All formats share the same packing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum ChunkFormat {
+    /// `## path` headings + fenced code (default).
+    #[default]
+    Md,
+    /// `<document path=".." lang=".." sha1=".."><source>..</source></document>`.
+    Xml,
+    /// One JSON record per part.
+    Jsonl,
+    /// Plain text with BEGIN/END delimiter lines.
+    Txt,
+}
+
+impl ChunkFormat {
+    /// Parse a CLI label (case-insensitive). Unknown -> `None`.
+    pub fn from_str_ic(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "md" | "markdown" => Some(ChunkFormat::Md),
+            "xml" => Some(ChunkFormat::Xml),
+            "jsonl" | "json" => Some(ChunkFormat::Jsonl),
+            "txt" | "text" | "plain" => Some(ChunkFormat::Txt),
+            _ => None,
+        }
+    }
+
+    /// File extension used for chunk files in this format.
+    pub fn ext(self) -> &'static str {
+        match self {
+            ChunkFormat::Md => "md",
+            ChunkFormat::Xml => "xml",
+            ChunkFormat::Jsonl => "jsonl",
+            ChunkFormat::Txt => "txt",
+        }
+    }
+}
+
+/// File ordering before packing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum ChunkOrder {
+    /// Plain path order (default).
+    #[default]
+    Path,
+    /// Cluster import-connected files and put definitions before uses,
+    /// so tightly-coupled files land in the same chunk.
+    Deps,
+}
+
+impl ChunkOrder {
+    /// Parse a CLI label (case-insensitive). Unknown -> `None`.
+    pub fn from_str_ic(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "path" => Some(ChunkOrder::Path),
+            "deps" | "dependency" | "dependencies" => Some(ChunkOrder::Deps),
+            _ => None,
+        }
+    }
+}
+
+/// Chunking knobs (mirrors `scan::ScanOptions`).
+#[derive(Clone, Debug)]
+pub struct ChunkOptions {
+    /// Approximate token cap per chunk (min 256).
+    pub token_cap: usize,
+    /// Output framing.
+    pub format: ChunkFormat,
+    /// File ordering before packing.
+    pub order: ChunkOrder,
+    /// Preamble token allowance; `None` = no preamble. Counts against `token_cap`.
+    pub preamble: Option<usize>,
+}
+
+impl Default for ChunkOptions {
+    fn default() -> Self {
+        Self {
+            token_cap: 15_000,
+            format: ChunkFormat::Md,
+            order: ChunkOrder::Path,
+            preamble: None,
+        }
+    }
 }
 
+/// Default preamble allowance when `--preamble` is given without a value.
+pub const DEFAULT_PREAMBLE_TOKENS: usize = 400;
+
+const MAX_FILES_PER_CHUNK: usize = 120;
+pub(crate) const MAX_SECTION_CHARS: usize = 32_000;
+pub(crate) const TARGET_SECTION_TOKENS: usize = 800;
+
 /// Build markdown "paste chunks" for LLMs from a JSONL index.
 /// - `index_path`: path to JSONL with one FileIntentEntry per line
 /// - `out_prefix`: prefix for output files, e.g. ".gpt/chunks/paste_"
 /// - `token_cap`: desired approximate token cap per chunk (min 256)
 pub fn chunk_index_for_gpt(index_path: &Path, out_prefix: &str, token_cap: usize) -> Result<()> {
-    let token_cap = token_cap.max(256);
-    const MAX_FILES_PER_CHUNK: usize = 120;
-    const MAX_SECTION_CHARS: usize = 32_000;
-    const TARGET_SECTION_TOKENS: usize = 800;
+    let opts = ChunkOptions { token_cap, ..ChunkOptions::default() };
+    chunk_index_with_opts(index_path, out_prefix, &opts).map(|_| ())
+}
+
+/// Full-control chunker. Returns the number of chunk files written.
+pub fn chunk_index_with_opts(index_path: &Path, out_prefix: &str, opts: &ChunkOptions) -> Result<usize> {
+    let entries = load_entries(index_path)
+        .with_context(|| format!("reading index at {}", index_path.display()))?;
+    chunk_entries(entries, out_prefix, opts)
+}
+
+/// Pack already-loaded entries into chunk files named `<out_prefix><N>.<ext>`.
+fn chunk_entries(mut entries: Vec<FileIntentEntry>, out_prefix: &str, opts: &ChunkOptions) -> Result<usize> {
+    let token_cap = opts.token_cap.max(256);
+    // The preamble lives inside the cap; keep at least 256 tokens for content.
+    let allowance = opts.preamble.map(|a| a.min(token_cap.saturating_sub(256)));
+    let content_cap = token_cap - allowance.unwrap_or(0);
 
     if let Some(parent) = Path::new(out_prefix).parent() {
         if !parent.as_os_str().is_empty() {
@@ -42,31 +157,86 @@ pub fn chunk_index_for_gpt(index_path: &Path, out_prefix: &str, token_cap: usize
         }
     }
 
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    entries.dedup_by(|a, b| a.path == b.path);
+    let chunks = plan(&entries, content_cap, opts.order, opts.format);
+
+    let context = allowance.map(|_| ProjectContext::from_entries(&entries));
+    let total = chunks.len();
+    for (i, parts) in chunks.iter().enumerate() {
+        let preamble = match (&context, allowance) {
+            (Some(ctx), Some(a)) => Some(Preamble::build(ctx, out_prefix, opts.format, i + 1, total, parts, a)),
+            _ => None,
+        };
+        write_chunk(out_prefix, i + 1, parts, opts.format, preamble.as_ref())?;
+    }
+    record_chunks(out_prefix, opts.format, &chunks)?;
+    Ok(total)
+}
+
+/// Packed chunks (no preamble) for the index entries accepted by
+/// `keep(path, sha1)`, without writing anything. Used by `session next`.
+pub(crate) fn plan_chunks_filtered(
+    index_path: &Path,
+    opts: &ChunkOptions,
+    keep: &dyn Fn(&str, &str) -> bool,
+) -> Result<Vec<Vec<Part>>> {
     let mut entries = load_entries(index_path)
         .with_context(|| format!("reading index at {}", index_path.display()))?;
+    entries.retain(|e| keep(&e.path, &e.sha1));
     entries.sort_by(|a, b| a.path.cmp(&b.path));
+    entries.dedup_by(|a, b| a.path == b.path);
+    Ok(plan(&entries, opts.token_cap.max(256), opts.order, opts.format))
+}
+
+/// Order, split, pack and cross-link `entries` (sorted, deduped) into chunks.
+fn plan(entries: &[FileIntentEntry], content_cap: usize, order: ChunkOrder, format: ChunkFormat) -> Vec<Vec<Part>> {
+    let graph = {
+        let nodes: Vec<DepNode<'_>> = entries
+            .iter()
+            .map(|e| DepNode { path: &e.path, lang: &e.lang, imports: &e.imports })
+            .collect();
+        DepGraph::build(&nodes)
+    };
+    let order: Vec<usize> = match order {
+        ChunkOrder::Path => (0..entries.len()).collect(),
+        ChunkOrder::Deps => graph.clustered_order(),
+    };
 
+    // Parts stay in entry order (path or dependency order), part_idx ascending.
+    // Until packing decides the chunks, every part is sized as if it pointed
+    // at each import neighbour in a chunk of its own (the longest see-also).
     let mut expanded: Vec<Part> = Vec::new();
-    for e in entries.into_iter() {
-        let parts = split_entry_into_parts(&e, TARGET_SECTION_TOKENS, MAX_SECTION_CHARS);
+    for &i in &order {
+        let mut parts = split_entry_into_parts(&entries[i], TARGET_SECTION_TOKENS, MAX_SECTION_CHARS);
+        let worst: Vec<(usize, String)> =
+            graph.neighbours(i).into_iter().enumerate().map(|(k, nb)| (usize::MAX - k, graph.paths[nb].clone())).collect();
+        for p in &mut parts {
+            p.see_also.clone_from(&worst);
+        }
         expanded.extend(parts);
     }
-    expanded.sort_by(|a, b| {
-        let o = a.path.cmp(&b.path);
-        if o == std::cmp::Ordering::Equal {
-            a.part_idx.cmp(&b.part_idx)
-        } else {
-            o
-        }
-    });
 
-    let mut chunk_idx = 1usize;
+    let mut chunks = pack_parts(expanded, content_cap, format);
+    for p in chunks.iter_mut().flatten() {
+        p.see_also.clear();
+    }
+    link_see_also(&mut chunks, &graph);
+    chunks
+}
+
+/// Greedy packing: keep part order, start a new chunk on token or file-count
+/// overflow. Tokens are counted as rendered in `format`: the chunk header
+/// comes off `token_cap`, and each part costs its body plus its framing.
+pub(crate) fn pack_parts(expanded: Vec<Part>, token_cap: usize, format: ChunkFormat) -> Vec<Vec<Part>> {
+    let mut chunks: Vec<Vec<Part>> = Vec::new();
     let mut cur_tokens = 0usize;
     let mut cur_files = 0usize;
     let mut cur_vec: Vec<Part> = Vec::new();
+    let token_cap = token_cap.saturating_sub(header_tokens(format));
 
-    for part in expanded.into_iter() {
-        let part_tokens = cmp::max(1, part.token_estimate);
+    for part in expanded {
+        let part_tokens = cmp::max(1, framed_tokens(&part, format));
 
         // new file if last part in the current chunk has a different path
         let will_add_new_file = match cur_vec.last() {
@@ -78,9 +248,7 @@ pub fn chunk_index_for_gpt(index_path: &Path, out_prefix: &str, token_cap: usize
         let too_many_files = will_add_new_file && cur_files >= MAX_FILES_PER_CHUNK;
 
         if !cur_vec.is_empty() && (would_overflow || too_many_files) {
-            write_chunk(out_prefix, chunk_idx, &cur_vec)?;
-            chunk_idx += 1;
-            cur_vec.clear();
+            chunks.push(std::mem::take(&mut cur_vec));
             cur_tokens = 0;
             cur_files = 0;
         }
@@ -93,10 +261,255 @@ pub fn chunk_index_for_gpt(index_path: &Path, out_prefix: &str, token_cap: usize
     }
 
     if !cur_vec.is_empty() {
-        write_chunk(out_prefix, chunk_idx, &cur_vec)?;
+        chunks.push(cur_vec);
     }
+    chunks
+}
 
-    Ok(())
+/// Annotate the first part of each file section with the chunks (1-based)
+/// holding its import neighbours, when those live in a different chunk.
+fn link_see_also(chunks: &mut [Vec<Part>], graph: &DepGraph) {
+    let index_of: HashMap<&str, usize> = graph
+        .paths
+        .iter()
+        .enumerate()
+        .map(|(i, p)| (p.as_str(), i))
+        .collect();
+    let mut chunks_of: HashMap<usize, BTreeSet<usize>> = HashMap::new();
+    for (ci, parts) in chunks.iter().enumerate() {
+        for p in parts {
+            if let Some(&n) = index_of.get(p.path.as_str()) {
+                chunks_of.entry(n).or_default().insert(ci + 1);
+            }
+        }
+    }
+
+    for (ci, parts) in chunks.iter_mut().enumerate() {
+        let here = ci + 1;
+        let mut last_path: Option<String> = None;
+        for p in parts.iter_mut() {
+            if last_path.as_deref() == Some(p.path.as_str()) {
+                continue;
+            }
+            last_path = Some(p.path.clone());
+            let Some(&n) = index_of.get(p.path.as_str()) else { continue };
+            let mut refs: BTreeSet<(usize, String)> = BTreeSet::new();
+            for nb in graph.neighbours(n) {
+                for &c in chunks_of.get(&nb).into_iter().flatten() {
+                    if c != here {
+                        refs.insert((c, graph.paths[nb].clone()));
+                    }
+                }
+            }
+            p.see_also = refs.into_iter().collect();
+        }
+    }
+}
+
+/* ================================== Preamble ======================================= */
+
+/// Per-run project facts shared by every chunk's preamble.
+struct ProjectContext {
+    /// `name — description` from a root manifest, else the README summary.
+    project: String,
+    /// One line per top-level dir: `` `src/` (12): chunker, scan, … ``.
+    modules: Vec<String>,
+}
+
+impl ProjectContext {
+    fn from_entries(entries: &[FileIntentEntry]) -> Self {
+        Self { project: project_one_liner(entries), modules: module_lines(entries) }
+    }
+}
+
+/// Orientation block prepended to a chunk; already trimmed to its allowance.
+#[derive(Clone, Debug)]
+pub(crate) struct Preamble {
+    pub(crate) index: usize,
+    pub(crate) total: usize,
+    pub(crate) prev: Option<String>,
+    pub(crate) next: Option<String>,
+    pub(crate) project: String,
+    pub(crate) modules: Vec<String>,
+    pub(crate) toc: Vec<String>,
+}
+
+impl Preamble {
+    /// Fill in priority order — navigation, project, TOC, modules — until the
+    /// token allowance is used up; overflowing lists end with "… +N more".
+    fn build(
+        ctx: &ProjectContext,
+        out_prefix: &str,
+        format: ChunkFormat,
+        index: usize,
+        total: usize,
+        parts: &[Part],
+        allowance: usize,
+    ) -> Self {
+        let file_name = |n: usize| {
+            let full = format!("{}{}.{}", out_prefix, n, format.ext());
+            Path::new(&full)
+                .file_name()
+                .map_or(full.clone(), |f| f.to_string_lossy().into_owned())
+        };
+        let mut pre = Preamble {
+            index,
+            total,
+            prev: (index > 1).then(|| file_name(index - 1)),
+            next: (index < total).then(|| file_name(index + 1)),
+            project: ctx.project.clone(),
+            modules: Vec::new(),
+            toc: Vec::new(),
+        };
+
+        let toc: Vec<String> = file_sections(parts)
+            .into_iter()
+            .map(|sec| {
+                let p = &sec[0];
+                let sum = p.summary.as_deref().map(str::trim).unwrap_or("");
+                let parts_note = if p.part_total > 1 {
+                    format!(" parts {}-{}/{}", p.part_idx, sec[sec.len() - 1].part_idx, p.part_total)
+                } else {
+                    String::new()
+                };
+                if sum.is_empty() {
+                    format!("`{}` [{}]{}", p.path, p.lang, parts_note)
+                } else {
+                    format!("`{}` [{}]{} — {}", p.path, p.lang, parts_note, clamp_chars(sum, 80))
+                }
+            })
+            .collect();
+
+        let mut left = allowance.saturating_sub(estimate_tokens_fallback(&pre.render_plain()));
+        pre.toc = take_within(toc, &mut left);
+        pre.modules = take_within(ctx.modules.clone(), &mut left);
+        pre
+    }
+
+    fn nav_line(&self) -> String {
+        let mut s = format!("chunk {} of {}", self.index, self.total);
+        if let Some(p) = &self.prev {
+            let _ = write!(s, " • prev: {}", p);
+        }
+        if let Some(n) = &self.next {
+            let _ = write!(s, " • next: {}", n);
+        }
+        s
+    }
+
+    /// Plain-text rendering; also the yardstick for the allowance.
+    fn render_plain(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "{}", self.nav_line());
+        let _ = writeln!(out, "project: {}", self.project);
+        if !self.modules.is_empty() {
+            let _ = writeln!(out, "modules:");
+            for m in &self.modules {
+                let _ = writeln!(out, "  {}", m.replace('`', ""));
+            }
+        }
+        if !self.toc.is_empty() {
+            let _ = writeln!(out, "in this chunk:");
+            for t in &self.toc {
+                let _ = writeln!(out, "  {}", t.replace('`', ""));
+            }
+        }
+        out
+    }
+}
+
+/// Take lines while they fit in `left` tokens (line length / 4, as the chunk
+/// estimate does); the rest collapse into a trailing "… +N more" line.
+fn take_within(lines: Vec<String>, left: &mut usize) -> Vec<String> {
+    let total = lines.len();
+    let mut out = Vec::new();
+    for line in lines {
+        let cost = line.len() / 4 + 1;
+        // Reserve room for the "… +N more" marker unless this is the last line.
+        let reserve = if out.len() + 1 < total { 3 } else { 0 };
+        if cost + reserve > *left {
+            break;
+        }
+        *left -= cost;
+        out.push(line);
+    }
+    if out.len() < total && *left >= 3 {
+        out.push(format!("… +{} more", total - out.len()));
+        *left -= 3;
+    }
+    out
+}
+
+/// `name — description` from a root Cargo.toml / pyproject.toml / package.json
+/// snippet; falls back to the root README summary.
+fn project_one_liner(entries: &[FileIntentEntry]) -> String {
+    for manifest in ["Cargo.toml", "pyproject.toml", "package.json"] {
+        let Some(e) = entries.iter().find(|e| e.path == manifest) else { continue };
+        let name = manifest_field(&e.snippet, "name");
+        let desc = manifest_field(&e.snippet, "description");
+        match (name, desc) {
+            (Some(n), Some(d)) => return format!("{} — {}", n, d),
+            (Some(n), None) => return n,
+            (None, Some(d)) => return d,
+            (None, None) => {}
+        }
+    }
+    entries
+        .iter()
+        .find(|e| e.path.eq_ignore_ascii_case("README.md"))
+        .and_then(|e| e.summary.as_deref())
+        .map(|s| clamp_chars(s.trim(), 160))
+        .filter(|s| !s.is_empty())
+        .unwrap_or_else(|| format!("{} indexed files", entries.len()))
+}
+
+/// First `key = "v"` (TOML) or `"key": "v"` (JSON) value in `snippet`.
+fn manifest_field(snippet: &str, key: &str) -> Option<String> {
+    snippet.lines().find_map(|l| {
+        let l = l.trim();
+        let rest = l
+            .strip_prefix(key)
+            .or_else(|| l.strip_prefix(&format!("\"{}\"", key)))?;
+        let val = rest.trim_start().strip_prefix(['=', ':'])?.trim();
+        let val = val.trim_end_matches(',').trim().trim_matches('"').trim();
+        (!val.is_empty()).then(|| val.to_string())
+    })
+}
+
+/// One line per top-level directory (root files grouped under `./`), listing
+/// file stems in path order.
+fn module_lines(entries: &[FileIntentEntry]) -> Vec<String> {
+    let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for e in entries {
+        let (group, rest) = match e.path.split_once('/') {
+            Some((g, r)) => (format!("{}/", g), r),
+            None => ("./".to_string(), e.path.as_str()),
+        };
+        // Root files keep their full name (`Cargo.toml`, not `Cargo`).
+        let stem = match Path::new(rest).file_stem() {
+            Some(s) if group != "./" => s.to_string_lossy().into_owned(),
+            _ => rest.to_string(),
+        };
+        groups.entry(group).or_default().push(stem);
+    }
+    groups
+        .into_iter()
+        .map(|(g, stems)| {
+            let n = stems.len();
+            let shown: Vec<&str> = stems.iter().take(12).map(String::as_str).collect();
+            let more = if n > shown.len() { format!(", … +{}", n - shown.len()) } else { String::new() };
+            format!("`{}` ({}): {}{}", g, n, shown.join(", "), more)
+        })
+        .collect()
+}
+
+fn clamp_chars(s: &str, max: usize) -> String {
+    if s.chars().count() <= max {
+        return s.to_string();
+    }
+    let mut t: String = s.chars().take(max).collect();
+    t.push('…');
+    t
 }
 
 /* ================================ Loading & Splitting ================================ */
@@ -130,18 +543,24 @@ fn load_entries(index_path: &Path) -> Result<Vec<FileIntentEntry>> {
     Ok(out)
 }
 
+/// One renderable slice of a file. Crate-visible so other packers
+/// (e.g. `budget_pack`) can reuse the chunk renderers.
 #[derive(Debug, Clone)]
-struct Part {
-    path: String,
-    lang: String,
-    sha1: String,
-    size: String,
-    last_modified: String,
-    summary: Option<String>,
-    part_idx: usize,
-    part_total: usize,
-    body: String,
-    token_estimate: usize,
+pub(crate) struct Part {
+    pub(crate) path: String,
+    pub(crate) lang: String,
+    pub(crate) sha1: String,
+    pub(crate) size: String,
+    pub(crate) last_modified: String,
+    pub(crate) summary: Option<String>,
+    pub(crate) part_idx: usize,
+    pub(crate) part_total: usize,
+    pub(crate) body: String,
+    pub(crate) token_estimate: usize,
+    /// What `body` holds when it is not the indexed snippet ("full", "signatures", ...).
+    pub(crate) detail: Option<&'static str>,
+    /// (chunk number, path) of import neighbours packed into other chunks.
+    pub(crate) see_also: Vec<(usize, String)>,
 }
 
 fn split_entry_into_parts(
@@ -149,34 +568,57 @@ fn split_entry_into_parts(
     target_tokens: usize,
     hard_char_cap: usize,
 ) -> Vec<Part> {
-    let snippet = if e.snippet.len() > hard_char_cap {
-        let mut s = String::with_capacity(hard_char_cap + 64);
-        s.push_str(&e.snippet[..hard_char_cap]);
+    let whole = Part {
+        path: e.path.clone(),
+        lang: e.lang.clone(),
+        sha1: e.sha1.clone(),
+        size: e.size.to_string(),
+        last_modified: e.last_modified.clone(),
+        summary: e.summary.clone(),
+        part_idx: 1,
+        part_total: 1,
+        body: e.snippet.clone(),
+        token_estimate: 0,
+        detail: None,
+        see_also: Vec::new(),
+    };
+    split_part(whole, target_tokens, hard_char_cap)
+}
+
+/// Split a whole-file `Part` (body = everything to paste) into sections of
+/// roughly `target_tokens`, truncating the body at `hard_char_cap` chars.
+/// Crate-visible so other packers can feed their own bodies through the
+/// same sectioning as `indexer chunk`.
+pub(crate) fn split_part(whole: Part, target_tokens: usize, hard_char_cap: usize) -> Vec<Part> {
+    let snippet = if whole.body.len() > hard_char_cap {
+        let mut cut = hard_char_cap;
+        while !whole.body.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        let mut s = String::with_capacity(cut + 64);
+        s.push_str(&whole.body[..cut]);
         s.push_str("\n// … [truncated]");
         s
     } else {
-        e.snippet.clone()
+        whole.body.clone()
     };
 
     let total_est = estimate_tokens_fallback(&snippet);
     if total_est <= target_tokens {
-        return vec![Part {
-            path: e.path.clone(),
-            lang: e.lang.clone(),
-            sha1: e.sha1.clone(),
-            size: e.size.to_string(),
-            last_modified: e.last_modified.clone(),
-            summary: e.summary.clone(),
-            part_idx: 1,
-            part_total: 1,
-            body: snippet,
-            token_estimate: total_est,
-        }];
+        return vec![Part { part_idx: 1, part_total: 1, body: snippet, token_estimate: total_est, ..whole }];
     }
 
     let mut parts = Vec::new();
     let mut acc = String::new();
     let mut idx = 1usize;
+    let section = |body: String, token_estimate: usize, idx: usize| Part {
+        summary: None, // summary on first part only
+        part_idx: idx,
+        part_total: 0, // fill later
+        body,
+        token_estimate,
+        ..whole.clone()
+    };
 
     for line in snippet.lines() {
         if !acc.is_empty() {
@@ -186,41 +628,18 @@ fn split_entry_into_parts(
 
         let acc_tokens = estimate_tokens_fallback(&acc);
         if acc_tokens >= target_tokens {
-            parts.push(Part {
-                path: e.path.clone(),
-                lang: e.lang.clone(),
-                sha1: e.sha1.clone(),
-                size: e.size.to_string(),
-                last_modified: e.last_modified.clone(),
-                summary: None, // summary on first part only
-                part_idx: idx,
-                part_total: 0, // fill later
-                body: acc.clone(),
-                token_estimate: acc_tokens,
-            });
-            acc.clear();
+            parts.push(section(std::mem::take(&mut acc), acc_tokens, idx));
             idx += 1;
         }
     }
 
     if !acc.is_empty() {
         let token_estimate = estimate_tokens_fallback(&acc);
-        parts.push(Part {
-            path: e.path.clone(),
-            lang: e.lang.clone(),
-            sha1: e.sha1.clone(),
-            size: e.size.to_string(),
-            last_modified: e.last_modified.clone(),
-            summary: None,
-            part_idx: idx,
-            part_total: 0,
-            body: acc,
-            token_estimate,
-        });
+        parts.push(section(acc, token_estimate, idx));
     }
 
     if let Some(first) = parts.first_mut() {
-        first.summary = e.summary.clone();
+        first.summary = whole.summary.clone();
     }
 
     let total = parts.len();
@@ -230,21 +649,115 @@ fn split_entry_into_parts(
     parts
 }
 
+/* ================================== Manifest ======================================= */
+
+/// `chunks/manifest.json`: which file parts each written chunk holds, so a
+/// chunk can later be resolved back to (path, sha1) pairs (`session mark`).
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ChunkManifest {
+    /// Chunk file name (`<slug>_paste_3.md`) -> parts in render order.
+    pub chunks: BTreeMap<String, Vec<ManifestPart>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestPart {
+    pub path: String,
+    pub sha1: String,
+    pub part: usize,
+    pub of: usize,
+}
+
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+impl ChunkManifest {
+    /// Manifest of `chunks_dir`; empty when missing or unreadable.
+    pub fn load(chunks_dir: &Path) -> Self {
+        fs::read_to_string(chunks_dir.join(MANIFEST_FILE))
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, chunks_dir: &Path) -> Result<()> {
+        let path = chunks_dir.join(MANIFEST_FILE);
+        crate::util::safe_write(&path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("writing {}", path.display()))
+    }
+}
+
+/// Replace the manifest records of every `<out_prefix><N>.*` chunk with `chunks`.
+pub(crate) fn record_chunks(out_prefix: &str, format: ChunkFormat, chunks: &[Vec<Part>]) -> Result<()> {
+    record_chunks_from(out_prefix, 1, format, chunks, true)
+}
+
+/// Record `chunks` as `<out_prefix><first>.<ext>`, `<first + 1>`, ...; with
+/// `replace`, older records under the same prefix are dropped first.
+pub(crate) fn record_chunks_from(
+    out_prefix: &str,
+    first: usize,
+    format: ChunkFormat,
+    chunks: &[Vec<Part>],
+    replace: bool,
+) -> Result<()> {
+    let prefix = Path::new(out_prefix);
+    let dir = prefix.parent().unwrap_or(Path::new("."));
+    let stem = prefix.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+
+    let mut manifest = ChunkManifest::load(dir);
+    if replace {
+        manifest.chunks.retain(|name, _| {
+            let Some(rest) = name.strip_prefix(stem.as_str()) else { return true };
+            let num = rest.split('.').next().unwrap_or_default();
+            num.is_empty() || !num.bytes().all(|b| b.is_ascii_digit())
+        });
+    }
+    for (i, parts) in chunks.iter().enumerate() {
+        let name = format!("{}{}.{}", stem, first + i, format.ext());
+        let records = parts
+            .iter()
+            .filter(|p| !p.sha1.is_empty())
+            .map(|p| ManifestPart { path: p.path.clone(), sha1: p.sha1.clone(), part: p.part_idx, of: p.part_total })
+            .collect();
+        manifest.chunks.insert(name, records);
+    }
+    manifest.save(dir)
+}
+
 /* ================================== Rendering ====================================== */
 
-fn write_chunk(out_prefix: &str, idx: usize, parts: &[Part]) -> Result<()> {
-    let path = format!("{}{}.md", out_prefix, idx);
+pub(crate) fn write_chunk(
+    out_prefix: &str,
+    idx: usize,
+    parts: &[Part],
+    format: ChunkFormat,
+    preamble: Option<&Preamble>,
+) -> Result<()> {
+    let path = format!("{}{}.{}", out_prefix, idx, format.ext());
+    let body = render_chunk(idx, parts, format, preamble)?;
     let mut out = File::create(&path).with_context(|| format!("create {}", path))?;
+    out.write_all(body.as_bytes())
+        .with_context(|| format!("write {}", path))?;
+    Ok(())
+}
 
-    let total_parts = parts.len();
-    let total_files = count_unique_files(parts);
-    let approx_tokens: usize = parts.iter().map(|p| cmp::max(1, p.token_estimate)).sum();
-
-    writeln!(out, "# GPT Paste Chunk {}\n", idx)?;
-    writeln!(out, "> generated: {}", Utc::now().to_rfc3339())?;
-    writeln!(out, "> files: {}  •  parts: {}  •  ~tokens: {}", total_files, total_parts, approx_tokens)?;
-    writeln!(out)?;
+/// Render one chunk in the requested framing.
+pub(crate) fn render_chunk(
+    idx: usize,
+    parts: &[Part],
+    format: ChunkFormat,
+    preamble: Option<&Preamble>,
+) -> Result<String> {
+    Ok(match format {
+        ChunkFormat::Md => render_chunk_md(idx, parts, preamble),
+        ChunkFormat::Xml => render_chunk_xml(idx, parts, preamble),
+        ChunkFormat::Jsonl => render_chunk_jsonl(idx, parts, preamble)?,
+        ChunkFormat::Txt => render_chunk_txt(idx, parts, preamble),
+    })
+}
 
+/// Split a chunk's parts into runs sharing the same path (one file section each).
+fn file_sections(parts: &[Part]) -> Vec<&[Part]> {
+    let mut out = Vec::new();
     let mut i = 0usize;
     while i < parts.len() {
         let start = i;
@@ -252,94 +765,304 @@ fn write_chunk(out_prefix: &str, idx: usize, parts: &[Part]) -> Result<()> {
         while i < parts.len() && parts[i].path == *path_here {
             i += 1;
         }
-        render_file_section(&mut out, &parts[start..i])?;
+        out.push(&parts[start..i]);
     }
+    out
+}
 
-    Ok(())
+fn chunk_stats(parts: &[Part]) -> (usize, usize, usize) {
+    let approx_tokens: usize = parts.iter().map(|p| cmp::max(1, p.token_estimate)).sum();
+    (count_unique_files(parts), parts.len(), approx_tokens)
 }
 
-fn render_file_section(out: &mut File, parts: &[Part]) -> Result<()> {
-    if parts.is_empty() {
-        return Ok(());
+fn render_chunk_md(idx: usize, parts: &[Part], preamble: Option<&Preamble>) -> String {
+    let (total_files, total_parts, approx_tokens) = chunk_stats(parts);
+    let mut out = String::new();
+    let _ = writeln!(out, "# GPT Paste Chunk {}\n", idx);
+    let _ = writeln!(out, "> generated: {}", Utc::now().to_rfc3339());
+    let _ = writeln!(out, "> files: {}  •  parts: {}  •  ~tokens: {}", total_files, total_parts, approx_tokens);
+    let _ = writeln!(out);
+    if let Some(pre) = preamble {
+        let _ = writeln!(out, "> {}", pre.nav_line());
+        let _ = writeln!(out, ">");
+        let _ = writeln!(out, "> **Project:** {}", pre.project);
+        let _ = writeln!(out);
+        if !pre.modules.is_empty() {
+            let _ = writeln!(out, "**Modules**\n");
+            for m in &pre.modules {
+                let _ = writeln!(out, "- {}", m);
+            }
+            let _ = writeln!(out);
+        }
+        let _ = writeln!(out, "**In this chunk**\n");
+        for t in &pre.toc {
+            let _ = writeln!(out, "- {}", t);
+        }
+        let _ = writeln!(out, "\n---\n");
+    }
+    for section in file_sections(parts) {
+        render_file_section_md(&mut out, section);
     }
-    let meta = &parts[0];
+    out
+}
+
+fn render_file_section_md(out: &mut String, parts: &[Part]) {
+    let Some(meta) = parts.first() else { return };
     let multi = parts.len() > 1 || meta.part_total > 1;
     let title = if multi {
         format!("`{}` [{}] ({} parts)", meta.path, meta.lang, meta.part_total)
     } else {
         format!("`{}` [{}]", meta.path, meta.lang)
     };
-    writeln!(out, "## {}\n", title)?;
-    writeln!(
+    let _ = writeln!(out, "## {}\n", title);
+    let _ = writeln!(
         out,
         "- sha1: `{}` • size: {} • mtime: {}",
         meta.sha1, meta.size, meta.last_modified
-    )?;
+    );
+    if let Some(detail) = meta.detail {
+        let _ = writeln!(out, "- content: {}", detail);
+    }
+    if let Some(line) = see_also_line(&meta.see_also) {
+        let _ = writeln!(out, "- see also: {}", line);
+    }
 
     if let Some(sum) = &meta.summary {
         if !sum.trim().is_empty() {
-            writeln!(out, "**Summary:** {}", sum.trim())?;
+            let _ = writeln!(out, "**Summary:** {}", sum.trim());
         }
     }
 
     for p in parts {
         if multi {
-            writeln!(out, "\n**Part {}/{}**", p.part_idx, p.part_total)?;
+            let _ = writeln!(out, "\n**Part {}/{}**", p.part_idx, p.part_total);
         }
-        let fence = fence_lang(&p.lang);
-        writeln!(out, "```{}\n{}\n```", fence, p.body)?;
+        let fence = fence_for(&p.body);
+        let _ = writeln!(out, "{}{}\n{}\n{}", fence, fence_lang(&p.lang), p.body, fence);
     }
 
-    writeln!(out)?;
-    Ok(())
+    let _ = writeln!(out);
+}
+
+fn render_chunk_xml(idx: usize, parts: &[Part], preamble: Option<&Preamble>) -> String {
+    let (total_files, total_parts, approx_tokens) = chunk_stats(parts);
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "<chunk index=\"{}\" generated=\"{}\" files=\"{}\" parts=\"{}\" tokens=\"{}\">",
+        idx,
+        Utc::now().to_rfc3339(),
+        total_files,
+        total_parts,
+        approx_tokens
+    );
+    if let Some(pre) = preamble {
+        let _ = writeln!(
+            out,
+            "<preamble chunk=\"{}\" of=\"{}\"{}{}>",
+            pre.index,
+            pre.total,
+            pre.prev.as_deref().map(|p| format!(" prev=\"{}\"", xml_attr(p))).unwrap_or_default(),
+            pre.next.as_deref().map(|p| format!(" next=\"{}\"", xml_attr(p))).unwrap_or_default()
+        );
+        let _ = writeln!(out, "<project>{}</project>", xml_text(&pre.project));
+        for m in &pre.modules {
+            let _ = writeln!(out, "<module>{}</module>", xml_text(m));
+        }
+        for t in &pre.toc {
+            let _ = writeln!(out, "<toc>{}</toc>", xml_text(t));
+        }
+        let _ = writeln!(out, "</preamble>");
+    }
+    for p in parts {
+        let _ = writeln!(
+            out,
+            "<document path=\"{}\" lang=\"{}\" sha1=\"{}\" size=\"{}\" part=\"{}\" parts=\"{}\"{}>",
+            xml_attr(&p.path),
+            xml_attr(&p.lang),
+            xml_attr(&p.sha1),
+            xml_attr(&p.size),
+            p.part_idx,
+            p.part_total,
+            p.detail.map(|d| format!(" content=\"{}\"", d)).unwrap_or_default()
+        );
+        if let Some(sum) = p.summary.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+            let _ = writeln!(out, "<summary>{}</summary>", xml_text(sum));
+        }
+        for (chunk, path) in &p.see_also {
+            let _ = writeln!(out, "<see_also chunk=\"{}\" path=\"{}\"/>", chunk, xml_attr(path));
+        }
+        let _ = writeln!(out, "<source>{}</source>", xml_cdata(&p.body));
+        let _ = writeln!(out, "</document>");
+    }
+    let _ = writeln!(out, "</chunk>");
+    out
+}
+
+fn render_chunk_jsonl(idx: usize, parts: &[Part], preamble: Option<&Preamble>) -> Result<String> {
+    let mut out = String::new();
+    if let Some(pre) = preamble {
+        let rec = serde_json::json!({
+            "chunk": idx,
+            "preamble": {
+                "chunk": pre.index,
+                "of": pre.total,
+                "prev": pre.prev,
+                "next": pre.next,
+                "project": pre.project,
+                "modules": pre.modules,
+                "toc": pre.toc,
+            },
+        });
+        out.push_str(&serde_json::to_string(&rec)?);
+        out.push('\n');
+    }
+    for p in parts {
+        let rec = serde_json::json!({
+            "chunk": idx,
+            "path": p.path,
+            "lang": p.lang,
+            "sha1": p.sha1,
+            "size": p.size,
+            "last_modified": p.last_modified,
+            "part": p.part_idx,
+            "parts": p.part_total,
+            "summary": p.summary,
+            "token_estimate": p.token_estimate,
+            "detail": p.detail,
+            "see_also": p.see_also.iter().map(|(c, path)| serde_json::json!({ "chunk": c, "path": path })).collect::<Vec<_>>(),
+            "content": p.body,
+        });
+        out.push_str(&serde_json::to_string(&rec)?);
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+fn render_chunk_txt(idx: usize, parts: &[Part], preamble: Option<&Preamble>) -> String {
+    let (total_files, total_parts, approx_tokens) = chunk_stats(parts);
+    let mut out = String::new();
+    let _ = writeln!(out, "GPT Paste Chunk {}", idx);
+    let _ = writeln!(out, "generated: {}", Utc::now().to_rfc3339());
+    let _ = writeln!(out, "files: {}  parts: {}  ~tokens: {}", total_files, total_parts, approx_tokens);
+    let _ = writeln!(out);
+    if let Some(pre) = preamble {
+        out.push_str(&pre.render_plain());
+        let _ = writeln!(out);
+    }
+    for p in parts {
+        let rule = rule_for(&p.body);
+        let _ = writeln!(
+            out,
+            "{rule} BEGIN {} [{}] part {}/{} sha1={}{} {rule}",
+            p.path,
+            p.lang,
+            p.part_idx,
+            p.part_total,
+            p.sha1,
+            p.detail.map(|d| format!(" content={}", d)).unwrap_or_default()
+        );
+        if let Some(sum) = p.summary.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+            let _ = writeln!(out, "summary: {}", sum);
+        }
+        if let Some(line) = see_also_line(&p.see_also) {
+            let _ = writeln!(out, "see also: {}", line.replace('`', ""));
+        }
+        let _ = writeln!(out, "{}", p.body);
+        let _ = writeln!(out, "{rule} END {} {rule}\n", p.path);
+    }
+    out
 }
 
 /* ================================== Heuristics ===================================== */
 
-fn estimate_tokens_fallback(s: &str) -> usize {
+pub(crate) fn estimate_tokens_fallback(s: &str) -> usize {
     let chars = s.len();
     (chars / 4).max(12)
 }
 
-fn fence_lang<'a>(lang: &'a str) -> &'a str {
-    let l = lang.trim();
-    if l.is_empty() {
-        ""
-    } else if l.eq_ignore_ascii_case("rs") || l.eq_ignore_ascii_case("rust") {
-        "rust"
-    } else if l.eq_ignore_ascii_case("ts") || l.eq_ignore_ascii_case("typescript") {
-        "ts"
-    } else if l.eq_ignore_ascii_case("js") || l.eq_ignore_ascii_case("javascript") {
-        "javascript"
-    } else if l.eq_ignore_ascii_case("py") || l.eq_ignore_ascii_case("python") {
-        "python"
-    } else if l.eq_ignore_ascii_case("go") || l.eq_ignore_ascii_case("golang") {
-        "go"
-    } else if l.eq_ignore_ascii_case("sh") || l.eq_ignore_ascii_case("bash") || l.eq_ignore_ascii_case("zsh") {
-        "bash"
-    } else if l.eq_ignore_ascii_case("c") || l.eq_ignore_ascii_case("h") {
-        "c"
-    } else if l.eq_ignore_ascii_case("cpp") || l.eq_ignore_ascii_case("cc")
-        || l.eq_ignore_ascii_case("cxx") || l.eq_ignore_ascii_case("hpp")
-    {
-        "cpp"
-    } else if l.eq_ignore_ascii_case("java") {
-        "java"
-    } else if l.eq_ignore_ascii_case("md") || l.eq_ignore_ascii_case("markdown") {
-        "md"
-    } else if l.eq_ignore_ascii_case("toml") {
-        "toml"
-    } else if l.eq_ignore_ascii_case("yaml") || l.eq_ignore_ascii_case("yml") {
-        "yaml"
-    } else if l.eq_ignore_ascii_case("json") || l.eq_ignore_ascii_case("jsonl") {
-        "json"
-    } else {
-        l
+/// Tokens of an empty chunk in `format`, plus room for its counts to grow.
+fn header_tokens(format: ChunkFormat) -> usize {
+    render_chunk(usize::MAX, &[], format, None).map_or(0, |s| s.len().div_ceil(4)) + 8
+}
+
+/// Tokens `part` adds to a chunk rendered in `format`: its body plus section
+/// header, fences, CDATA wrapping or JSON escaping.
+fn framed_tokens(part: &Part, format: ChunkFormat) -> usize {
+    let bare = render_chunk(usize::MAX, &[], format, None).map_or(0, |s| s.len());
+    match render_chunk(usize::MAX, std::slice::from_ref(part), format, None) {
+        Ok(s) => s.len().saturating_sub(bare).div_ceil(4),
+        Err(_) => part.token_estimate,
     }
 }
 
+/// Fence info string for a language label (the analyzer's `fence`, else the label).
+pub(crate) fn fence_lang(lang: &str) -> String {
+    let l = lang.trim();
+    crate::lang::analyzer(l).map_or_else(|| l.to_string(), |a| a.fence().to_string())
+}
+
 /* ================================== Utilities ====================================== */
 
+/// Markdown fence long enough to wrap `body`: one backtick more than the longest
+/// backtick run inside it (min 3), so nested ``` blocks cannot close it early.
+fn fence_for(body: &str) -> String {
+    "`".repeat(cmp::max(3, longest_run(body, b'`') + 1))
+}
+
+/// `txt` delimiter run: one `=` more than the longest `=` run in `body`
+/// (min 5), so no body line can pass for the part's END line.
+fn rule_for(body: &str) -> String {
+    "=".repeat(cmp::max(5, longest_run(body, b'=') + 1))
+}
+
+fn longest_run(body: &str, byte: u8) -> usize {
+    let mut longest = 0usize;
+    let mut run = 0usize;
+    for b in body.bytes() {
+        if b == byte {
+            run += 1;
+            longest = longest.max(run);
+        } else {
+            run = 0;
+        }
+    }
+    longest
+}
+
+/// Refs grouped per chunk: ``chunk 2 (`a.rs`, `b.rs`), chunk 4 (`c.rs`)``.
+/// `None` when there is nothing to point at.
+fn see_also_line(refs: &[(usize, String)]) -> Option<String> {
+    if refs.is_empty() {
+        return None;
+    }
+    let mut grouped: BTreeMap<usize, Vec<&str>> = BTreeMap::new();
+    for (c, p) in refs {
+        grouped.entry(*c).or_default().push(p);
+    }
+    let items: Vec<String> = grouped
+        .into_iter()
+        .map(|(c, paths)| {
+            let list = paths.iter().map(|p| format!("`{}`", p)).collect::<Vec<_>>().join(", ");
+            format!("chunk {} ({})", c, list)
+        })
+        .collect();
+    Some(items.join(", "))
+}
+
+/// Wrap text in CDATA, splitting any `]]>` so the section cannot terminate early.
+fn xml_cdata(s: &str) -> String {
+    format!("<![CDATA[{}]]>", s.replace("]]>", "]]]]><![CDATA[>"))
+}
+
+fn xml_text(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+fn xml_attr(s: &str) -> String {
+    xml_text(s).replace('"', "&quot;")
+}
+
 fn count_unique_files(parts: &[Part]) -> usize {
     let mut n = 0usize;
     let mut last: Option<&str> = None;
@@ -376,6 +1099,7 @@ mod tests {
             summary: Some("entrypoint".into()),
             snippet: "fn main() {}".into(),
             token_estimate: 0,
+            imports: Vec::new(),
         };
         let parts = split_entry_into_parts(&e, 800, 32_000);
         assert_eq!(parts.len(), 1);
@@ -399,6 +1123,7 @@ mod tests {
             summary: Some("lib".into()),
             snippet: body,
             token_estimate: 0,
+            imports: Vec::new(),
         };
         let parts = split_entry_into_parts(&e, 800, 32_000);
         assert!(parts.len() > 1);
@@ -406,4 +1131,164 @@ mod tests {
         assert!(parts.iter().skip(1).all(|p| p.summary.is_none()));
         assert!(parts.iter().all(|p| p.part_total == parts.len()));
     }
+
+    fn part(path: &str, body: &str) -> Part {
+        Part {
+            path: path.into(),
+            lang: "rust".into(),
+            sha1: "deadbeef".into(),
+            size: "10".into(),
+            last_modified: "0".into(),
+            summary: Some("a <b> & c".into()),
+            part_idx: 1,
+            part_total: 1,
+            body: body.into(),
+            token_estimate: 12,
+            detail: None,
+            see_also: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn fence_outgrows_nested_backticks() {
+        assert_eq!(fence_for("fn main() {}"), "```");
+        assert_eq!(fence_for("```rust\nx\n```"), "````");
+        assert_eq!(fence_for("`````"), "``````");
+    }
+
+    #[test]
+    fn md_section_survives_inner_fences() {
+        let md = render_chunk_md(1, &[part("README.md", "```sh\ncargo build\n```")], None);
+        assert!(md.contains("````rust\n```sh\ncargo build\n```\n````\n"));
+    }
+
+    #[test]
+    fn txt_delimiters_outgrow_the_body() {
+        let plain = render_chunk_txt(1, &[part("a.rs", "x")], None);
+        assert!(plain.contains("===== BEGIN a.rs [rust] part 1/1 sha1=") && plain.contains("\n===== END a.rs =====\n"));
+
+        let body = "let s = \"\n===== END a.rs =====\n\";";
+        let txt = render_chunk_txt(1, &[part("a.rs", body)], None);
+        assert!(txt.contains("====== BEGIN a.rs"), "{txt}");
+        // The body's own END line is not the delimiter; the real one follows it.
+        assert_eq!(txt.matches("\n====== END a.rs ======\n").count(), 1);
+        assert!(txt.contains(&format!("{body}\n====== END a.rs ======\n")));
+    }
+
+    #[test]
+    fn xml_escapes_attrs_and_cdata() {
+        let xml = render_chunk_xml(2, &[part("src/a\"b.rs", "let s = \"]]>\";")], None);
+        assert!(xml.contains("path=\"src/a&quot;b.rs\""));
+        assert!(xml.contains("<summary>a &lt;b&gt; &amp; c</summary>"));
+        assert!(xml.contains("]]]]><![CDATA[>"));
+        assert!(xml.trim_end().ends_with("</chunk>"));
+    }
+
+    #[test]
+    fn jsonl_one_record_per_part() {
+        let out = render_chunk_jsonl(3, &[part("a.rs", "x"), part("b.rs", "y")], None).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 2);
+        let v: serde_json::Value = serde_json::from_str(lines[1]).unwrap();
+        assert_eq!(v["chunk"], 3);
+        assert_eq!(v["path"], "b.rs");
+        assert_eq!(v["content"], "y");
+    }
+
+    #[test]
+    fn format_labels_parse() {
+        assert_eq!(ChunkFormat::from_str_ic("XML"), Some(ChunkFormat::Xml));
+        assert_eq!(ChunkFormat::from_str_ic("plain"), Some(ChunkFormat::Txt));
+        assert_eq!(ChunkFormat::from_str_ic("yaml"), None);
+        assert_eq!(ChunkFormat::Jsonl.ext(), "jsonl");
+    }
+
+    #[test]
+    fn see_also_points_across_chunks() {
+        let nodes = [
+            DepNode { path: "src/a.rs", lang: "rust", imports: &["crate::b::B".to_string()] },
+            DepNode { path: "src/b.rs", lang: "rust", imports: &[] },
+        ];
+        let graph = DepGraph::build(&nodes);
+        let mut chunks = vec![vec![part("src/a.rs", "x")], vec![part("src/b.rs", "y")]];
+        link_see_also(&mut chunks, &graph);
+        assert_eq!(chunks[0][0].see_also, vec![(2, "src/b.rs".to_string())]);
+        assert_eq!(chunks[1][0].see_also, vec![(1, "src/a.rs".to_string())]);
+
+        let md = render_chunk(1, &chunks[0], ChunkFormat::Md, None).unwrap();
+        assert!(md.contains("- see also: chunk 2 (`src/b.rs`)"), "{md}");
+    }
+
+    #[test]
+    fn order_labels_parse() {
+        assert_eq!(ChunkOrder::from_str_ic("DEPS"), Some(ChunkOrder::Deps));
+        assert_eq!(ChunkOrder::from_str_ic("path"), Some(ChunkOrder::Path));
+        assert_eq!(ChunkOrder::from_str_ic("size"), None);
+    }
+
+    fn entry(path: &str, snippet: &str, summary: Option<&str>) -> FileIntentEntry {
+        FileIntentEntry {
+            path: path.into(),
+            lang: "rust".into(),
+            sha1: "0".into(),
+            size: 1,
+            last_modified: "0".into(),
+            summary: summary.map(Into::into),
+            snippet: snippet.into(),
+            token_estimate: 0,
+            imports: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn rendered_chunks_stay_within_the_cap() {
+        // Quote- and newline-heavy bodies: JSON escaping and framing add a lot.
+        let body = "let s = \"\\\"quoted\\\"\";\n".repeat(12);
+        let entries: Vec<FileIntentEntry> = (0..30)
+            .map(|i| FileIntentEntry {
+                imports: vec![format!("crate::m{:02}::X", (i + 1) % 30)],
+                ..entry(&format!("src/m{i:02}.rs"), &body, Some("A module with a summary."))
+            })
+            .collect();
+        for format in [ChunkFormat::Md, ChunkFormat::Xml, ChunkFormat::Jsonl, ChunkFormat::Txt] {
+            let chunks = plan(&entries, 400, ChunkOrder::Deps, format);
+            assert!(chunks.len() > 1);
+            for (i, parts) in chunks.iter().enumerate() {
+                let tokens = estimate_tokens_fallback(&render_chunk(i + 1, parts, format, None).unwrap());
+                assert!(tokens <= 400, "{format:?} chunk {}: {tokens} tokens", i + 1);
+            }
+        }
+    }
+
+    #[test]
+    fn project_line_prefers_manifest_then_readme() {
+        let entries = vec![
+            entry("Cargo.toml", "[package]\nname = \"demo\"\ndescription = \"Does things\"\n", None),
+            entry("README.md", "# demo", Some("Readme words")),
+        ];
+        assert_eq!(project_one_liner(&entries), "demo — Does things");
+        assert_eq!(project_one_liner(&entries[1..]), "Readme words");
+        assert_eq!(manifest_field("  \"name\": \"web\",", "name").as_deref(), Some("web"));
+    }
+
+    #[test]
+    fn preamble_respects_allowance_and_navigates() {
+        let entries: Vec<FileIntentEntry> =
+            (0..40).map(|i| entry(&format!("src/m{i:02}.rs"), "", Some("a module"))).collect();
+        let ctx = ProjectContext::from_entries(&entries);
+        assert_eq!(ctx.modules.len(), 1);
+        assert!(ctx.modules[0].starts_with("`src/` (40): m00, m01"), "{}", ctx.modules[0]);
+
+        let parts: Vec<Part> = (0..40).map(|i| part(&format!("src/m{i:02}.rs"), "x")).collect();
+        let pre = Preamble::build(&ctx, ".gpt_index/chunks/demo_paste_", ChunkFormat::Md, 2, 3, &parts, 120);
+        assert!(estimate_tokens_fallback(&pre.render_plain()) <= 120 + 12);
+        assert!(pre.toc.last().unwrap().starts_with("… +"), "{:?}", pre.toc);
+        assert_eq!(pre.prev.as_deref(), Some("demo_paste_1.md"));
+        assert_eq!(pre.next.as_deref(), Some("demo_paste_3.md"));
+
+        let md = render_chunk(2, &parts[..1], ChunkFormat::Md, Some(&pre)).unwrap();
+        assert!(md.contains("> chunk 2 of 3 • prev: demo_paste_1.md • next: demo_paste_3.md"), "{md}");
+        let xml = render_chunk(2, &parts[..1], ChunkFormat::Xml, Some(&pre)).unwrap();
+        assert!(xml.contains("<preamble chunk=\"2\" of=\"3\" prev=\"demo_paste_1.md\""), "{xml}");
+    }
 }
@@ -1,20 +1,28 @@
 use anyhow::{anyhow, Context, Result};
 use std::{
+    collections::BTreeSet,
     env,
     fs,
     path::{Path, PathBuf},
 };
 
 use crate::{
+    budget_pack,
+    changelog,
     chunker,
     custom_view::build_custom_from_index,
+    delta_pack,
     diff,
     functions_view,
     map_view,
+    patch,
     scan,
+    semver,
+    session,
     index_v3,
     types_view,
     util,
+    verify,
 };
 
 pub fn run_cli() -> Result<()> {
@@ -39,14 +47,22 @@ pub fn run_cli() -> Result<()> {
     }
 
     match cmd {
-        "init" => index_root(false),
-        "reindex" => index_root(true),
+        "init" => index_root(false, &[]),
+        "reindex" => index_root(true, &args[2..]),
         "sub" => index_subdir(),
         "map" => generate_map(),
         "types" => generate_types(),
         "functions" => generate_functions(),
-        "chunk" => chunk_index(args.get(2).map(|s| s.as_str())),
-        "v3" | "emit-v3" => emit_v3(),
+        "chunk" => chunk_index(&args[2..]),
+        "pack" => budget_pack_index(&args[2..]),
+        "delta-pack" => delta_pack_index(&args[2..]),
+        "session" => session_cmd(&args[2..]),
+        "semver" => semver_check(&args[2..]),
+        "changelog" => changelog_between(&args[2..]),
+        "v3" | "emit-v3" => emit_v3(&args[2..]),
+        "apply" => apply_patch(&args[2..]),
+        "verify" => verify_v3(&args[2..]),
+        "proof" => merkle_proof(&args[2..]),
         "help" => {
             let sub = args.get(2).map(|s| s.as_str());
             print_help_dispatch(sub)
@@ -59,6 +75,24 @@ pub fn run_cli() -> Result<()> {
     }
 }
 
+/// A check that ran to completion and failed (stale pack, breaking API
+/// change), as opposed to the tool failing. `main` exits with [`Verdict::EXIT`]
+/// for it and 1 for every other error, so scripts can tell the two apart.
+#[derive(Debug)]
+pub struct Verdict(pub String);
+
+impl Verdict {
+    pub const EXIT: u8 = 2;
+}
+
+impl std::fmt::Display for Verdict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Verdict {}
+
 fn is_help_flag(s: &str) -> bool {
     matches!(s, "--help" | "-h" | "help")
 }
@@ -134,8 +168,10 @@ struct ResolvedPaths {
  * Commands
  *───────────────────────────────────────────────────────────────────────────*/
 
-fn index_root(is_reindex: bool) -> Result<()> {
+fn index_root(is_reindex: bool, args: &[String]) -> Result<()> {
     let p = resolve_paths()?;
+    let diff_opts = diff::DiffOptions { similarity_threshold: parse_similarity(args)? };
+    let v3_opts = parse_v3_options(args)?;
 
     // Archive old index if reindexing
     if is_reindex && p.index_file.exists() {
@@ -148,12 +184,24 @@ fn index_root(is_reindex: bool) -> Result<()> {
         let new_entries = scan::scan_and_write_index(&p.cwd, &p.index_file)
             .context("reindex scan/write failed")?;
 
-        let diff_val = diff::diff_indexes(&old_entries, &new_entries);
+        let diff_val = diff::diff_indexes_with_opts(&old_entries, &new_entries, &diff_opts);
         let diff_path = p.history_diff.join(format!("{}_{}.json", p.dir_name, ts));
         let mut f = fs::File::create(&diff_path)
             .with_context(|| format!("creating {}", diff_path.display()))?;
         serde_json::to_writer_pretty(&mut f, &diff_val).context("writing diff json")?;
         println!("Index updated. Diff written to {}.", diff_path.display());
+
+        let log_path = p.history_diff.join(format!("{}_{}.md", p.dir_name, ts));
+        let title = format!("{} → current", backup_path.file_name().unwrap_or_default().to_string_lossy());
+        let packages = changelog::package_roots(old_entries.iter().chain(&new_entries));
+        util::safe_write(&log_path, changelog::changelog_markdown(&diff_val, &title, &packages))
+            .with_context(|| format!("writing {}", log_path.display()))?;
+        println!("Changelog written to {}.", log_path.display());
+
+        let api_path = p.history_diff.join(format!("{}_{}_api.md", p.dir_name, ts));
+        util::safe_write(&api_path, diff::api_changes_markdown(&diff_val))
+            .with_context(|| format!("writing {}", api_path.display()))?;
+        println!("API changes written to {}.", api_path.display());
     } else {
         scan::scan_and_write_index(&p.cwd, &p.index_file)
             .context("initial scan/write failed")?;
@@ -193,9 +241,10 @@ fn index_root(is_reindex: bool) -> Result<()> {
 
     // V3 (LLM-CODE-INDEX pack)
     let out_v3 = p.index_dir.join("index_v3.json");
-    index_v3::build_index_v3(&p.index_file, &p.cwd, &out_v3)
+    let v3 = index_v3::build_index_v3_with(&p.index_file, &p.cwd, &out_v3, &v3_opts)
         .context("emitting LLM-CODE-INDEX/v3")?;
     println!("LLM-CODE-INDEX/v3 written to {}", out_v3.display());
+    print_v3_report(&v3);
 
 
     Ok(())
@@ -262,11 +311,11 @@ fn generate_custom() -> Result<()> {
         .map_err(anyhow::Error::from)
 }
 
-/// Support: `indexer chunk` or `indexer chunk --cap=12000`
+/// Support: `indexer chunk`, `indexer chunk --cap=12000`, `indexer chunk --format=xml`
 /// Also supports: `indexer help chunk` | `indexer chunk --help`
-fn chunk_index(arg: Option<&str>) -> Result<()> {
-    // Accept `--help` passed as the sole arg: `indexer chunk --help`
-    if arg.is_some() && is_help_flag(arg.unwrap()) {
+fn chunk_index(args: &[String]) -> Result<()> {
+    // Accept `--help` anywhere in the args: `indexer chunk --cap=9000 --help`
+    if args.iter().any(|a| is_help_flag(a)) {
         print_help_chunk();
         return Ok(());
     }
@@ -279,23 +328,351 @@ fn chunk_index(arg: Option<&str>) -> Result<()> {
         .to_str()
         .ok_or_else(|| anyhow!("non-utf8 path: {}", out_prefix.display()))?;
 
-    let cap = parse_cap(arg).unwrap_or(15_000);
-    chunker::chunk_index_for_gpt(&p.index_file, out_prefix_str, cap).with_context(|| {
+    let opts = parse_chunk_options(args)?;
+    let n = chunker::chunk_index_with_opts(&p.index_file, out_prefix_str, &opts).with_context(|| {
         format!(
             "chunking {} with cap {}",
             p.index_file.display(),
-            cap
+            opts.token_cap
         )
     })?;
-    println!("Paste chunks written to {}", p.chunks_dir.display());
+    println!(
+        "{} paste chunk(s) [{}] written to {}",
+        n,
+        opts.format.ext(),
+        p.chunks_dir.display()
+    );
     Ok(())
 }
 
-fn parse_cap(arg: Option<&str>) -> Option<usize> {
-    let a = arg?;
-    a.trim()
-        .strip_prefix("--cap=")
-        .and_then(|rest| rest.parse::<usize>().ok())
+/// Shared `--cap=<N>` / `--format=<md|xml|jsonl|txt>` / `--order=<path|deps>` /
+/// `--preamble[=<N>]` parsing for chunk-emitting commands.
+fn parse_chunk_options(args: &[String]) -> Result<chunker::ChunkOptions> {
+    let mut opts = chunker::ChunkOptions::default();
+    if let Some(cap) = parse_cap(args)? {
+        opts.token_cap = cap;
+    }
+    if let Some(f) = flag_value(args, "--format") {
+        opts.format = chunker::ChunkFormat::from_str_ic(f)
+            .ok_or_else(|| anyhow!("unknown chunk format `{f}` (expected md|xml|jsonl|txt)"))?;
+    }
+    if let Some(o) = flag_value(args, "--order") {
+        opts.order = chunker::ChunkOrder::from_str_ic(o)
+            .ok_or_else(|| anyhow!("unknown chunk order `{o}` (expected path|deps)"))?;
+    }
+    opts.preamble = parse_preamble(args)?;
+    Ok(opts)
+}
+
+/// `--preamble` (default allowance) or `--preamble=<N>` / `--preamble <N>`.
+fn parse_preamble(args: &[String]) -> Result<Option<usize>> {
+    let Some(pos) = args.iter().position(|a| a.trim() == "--preamble" || a.trim().starts_with("--preamble=")) else {
+        return Ok(None);
+    };
+    let raw = match args[pos].trim().strip_prefix("--preamble=") {
+        Some(v) => Some(v),
+        // A bare flag only takes the next arg when it is a number.
+        None => args.get(pos + 1).map(|a| a.trim()).filter(|a| a.parse::<usize>().is_ok()),
+    };
+    match raw {
+        None => Ok(Some(chunker::DEFAULT_PREAMBLE_TOKENS)),
+        Some(v) => v
+            .parse::<usize>()
+            .map(Some)
+            .map_err(|_| anyhow!("bad --preamble value `{v}` (expected a token count)")),
+    }
+}
+
+/// `--similarity=<0..1>` for rename/copy pairing; the `DiffOptions` default when absent.
+fn parse_similarity(args: &[String]) -> Result<f64> {
+    let Some(v) = flag_value(args, "--similarity") else {
+        return Ok(diff::DiffOptions::default().similarity_threshold);
+    };
+    v.trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|t| (0.0..=1.0).contains(t))
+        .ok_or_else(|| anyhow!("bad --similarity `{v}` (expected 0..1)"))
+}
+
+/// `--cap=<N>` as a token count; `None` when absent.
+fn parse_cap(args: &[String]) -> Result<Option<usize>> {
+    flag_value(args, "--cap")
+        .map(|v| v.trim().parse::<usize>().map_err(|_| anyhow!("bad --cap `{v}` (expected a token count)")))
+        .transpose()
+}
+
+/// Value of `--name=<v>` or `--name <v>`; first occurrence wins.
+fn flag_value<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    let mut it = args.iter();
+    while let Some(a) = it.next() {
+        let a = a.trim();
+        if let Some(rest) = a.strip_prefix(name) {
+            if let Some(v) = rest.strip_prefix('=') {
+                return Some(v);
+            }
+            if rest.is_empty() {
+                return it.next().map(String::as_str);
+            }
+        }
+    }
+    None
+}
+
+/// `indexer pack --budget 100000 [--weights role=1,exports=0.5,churn=0.5,size=0.25] [--format=md]`
+fn budget_pack_index(args: &[String]) -> Result<()> {
+    let p = resolve_paths()?;
+    ensure_index_exists(&p.index_file)?;
+
+    let mut opts = budget_pack::PackOptions {
+        format: parse_chunk_options(args)?.format,
+        ..Default::default()
+    };
+    if let Some(b) = flag_value(args, "--budget") {
+        opts.budget = b
+            .trim()
+            .parse()
+            .with_context(|| format!("bad --budget `{b}`"))?;
+    }
+    if let Some(w) = flag_value(args, "--weights") {
+        opts.weights = budget_pack::PackWeights::parse(w)?;
+    }
+
+    let out = p
+        .chunks_dir
+        .join(format!("{}_pack.{}", p.dir_name, opts.format.ext()));
+    let report = budget_pack::build_budget_pack(&p.index_file, &p.cwd, &p.history_full, &out, &opts)
+        .with_context(|| format!("packing {} into {} tokens", p.index_file.display(), opts.budget))?;
+
+    let report_path = p.chunks_dir.join(format!("{}_pack_report.json", p.dir_name));
+    util::safe_write(&report_path, serde_json::to_string_pretty(&report)?)
+        .with_context(|| format!("writing {}", report_path.display()))?;
+
+    let by_detail = |d: &str| report.included.iter().filter(|i| i.detail == d).count();
+    println!(
+        "Packed {} file(s) into ~{}/{} tokens (full: {}, snippet: {}, signatures: {}); dropped {}.",
+        report.included.len(),
+        report.used_tokens,
+        report.budget,
+        by_detail("full"),
+        by_detail("snippet"),
+        by_detail("signatures"),
+        report.dropped.len()
+    );
+    for d in report.dropped.iter().take(20) {
+        println!("  dropped {} (score {:.3}): {}", d.path, d.score, d.reason);
+    }
+    if report.dropped.len() > 20 {
+        println!("  … {} more in {}", report.dropped.len() - 20, report_path.display());
+    }
+    println!("Pack written to {}", out.display());
+    Ok(())
+}
+
+fn delta_pack_index(args: &[String]) -> Result<()> {
+    let p = resolve_paths()?;
+    ensure_index_exists(&p.index_file)?;
+    let since = flag_value(args, "--since")
+        .ok_or_else(|| anyhow!("usage: indexer delta-pack --since <snapshot.jsonl|git-ref> [--cap=<N>] [--format=<F>]"))?;
+
+    let old = if p.cwd.join(since).is_file() {
+        scan::read_index(&p.cwd.join(since)).with_context(|| format!("reading {since}"))?
+    } else {
+        delta_pack::snapshot_at_rev(since, &p.cwd)?
+    };
+    let new = scan::read_index(&p.index_file)?;
+
+    let opts = parse_chunk_options(args)?;
+    let out_prefix = p.chunks_dir.join(format!("{}_delta_", p.dir_name));
+    let out_prefix_str = out_prefix
+        .to_str()
+        .ok_or_else(|| anyhow!("non-utf8 path: {}", out_prefix.display()))?;
+    let report = delta_pack::build_delta_pack(&old, &new, &p.cwd, out_prefix_str, since, &opts)
+        .with_context(|| format!("building delta pack since {since}"))?;
+
+    println!(
+        "{} delta chunk(s) [{}] written to {}: {} file(s) ({} as changed items), {} removed, {} renamed.",
+        report.chunks,
+        opts.format.ext(),
+        p.chunks_dir.display(),
+        report.files,
+        report.item_only,
+        report.removed.len(),
+        report.renamed.len()
+    );
+    Ok(())
+}
+
+fn session_cmd(args: &[String]) -> Result<()> {
+    let p = resolve_paths()?;
+    let usage = || anyhow!("usage: indexer session <new|mark|next|show> <name> [..] (see `indexer session --help`)");
+    let (Some(action), Some(name)) = (args.first(), args.get(1)) else {
+        return Err(usage());
+    };
+
+    match action.as_str() {
+        "new" => {
+            let s = session::Session::create(&p.index_dir, name)?;
+            println!("Session `{}` created at {}", s.name, session::Session::path(&p.index_dir, name).display());
+        }
+        "mark" => {
+            ensure_index_exists(&p.index_file)?;
+            let mut s = session::Session::load(&p.index_dir, name)?;
+            let entries = scan::read_index(&p.index_file)?;
+            let sha1_of = |path: &str| entries.iter().find(|e| e.path == path).map(|e| e.sha1.clone());
+            let targets: Vec<&String> = args[2..].iter().filter(|a| !a.starts_with("--")).collect();
+            if targets.is_empty() {
+                return Err(anyhow!("usage: indexer session mark <name> <chunk|path>..."));
+            }
+            let mut n = 0;
+            for t in targets {
+                let parts = session::resolve_target(t, &p.chunks_dir, &sha1_of)?;
+                n += s.mark_parts(&parts);
+            }
+            s.save(&p.index_dir)?;
+            println!("Session `{name}`: marked {n} part(s); {} file(s) delivered so far.", s.delivered.len());
+        }
+        "next" => {
+            ensure_index_exists(&p.index_file)?;
+            let mut s = session::Session::load(&p.index_dir, name)?;
+            let opts = parse_chunk_options(&args[2..])?;
+            let next = session::next_chunk(&mut s, &p.index_file, &p.chunks_dir, &p.dir_name, &opts)?;
+            let Some(file) = next.file else {
+                println!("Session `{name}` is up to date: nothing new or changed to deliver.");
+                return Ok(());
+            };
+            if args.iter().any(|a| a == "--mark") {
+                s.mark_parts(&next.parts);
+            }
+            s.save(&p.index_dir)?;
+            let files: BTreeSet<&str> = next.parts.iter().map(|p| p.path.as_str()).collect();
+            println!("Next chunk for `{name}`: {} ({} file(s)).", file.display(), files.len());
+            if next.remaining_files > 0 {
+                println!("  {} more file(s) waiting after this one.", next.remaining_files);
+            }
+            if !args.iter().any(|a| a == "--mark") {
+                println!("  After pasting it, run: indexer session mark {name} {}", file.display());
+            }
+        }
+        "show" => {
+            let s = session::Session::load(&p.index_dir, name)?;
+            let current = if p.index_file.exists() { scan::read_index(&p.index_file)? } else { Vec::new() };
+            let stale = current
+                .iter()
+                .filter(|e| s.delivered.contains_key(&e.path) && !s.has_seen(&e.path, &e.sha1))
+                .count();
+            let unseen = current.iter().filter(|e| !s.delivered.contains_key(&e.path)).count();
+            println!(
+                "Session `{}` (created {}): {} file(s) delivered, {} changed or partial since, {} never sent; {} chunk(s) issued.",
+                s.name,
+                s.created,
+                s.delivered.len(),
+                stale,
+                unseen,
+                s.issued
+            );
+        }
+        _ => return Err(usage()),
+    }
+    Ok(())
+}
+
+fn changelog_between(args: &[String]) -> Result<()> {
+    let p = resolve_paths()?;
+    let mut positional = Vec::new();
+    let mut it = args.iter();
+    while let Some(a) = it.next() {
+        if a == "--out" || a == "--similarity" {
+            it.next();
+        } else if !a.starts_with("--") {
+            positional.push(PathBuf::from(a));
+        }
+    }
+    let (old_path, new_path) = match positional.as_slice() {
+        [old] => {
+            ensure_index_exists(&p.index_file)?;
+            (old.clone(), p.index_file.clone())
+        }
+        [old, new] => (old.clone(), new.clone()),
+        _ => return Err(anyhow!("usage: indexer changelog <old.jsonl> [<new.jsonl>] [--out <file>]")),
+    };
+
+    let opts = diff::DiffOptions { similarity_threshold: parse_similarity(args)? };
+
+    let old = scan::read_index(&old_path).with_context(|| format!("reading {}", old_path.display()))?;
+    let new = scan::read_index(&new_path).with_context(|| format!("reading {}", new_path.display()))?;
+    let diff_val = diff::diff_indexes_with_opts(&old, &new, &opts);
+    let name = |path: &Path| path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+    let title = format!("{} → {}", name(&old_path), name(&new_path));
+    let md = changelog::changelog_markdown(&diff_val, &title, &changelog::package_roots(old.iter().chain(&new)));
+
+    match flag_value(args, "--out") {
+        Some(out) => {
+            util::safe_write(Path::new(out), md).with_context(|| format!("writing {out}"))?;
+            println!("Changelog written to {out}");
+        }
+        None => print!("{md}"),
+    }
+    Ok(())
+}
+
+fn semver_check(args: &[String]) -> Result<()> {
+    let specs: Vec<&str> = args
+        .iter()
+        .map(String::as_str)
+        .filter(|a| !a.starts_with("--"))
+        .collect();
+    let [old, new] = specs[..] else {
+        return Err(anyhow!("usage: indexer semver <old> <new> [--json]"));
+    };
+    let cwd = env::current_dir().context("failed to get current_dir")?;
+
+    let surface = |spec: &str| -> Result<semver::ApiSurface> {
+        let files = semver::load_symbols(spec, &cwd).with_context(|| format!("loading `{spec}`"))?;
+        semver::api_surface(&files).with_context(|| format!("public API of `{spec}`"))
+    };
+    let report = semver::compare(&surface(old)?, &surface(new)?);
+
+    if args.iter().any(|a| a == "--json") {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        println!(
+            "{old} -> {new}: {} pub item(s) -> {}; required bump: {}",
+            report.old_items,
+            report.new_items,
+            report.level.as_str()
+        );
+        for c in &report.changes {
+            println!("  [{}] {} ({}): {}", c.level.as_str(), c.path, c.kind, c.reason);
+        }
+    }
+
+    let breaking = report.changes.iter().filter(|c| c.level == semver::Level::Major).count();
+    if breaking > 0 {
+        return Err(Verdict(format!("{breaking} breaking API change(s) between {old} and {new}")).into());
+    }
+    Ok(())
+}
+
+/// `--chunking=fixed|cdc` for the v3 pack's chunk hashes (default: fixed),
+/// plus the layout flags `--shard`, `--zstd`, `--compact`, and `--full`.
+fn parse_v3_options(args: &[String]) -> Result<index_v3::V3Options> {
+    let chunking = match flag_value(args, "--chunking") {
+        None | Some("fixed") => index_v3::Chunking::default(),
+        Some("cdc" | "fastcdc") => index_v3::Chunking::CDC_DEFAULT,
+        Some(other) => return Err(anyhow!("bad --chunking `{other}` (expected fixed|cdc)")),
+    };
+    let has = |flag: &str| args.iter().any(|a| a == flag);
+    if has("--zstd") && !cfg!(feature = "zstd") {
+        return Err(anyhow!("--zstd needs a build with `--features zstd`"));
+    }
+    Ok(index_v3::V3Options {
+        chunking,
+        shard: has("--shard"),
+        zstd: has("--zstd"),
+        compact: has("--compact"),
+        full: has("--full"),
+    })
 }
 
 fn ensure_index_exists(p: &Path) -> Result<()> {
@@ -308,13 +685,161 @@ fn ensure_index_exists(p: &Path) -> Result<()> {
     ))
 }
 
-fn emit_v3() -> Result<()> {
+fn emit_v3(args: &[String]) -> Result<()> {
+    let opts = parse_v3_options(args)?;
     let p = resolve_paths()?;
     ensure_index_exists(&p.index_file)?;
     let out = p.index_dir.join("index_v3.json");
-    index_v3::build_index_v3(&p.index_file, &p.cwd, &out)
+    let v3 = index_v3::build_index_v3_with(&p.index_file, &p.cwd, &out, &opts)
         .context("emitting LLM-CODE-INDEX/v3")?;
     println!("LLM-CODE-INDEX/v3 written to {}", out.display());
+    print_v3_report(&v3);
+    Ok(())
+}
+
+fn print_v3_report(report: &index_v3::V3Report) {
+    if report.shards > 0 {
+        println!("  {} file(s) in {} shard(s)", report.files, report.shards);
+    }
+    if let Some(d) = &report.delta {
+        println!(
+            "  reused {} unchanged file(s); vs previous pack: {} added, {} removed, {} changed, {} anchor change(s) (index_v3.delta.json)",
+            report.reused,
+            d.files.added.len(),
+            d.files.removed.len(),
+            d.files.changed.len(),
+            d.anchors.len()
+        );
+    }
+    if report.degraded.is_empty() {
+        return;
+    }
+    println!("  {} file(s) degraded (no anchors, or left out of the pack):", report.degraded.len());
+    for (path, error) in &report.degraded {
+        println!("    {path}: {error}");
+    }
+}
+
+fn apply_patch(args: &[String]) -> Result<()> {
+    let dry_run = args.iter().any(|a| a == "--dry-run");
+    let Some(src) = args.iter().find(|a| !a.starts_with("--") || a.as_str() == "-") else {
+        return Err(anyhow!("usage: indexer apply <patch.diff|-> [--dry-run]"));
+    };
+    let p = resolve_paths()?;
+    let v3 = p.index_dir.join("index_v3.json");
+    if !v3.exists() {
+        return Err(anyhow!("{} not found. Run `indexer v3` first.", v3.display()));
+    }
+    let text = if src == "-" {
+        std::io::read_to_string(std::io::stdin()).context("reading patch from stdin")?
+    } else {
+        fs::read_to_string(src).with_context(|| format!("reading {src}"))?
+    };
+
+    let report = patch::apply_patch(&text, &v3, &p.cwd, dry_run)?;
+    for f in &report.files {
+        match &f.problem {
+            Some(why) => println!("{}: REFUSED ({why})", f.path),
+            None => println!("{}:", f.path),
+        }
+        for h in &f.hunks {
+            if h.problems.is_empty() {
+                println!("  ok       {}  [{}]", h.header, h.anchors.join(", "));
+            } else {
+                println!("  REFUSED  {}", h.header);
+                for why in &h.problems {
+                    println!("           - {why}");
+                }
+            }
+        }
+    }
+
+    let hunks: usize = report.files.iter().map(|f| f.hunks.len()).sum();
+    if !report.ok() {
+        return Err(anyhow!("patch refused: nothing was written"));
+    }
+    if dry_run {
+        println!("Dry run: {hunks} hunk(s) in {} file(s) would apply", report.files.len());
+    } else {
+        println!("Applied {hunks} hunk(s) to {} file(s)", report.written.len());
+    }
+    Ok(())
+}
+
+fn verify_v3(args: &[String]) -> Result<()> {
+    let p = resolve_paths()?;
+    let pack = match args.iter().find(|a| !a.starts_with("--")) {
+        Some(path) => PathBuf::from(path),
+        None => p.index_dir.join("index_v3.json"),
+    };
+    if !pack.exists() {
+        return Err(anyhow!("{} not found. Run `indexer v3` first.", pack.display()));
+    }
+
+    let report = verify::verify_pack(&pack, &p.cwd)?;
+    if args.iter().any(|a| a == "--json") {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        let s = &report.summary;
+        println!(
+            "{}: {} file(s): {} ok, {} modified, {} missing; {} anchor(s): {} ok, {} moved, {} stale, {} missing; {} pack error(s)",
+            report.pack,
+            s.files,
+            s.files_ok,
+            s.files_modified,
+            s.files_missing,
+            s.anchors,
+            s.anchors_ok,
+            s.anchors_moved,
+            s.anchors_stale,
+            s.anchors_missing,
+            s.pack_errors
+        );
+        for f in &report.files {
+            println!("  {} [{}]", f.path, f.status);
+            for why in &f.problems {
+                println!("      - {why}");
+            }
+            for a in &f.anchors {
+                let now = a.now.map(|[s, e]| format!(" -> now {s}-{e}")).unwrap_or_default();
+                println!("      {} {} {} (lines {}-{}{now})", a.status, a.kind, a.name, a.range[0], a.range[1]);
+            }
+        }
+    }
+
+    if !report.safe {
+        return Err(Verdict("pack is stale: re-run `indexer v3` before handing it to an agent".into()).into());
+    }
+    Ok(())
+}
+
+fn merkle_proof(args: &[String]) -> Result<()> {
+    let anchor = flag_value(args, "--anchor");
+    let mut pos = Vec::new();
+    let mut it = args.iter();
+    while let Some(a) = it.next() {
+        if a == "--anchor" || a == "--pack" {
+            it.next();
+        } else if !a.starts_with("--") {
+            pos.push(a.as_str());
+        }
+    }
+    let usage = || anyhow!("usage: indexer proof <path> (<chunk-index> | --anchor <name>) [--with-chunk] [--pack <file>]");
+    let target = match (pos.as_slice(), anchor) {
+        ([_, idx], None) => verify::ProofTarget::Chunk(idx.parse().map_err(|_| anyhow!("bad chunk index `{idx}`"))?),
+        ([_], Some(name)) => verify::ProofTarget::Anchor(name),
+        _ => return Err(usage()),
+    };
+    let path = pos[0].trim_start_matches("./").replace('\\', "/");
+
+    let p = resolve_paths()?;
+    let pack = flag_value(args, "--pack").map_or_else(|| p.index_dir.join("index_v3.json"), PathBuf::from);
+    if !pack.exists() {
+        return Err(anyhow!("{} not found. Run `indexer v3` first.", pack.display()));
+    }
+    let with_chunk = args.iter().any(|a| a == "--with-chunk");
+    let bundle = verify::prove_in_pack(&pack, &p.cwd, &path, &target, with_chunk)?;
+    println!("{}", serde_json::to_string_pretty(&bundle)?);
     Ok(())
 }
 
@@ -356,10 +881,42 @@ fn print_help_dispatch(sub: Option<&str>) -> Result<()> {
             print_help_chunk();
             Ok(())
         }
+        Some("pack") => {
+            print_help_pack();
+            Ok(())
+        }
+        Some("session") => {
+            print_help_session();
+            Ok(())
+        }
+        Some("delta-pack") => {
+            print_help_delta_pack();
+            Ok(())
+        }
+        Some("changelog") => {
+            print_help_changelog();
+            Ok(())
+        }
+        Some("semver") => {
+            print_help_semver();
+            Ok(())
+        }
         Some("v3") | Some("emit-v3") => {
             print_help_v3();
             Ok(())
         }
+        Some("apply") => {
+            print_help_apply();
+            Ok(())
+        }
+        Some("verify") => {
+            print_help_verify();
+            Ok(())
+        }
+        Some("proof") => {
+            print_help_proof();
+            Ok(())
+        }
         Some(cmd) if is_help_flag(cmd) => {
             print_help_main();
             Ok(())
@@ -386,7 +943,15 @@ GLOBAL COMMANDS:
     types        Rebuild types markdown (structs, enums) grouped by file
     functions    Rebuild functions markdown (public/internal/tests) grouped by file
     chunk        Split index into pasteable chunks with token caps
+    pack         Fit the most important files into one total token budget
+    delta-pack   Paste chunks with only what changed since a snapshot or git ref
+    session      Track which chunks each LLM conversation has seen; emit what's new
+    changelog    Render a Markdown changelog between two index snapshots
+    semver       Compare the public Rust API of two versions; fail on breaking changes
     v3           Emit LLM-CODE-INDEX/v3 JSON (.gpt_index/index_v3.json)
+    apply        Apply a unified diff only where it stays inside verified v3 anchors
+    verify       Check index_v3.json hashes and anchors against the working tree
+    proof        Print a merkle inclusion proof for a v3 chunk or anchor
 
 GLOBAL FLAGS:
     -h, --help       Show this help or help for a subcommand
@@ -398,6 +963,9 @@ EXAMPLES:
     indexer chunk --help
     indexer init
     indexer chunk --cap=12000
+    indexer chunk --format=jsonl
+    indexer pack --budget 100000
+    indexer semver v0.2.0 .
     indexer v3
 "#
     );
@@ -420,6 +988,8 @@ USAGE:
 
 NOTES:
     - Uses JSONL index; views are rebuilt every run.
+    - Secrets are redacted from snippets; hits (file, line, kind) are listed in
+      indexes/<slug>.redactions.json. Add project rules in .gptredact.
 "#
     );
 }
@@ -430,10 +1000,24 @@ fn print_help_reindex() {
 
 DESCRIPTION:
     Archive the previous index snapshot (history/full/<slug>_<ts>.jsonl),
-    write a structured diff (history/diffs/<slug>_<ts>.json), and rebuild views.
+    write a structured diff (history/diffs/<slug>_<ts>.json) with symbol-level
+    deltas for Rust files, a Markdown changelog (history/diffs/<slug>_<ts>.md),
+    an "API changes" report (history/diffs/<slug>_<ts>_api.md), and rebuild views.
+
+    Removed/added files whose snippets share enough lines (Jaccard over distinct
+    trimmed lines) are reported as `renamed_with_changes` with a similarity
+    score; added files similar to a file that still exists are `copied`.
 
 USAGE:
+    indexer reindex [--similarity=<0..1>] [--chunking=<fixed|cdc>]
+
+FLAGS:
+    --similarity=<T>   Minimum similarity for rename/copy pairing (default: 0.5)
+    --chunking=<M>     v3 chunk hashing, as for `indexer v3` (default: fixed)
+
+EXAMPLES:
     indexer reindex
+    indexer reindex --similarity=0.7
 "#
     );
 }
@@ -503,18 +1087,38 @@ fn print_help_chunk() {
         r#"indexer chunk
 
 DESCRIPTION:
-    Split the indexed content into pasteable chunks (<slug>_paste_*.md), trying
+    Split the indexed content into pasteable chunks (<slug>_paste_*.<ext>), trying
     to respect an approximate token budget per chunk.
 
 USAGE:
-    indexer chunk [--cap=<N>]
+    indexer chunk [--cap=<N>] [--format=<md|xml|jsonl|txt>] [--order=<path|deps>]
+                  [--preamble[=<N>]]
 
 FLAGS:
-    --cap=<N>     Approximate token cap per chunk (default: 15000)
+    --cap=<N>         Approximate token cap per chunk (default: 15000)
+    --format=<F>      Chunk framing (default: md)
+                        md     `## path` headings + fenced code
+                        xml    <document path=".." lang=".." sha1=".."><source>..</source></document>
+                        jsonl  one JSON record per part
+                        txt    plain text with BEGIN/END delimiter lines
+    --order=<O>       File order before packing (default: path)
+                        path   alphabetical by path
+                        deps   cluster files linked by imports, definitions
+                               before their users, so related files share chunks
+    --preamble[=<N>]  Prepend an orientation block to every chunk: project
+                      one-liner, module list, this chunk's files and
+                      "chunk N of M" prev/next navigation. N is its token
+                      allowance (default: 400), taken out of --cap.
+
+    Each file section lists "see also: chunk N (path)" for import neighbours
+    that were packed into a different chunk.
 
 EXAMPLES:
     indexer chunk
     indexer chunk --cap=12000
+    indexer chunk --format=xml
+    indexer chunk --order=deps
+    indexer chunk --preamble=600
 
 REQUIRES:
     A prior `indexer init` or `indexer reindex`
@@ -522,6 +1126,176 @@ REQUIRES:
     );
 }
 
+fn print_help_pack() {
+    println!(
+        r#"indexer pack
+
+DESCRIPTION:
+    Rank indexed files by weighted signals and greedily pack the highest-value
+    content into a single file (<slug>_pack.<ext>) under a total token budget.
+    Each file degrades full source -> snippet -> signatures until it fits;
+    anything left over is dropped. Decisions go to <slug>_pack_report.json.
+
+USAGE:
+    indexer pack [--budget <N>] [--weights <spec>] [--format=<md|xml|jsonl|txt>]
+
+FLAGS:
+    --budget <N>       Total token budget (default: 100000)
+    --weights <spec>   Comma list of key=value (defaults shown):
+                         role=1       Bin/Lib/Core first, Test/Doc last
+                         exports=0.5  public export count
+                         churn=0.5    sha1 changes across history snapshots
+                         size=0.25    size penalty
+    --format=<F>       Output framing, as for `indexer chunk` (default: md)
+
+EXAMPLES:
+    indexer pack --budget 100000
+    indexer pack --budget=60000 --weights churn=1,size=0.5
+
+REQUIRES:
+    A prior `indexer init` or `indexer reindex`
+"#
+    );
+}
+
+fn print_help_session() {
+    println!(
+        r#"indexer session
+
+DESCRIPTION:
+    Track what each LLM conversation has been given. A session records, per
+    path, the sha1 that was delivered (.gpt_index/sessions/<name>.json).
+    `next` packs one chunk of files the session has not seen, or whose sha1
+    changed since delivery; chunk files resolve to their contents through
+    chunks/manifest.json, written by every chunk run.
+
+USAGE:
+    indexer session new  <name>
+    indexer session mark <name> <chunk|path>...
+    indexer session next <name> [--mark] [--cap=<N>] [--format=<md|xml|jsonl|txt>] [--order=<path|deps>]
+    indexer session show <name>
+
+ARGS:
+    <chunk>   A chunk file (path or file name) from `chunk`, `delta-pack` or `session next`
+    <path>    An indexed file path; marked as delivered at its current sha1
+
+FLAGS:
+    --mark    Mark the emitted chunk as delivered right away
+    --cap, --format, --order   As for `indexer chunk`
+
+EXAMPLES:
+    indexer session new review
+    indexer session mark review .gpt_index/chunks/app_paste_1.md src/main.rs
+    indexer session next review --cap=12000
+    indexer session next review --mark
+
+REQUIRES:
+    A prior `indexer init` or `indexer reindex`
+"#
+    );
+}
+
+fn print_help_delta_pack() {
+    println!(
+        r#"indexer delta-pack
+
+DESCRIPTION:
+    Chunk only what changed since a baseline, for LLM sessions that have
+    already seen the code. Added, modified, renamed-with-changes and copied
+    files are included; Rust files carry just the items whose source
+    changed. Chunk 1 starts with a DELTA.md header summarising the delta
+    and listing removed and renamed paths. Written to
+    chunks/<slug>_delta_<N>.<ext>.
+
+USAGE:
+    indexer delta-pack --since <snapshot.jsonl|git-ref> [--cap=<N>] [--format=<md|xml|jsonl|txt>]
+
+FLAGS:
+    --since <S>    Baseline: an index snapshot (e.g. history/full/<slug>_<ts>.jsonl)
+                   or a git revision (exported with `git archive` and scanned;
+                   needs only `git`, no external `tar`)
+    --cap=<N>      Approximate token cap per chunk (default: 15000)
+    --format=<F>   Chunk framing, as for `indexer chunk` (default: md)
+
+EXAMPLES:
+    indexer delta-pack --since HEAD~3
+    indexer delta-pack --since .gpt_index/history/full/app_20250101_120000.jsonl --format=xml
+
+REQUIRES:
+    A prior `indexer init` or `indexer reindex` (the current index is the "after" side)
+"#
+    );
+}
+
+fn print_help_changelog() {
+    println!(
+        r#"indexer changelog
+
+DESCRIPTION:
+    Diff two index snapshots and render a Markdown changelog: files grouped by
+    package (dirs with Cargo.toml / package.json / pyproject.toml) or top-level
+    dir; added / removed / renamed / copied / modified with size and LOC
+    deltas; role and module changes highlighted; tag churn summarised.
+    `indexer reindex` writes the same changelog to history/diffs/<slug>_<ts>.md.
+
+USAGE:
+    indexer changelog <old.jsonl> [<new.jsonl>] [--out <file>] [--similarity=<T>]
+
+ARGS:
+    <old.jsonl>        Older snapshot (e.g. .gpt_index/history/full/<slug>_<ts>.jsonl)
+    <new.jsonl>        Newer snapshot (default: the current index)
+
+FLAGS:
+    --out <file>       Write to a file instead of stdout
+    --similarity=<T>   Minimum similarity for rename/copy pairing (default: 0.5)
+
+EXAMPLES:
+    indexer changelog .gpt_index/history/full/app_20250101_120000.jsonl
+    indexer changelog old.jsonl new.jsonl --out CHANGES.md
+"#
+    );
+}
+
+fn print_help_semver() {
+    println!(
+        r#"indexer semver
+
+DESCRIPTION:
+    Compare the public API of a Rust library crate between two versions and
+    report the required semver bump. The API is every `pub` item reachable
+    from src/lib.rs via `pub mod` and `pub use` (globs included); pub(crate)
+    and private items are ignored.
+
+    major: removed items, changed signatures, removed/retyped pub fields,
+           new fields on literal-constructible structs, new variants on
+           exhaustive enums, newly #[non_exhaustive] types
+    minor: added items, pub fields or #[non_exhaustive] variants
+    patch: no public API change
+
+EXIT STATUS:
+    0    No breaking change (minor or patch)
+    1    The comparison could not run (bad revision, unreadable snapshot,
+         source that does not parse)
+    2    At least one breaking (major) change
+
+USAGE:
+    indexer semver <old> <new> [--json]
+
+ARGS:
+    <old>, <new>   A JSONL snapshot (e.g. .gpt_index/history/full/<slug>_<ts>.jsonl),
+                   a git revision (tag, branch, sha), or `.` for the working tree
+
+FLAGS:
+    --json         Print the report as JSON
+
+EXAMPLES:
+    indexer semver v0.2.0 .
+    indexer semver HEAD~5 HEAD --json
+    indexer semver .gpt_index/history/full/app_20250101T000000.jsonl .gpt_index/indexes/app.jsonl
+"#
+    );
+}
+
 fn print_help_v3() {
     println!(
         r#"indexer v3
@@ -531,12 +1305,132 @@ DESCRIPTION:
       - file sha256 + Merkle chunking
       - per-anchor verbatim slices (base64) + slice sha256
       - normalized schemas & signatures
+    Anchors are extracted for Rust, Python, TypeScript/JavaScript and Go.
 
 USAGE:
-    indexer v3
+    indexer v3 [--chunking=<fixed|cdc>] [--shard] [--zstd] [--compact] [--full]
+
+FLAGS:
+    --chunking=<M>   fixed: 16 KB chunks (default)
+                     cdc:   FastCDC content-defined chunks (4/16/64 KB min/avg/max);
+                            chunks away from an edit keep their hashes
+    --shard          Write index_v3.json as a small manifest plus one shard per
+                     package / top-level dir under index_v3.shards/
+    --zstd           zstd-compress the shards (implies --shard; needs a build
+                     with `--features zstd`)
+    --compact        Single-line JSON instead of pretty-printed
+    --full           Re-parse every file instead of reusing unchanged entries
+                     from the previous pack
+
+NOTES:
+    Files that fail to parse keep hashes and chunks, get no anchors and a
+    `parse_error`; unreadable paths are listed under `skipped`. Both are
+    printed as degraded, and the pack is still written.
 
 REQUIRES:
     A prior `indexer init` or `indexer reindex` (for .jsonl existence)
 "#
     );
 }
+
+fn print_help_apply() {
+    println!(
+        r#"indexer apply
+
+DESCRIPTION:
+    Apply a unified diff (e.g. an LLM-proposed patch) under the v3
+    patch_contract. Each target file must still match its file_sha256 in
+    index_v3.json, and each hunk may only remove or insert lines inside an
+    anchor whose slice_sha256 still matches. Hunks are located by context.
+    Either every hunk applies or nothing is written; refusals are reported
+    per hunk and exit non-zero.
+
+USAGE:
+    indexer apply <patch.diff|-> [--dry-run]
+
+ARGS:
+    <patch.diff>   Unified diff (`diff -u` / `git diff`); `-` reads stdin
+
+FLAGS:
+    --dry-run      Check every hunk and report, without writing
+
+EXAMPLES:
+    indexer apply fix.diff --dry-run
+    git diff | indexer apply -
+
+REQUIRES:
+    A current `indexer v3` (.gpt_index/index_v3.json)
+"#
+    );
+}
+
+fn print_help_verify() {
+    println!(
+        r#"indexer verify
+
+DESCRIPTION:
+    Check whether a v3 pack still describes the working tree. Recomputes every
+    file_sha256, 16 KB chunk hash and merkle root, checks that each anchor's
+    verbatim_b64 matches its slice_sha256, and that the slice still sits at
+    the recorded line range. Anchors that drifted are reported as moved (same
+    bytes elsewhere), stale (same item, new bytes) or missing.
+
+EXIT STATUS:
+    0    The pack is fully current
+    1    The check could not run (missing or unreadable pack, I/O error)
+    2    The pack is stale: files, chunks or anchors drifted
+
+USAGE:
+    indexer verify [pack] [--json]
+
+ARGS:
+    [pack]    Pack to check (default: .gpt_index/index_v3.json)
+
+FLAGS:
+    --json    Print the full machine-readable report
+
+EXAMPLES:
+    indexer verify
+    indexer verify --json > verify.json
+
+REQUIRES:
+    A prior `indexer v3`
+"#
+    );
+}
+
+fn print_help_proof() {
+    println!(
+        r#"indexer proof
+
+DESCRIPTION:
+    Print merkle inclusion proofs (JSON) showing that chunks belong to a
+    file's merkle_root in index_v3.json, so one chunk can be sent with its
+    proof instead of the whole pack. With --anchor, every chunk overlapping
+    the anchor's lines is proven. Check proofs with `merkle::verify_chunk`.
+    Tree scheme: leaf = sha256(0x00 || chunk_sha256), node =
+    sha256(0x01 || left || right), an odd last node is paired with itself,
+    root = sha256(0x02 || leaf count as u64 big-endian || top node).
+
+USAGE:
+    indexer proof <path> <chunk-index> [--with-chunk] [--pack <file>]
+    indexer proof <path> --anchor <name> [--with-chunk] [--pack <file>]
+
+ARGS:
+    <path>          File path as recorded in the pack
+    <chunk-index>   0-based chunk index
+
+FLAGS:
+    --anchor <N>    Anchor name (`parse` or `fn parse`)
+    --with-chunk    Embed the chunk bytes (base64), read from the working tree
+    --pack <file>   Pack to read (default: .gpt_index/index_v3.json)
+
+EXAMPLES:
+    indexer proof src/lib.rs 0
+    indexer proof src/lib.rs --anchor build_index --with-chunk
+
+REQUIRES:
+    A current `indexer v3`
+"#
+    );
+}
@@ -0,0 +1,420 @@
+// indexer/src/delta_pack.rs
+//! "Changed since" paste pack for follow-up LLM sessions.
+//!
+//! - Diffs a baseline (index snapshot or git revision) against the current
+//!   index with `diff::diff_indexes`.
+//! - Emits only added / modified / renamed-with-changes / copied files; Rust
+//!   files carry just the items whose source hash changed (read from disk,
+//!   secrets redacted), everything else its indexed snippet.
+//! - Chunk 1 opens with a `DELTA.md` header: what changed, removed and renamed
+//!   paths. Packing, cap and formats match `indexer chunk`.
+
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::Write as _,
+    fs,
+    path::Path,
+    process::Command,
+};
+
+use crate::{
+    chunker::{self, ChunkOptions, Part, MAX_SECTION_CHARS, TARGET_SECTION_TOKENS},
+    diff::diff_indexes,
+    file_intent_entry::FileIntentEntry,
+    redact::Redactor,
+    scan,
+    symbols::{hashed, keyed, rust_item_lines, Symbol},
+    util,
+};
+
+/// What went into a delta pack.
+#[derive(Debug, Default)]
+pub struct DeltaPackReport {
+    pub chunks: usize,
+    /// Files with content in the pack.
+    pub files: usize,
+    /// Of those, Rust files reduced to their changed items.
+    pub item_only: usize,
+    pub removed: Vec<String>,
+    /// (from, to) for pure and changed renames.
+    pub renamed: Vec<(String, String)>,
+}
+
+/// Write `<out_prefix><N>.<ext>` chunks holding what changed from `old` to `new`.
+/// `since` labels the baseline in the header; `root` is where changed Rust
+/// files are read from.
+pub fn build_delta_pack(
+    old: &[FileIntentEntry],
+    new: &[FileIntentEntry],
+    root: &Path,
+    out_prefix: &str,
+    since: &str,
+    opts: &ChunkOptions,
+) -> Result<DeltaPackReport> {
+    let diff = diff_indexes(old, new);
+    let by_path: HashMap<&str, &FileIntentEntry> = new.iter().map(|e| (e.path.as_str(), e)).collect();
+    let old_by_path: HashMap<&str, &FileIntentEntry> = old.iter().map(|e| (e.path.as_str(), e)).collect();
+    let redactor = Redactor::load(root)?;
+    let mut report = DeltaPackReport::default();
+
+    // (path, baseline path) for every file whose content is included.
+    let mut included: Vec<(&str, Option<&str>)> = Vec::new();
+    for f in items(&diff, "added") {
+        included.push((str_of(&f["path"]), None));
+    }
+    for f in items(&diff, "copied") {
+        included.push((str_of(&f["to"]), None));
+    }
+    for f in items(&diff, "modified") {
+        // Signal-only changes (same sha1) have nothing new to paste.
+        if f["before"]["sha1"] != f["after"]["sha1"] {
+            included.push((str_of(&f["path"]), Some(str_of(&f["path"]))));
+        }
+    }
+    for f in items(&diff, "renamed_with_changes") {
+        included.push((str_of(&f["to"]), Some(str_of(&f["from"]))));
+        report.renamed.push((str_of(&f["from"]).into(), str_of(&f["to"]).into()));
+    }
+    for f in items(&diff, "renamed") {
+        report.renamed.push((str_of(&f["from"]).into(), str_of(&f["to"]).into()));
+    }
+    for f in items(&diff, "removed") {
+        report.removed.push(str_of(&f["path"]).into());
+    }
+    included.sort_by_key(|(p, _)| *p);
+    included.dedup_by_key(|(p, _)| *p);
+    report.renamed.sort();
+
+    let mut expanded: Vec<Part> = Vec::new();
+    for (path, base) in &included {
+        let Some(e) = by_path.get(path) else { continue };
+        let mut part = Part {
+            path: e.path.clone(),
+            lang: e.lang.clone(),
+            sha1: e.sha1.clone(),
+            size: e.size.to_string(),
+            last_modified: e.last_modified.clone(),
+            summary: e.summary.clone(),
+            part_idx: 1,
+            part_total: 1,
+            body: e.snippet.clone(),
+            token_estimate: 0,
+            detail: None,
+            see_also: Vec::new(),
+        };
+        let before = base.and_then(|b| old_by_path.get(b)).and_then(|b| b.symbols.as_deref());
+        let after = e.symbols.as_deref();
+        let body = before.zip(after).and_then(|(b, a)| changed_items(root, path, b, a, &redactor));
+        if let Some(body) = body {
+            part.body = body;
+            part.detail = Some("changed items");
+            report.item_only += 1;
+        }
+        expanded.extend(chunker::split_part(part, TARGET_SECTION_TOKENS, MAX_SECTION_CHARS));
+    }
+    report.files = included.len();
+
+    let header = header_text(&diff, since, &report);
+    let mut all = chunker::split_part(
+        Part {
+            path: "DELTA.md".into(),
+            lang: "markdown".into(),
+            sha1: String::new(),
+            size: header.len().to_string(),
+            last_modified: String::new(),
+            summary: None,
+            part_idx: 1,
+            part_total: 1,
+            body: header,
+            token_estimate: 0,
+            detail: Some("delta header"),
+            see_also: Vec::new(),
+        },
+        TARGET_SECTION_TOKENS,
+        MAX_SECTION_CHARS,
+    );
+    all.extend(expanded);
+
+    if let Some(parent) = Path::new(out_prefix).parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent).with_context(|| format!("creating {}", parent.display()))?;
+    }
+    let chunks = chunker::pack_parts(all, opts.token_cap.max(256), opts.format);
+    for (i, parts) in chunks.iter().enumerate() {
+        chunker::write_chunk(out_prefix, i + 1, parts, opts.format, None)?;
+    }
+    chunker::record_chunks(out_prefix, opts.format, &chunks)?;
+    report.chunks = chunks.len();
+    Ok(report)
+}
+
+/// Index entries for git revision `rev`, scanned exactly like the working tree
+/// (same snippets, sha1s and symbols) from a `git archive` export of the
+/// directory `root` corresponds to. Needs only `git`: the tar stream is
+/// unpacked here, not by an external `tar`.
+pub fn snapshot_at_rev(rev: &str, root: &Path) -> Result<Vec<FileIntentEntry>> {
+    let git = |args: &[&str]| -> Result<String> {
+        let out = Command::new("git")
+            .args(args)
+            .current_dir(root)
+            .output()
+            .with_context(|| format!("running git {}", args.join(" ")))?;
+        if !out.status.success() {
+            bail!("git {} failed: {}", args.join(" "), String::from_utf8_lossy(&out.stderr).trim());
+        }
+        Ok(String::from_utf8_lossy(&out.stdout).trim().to_string())
+    };
+    git(&["rev-parse", "--verify", "--quiet", &format!("{rev}^{{commit}}")])
+        .with_context(|| format!("`{rev}` is neither a snapshot file nor a git revision"))?;
+    let prefix = git(&["rev-parse", "--show-prefix"])?;
+
+    let tmp = std::env::temp_dir().join(format!("indexer-since-{}-{}", std::process::id(), util::now_ts_compact()));
+    fs::create_dir_all(&tmp).with_context(|| format!("creating {}", tmp.display()))?;
+    let result = (|| {
+        let archive = Command::new("git")
+            .args(["archive", "--format=tar", &format!("{rev}:{prefix}")])
+            .current_dir(root)
+            .output()
+            .context("running git archive")?;
+        if !archive.status.success() {
+            bail!("exporting {rev}:{prefix} failed: {}", String::from_utf8_lossy(&archive.stderr).trim());
+        }
+        untar(&archive.stdout, &tmp)?;
+        scan::index_project(&tmp)
+    })();
+    let _ = fs::remove_dir_all(&tmp);
+    result.with_context(|| format!("indexing {rev}"))
+}
+
+/* ================================ Helpers ================================ */
+
+/// Unpacks the ustar stream `git archive` writes: regular files and
+/// directories, with pax `path=` overrides. Links and other entry types are
+/// skipped; entries that would land outside `into` are an error.
+fn untar(data: &[u8], into: &Path) -> Result<()> {
+    let field = |h: &[u8], r: std::ops::Range<usize>| {
+        let f = &h[r];
+        String::from_utf8_lossy(&f[..memchr::memchr(0, f).unwrap_or(f.len())]).into_owned()
+    };
+    let mut pax_path: Option<String> = None;
+    let mut at = 0;
+    while let Some(h) = data.get(at..at + 512) {
+        if h.iter().all(|&b| b == 0) {
+            break;
+        }
+        let size = usize::from_str_radix(field(h, 124..136).trim(), 8).context("tar entry size")?;
+        let body = data.get(at + 512..at + 512 + size).context("truncated tar entry")?;
+        at += 512 + size.div_ceil(512) * 512;
+
+        let name = match (pax_path.take(), field(h, 345..500)) {
+            (Some(p), _) => p,
+            (None, pre) if !pre.is_empty() => format!("{pre}/{}", field(h, 0..100)),
+            (None, _) => field(h, 0..100),
+        };
+        match h[156] {
+            b'x' => {
+                // "<len> key=value\n" records; only the path matters here.
+                pax_path = String::from_utf8_lossy(body)
+                    .lines()
+                    .find_map(|r| r.split_once(' ')?.1.strip_prefix("path=").map(str::to_string));
+                continue;
+            }
+            b'0' | 0 | b'5' => {}
+            _ => continue,
+        }
+        let rel = Path::new(&name);
+        if !rel.components().all(|c| matches!(c, std::path::Component::Normal(_) | std::path::Component::CurDir)) {
+            bail!("tar entry `{name}` escapes the export directory");
+        }
+        let dest = into.join(rel);
+        if h[156] == b'5' {
+            fs::create_dir_all(&dest).with_context(|| format!("creating {}", dest.display()))?;
+        } else {
+            if let Some(parent) = dest.parent() {
+                fs::create_dir_all(parent).with_context(|| format!("creating {}", parent.display()))?;
+            }
+            fs::write(&dest, body).with_context(|| format!("writing {}", dest.display()))?;
+        }
+    }
+    Ok(())
+}
+
+fn items<'a>(v: &'a Value, key: &str) -> impl Iterator<Item = &'a Value> {
+    v[key].as_array().into_iter().flatten()
+}
+
+fn str_of(v: &Value) -> &str {
+    v.as_str().unwrap_or_default()
+}
+
+/// Source of the added/changed items of a Rust file (item hash differs or
+/// item is new), each under a `// lines a-b` marker, plus a `// removed:`
+/// line. Items are matched by their [`keyed`] key, not their bare name. `None` when that can't be told (symbols without hashes from older
+/// snapshots), nothing item-shaped changed (imports, consts) or the file is
+/// unreadable, so the caller falls back to the snippet.
+fn changed_items(root: &Path, path: &str, before: &[Symbol], after: &[Symbol], redactor: &Redactor) -> Option<String> {
+    if before.iter().chain(after).filter(|s| hashed(s)).any(|s| s.hash.is_none()) {
+        return None;
+    }
+    let old_hashes: HashMap<String, &Option<String>> =
+        keyed(before).into_iter().filter(|(_, s)| hashed(s)).map(|(k, s)| (k, &s.hash)).collect();
+    let new_items: Vec<(String, &Symbol)> = keyed(after).into_iter().filter(|(_, s)| hashed(s)).collect();
+    let new_keys: BTreeSet<&str> = new_items.iter().map(|(k, _)| k.as_str()).collect();
+    let wanted: BTreeSet<&str> =
+        new_items.iter().filter(|(k, s)| old_hashes.get(k) != Some(&&s.hash)).map(|(k, _)| k.as_str()).collect();
+    let removed: BTreeSet<&str> = old_hashes.keys().map(String::as_str).filter(|k| !new_keys.contains(k)).collect();
+    if wanted.is_empty() {
+        return None;
+    }
+
+    let src = fs::read_to_string(root.join(path)).ok()?;
+    let mut spans: Vec<(usize, usize)> = rust_item_lines(&src)?
+        .into_iter()
+        .filter(|(key, _, _)| wanted.contains(key.as_str()))
+        .map(|(_, a, b)| (a, b))
+        .collect();
+    if spans.is_empty() {
+        return None;
+    }
+    spans.sort_unstable();
+    // Items in inline mods can touch or overlap; merge adjacent spans.
+    let mut merged: Vec<(usize, usize)> = Vec::new();
+    for (a, b) in spans {
+        match merged.last_mut() {
+            Some(last) if a <= last.1 + 1 => last.1 = last.1.max(b),
+            _ => merged.push((a, b)),
+        }
+    }
+
+    let lines: Vec<&str> = src.lines().collect();
+    let mut body = String::new();
+    if !removed.is_empty() {
+        let _ = writeln!(body, "// removed: {}\n", removed.into_iter().collect::<Vec<_>>().join(", "));
+    }
+    for (i, (a, b)) in merged.iter().enumerate() {
+        if i > 0 {
+            body.push('\n');
+        }
+        if a == b {
+            let _ = writeln!(body, "// line {a}");
+        } else {
+            let _ = writeln!(body, "// lines {a}-{b}");
+        }
+        let end = (*b).min(lines.len());
+        for l in lines.get(a.saturating_sub(1)..end).unwrap_or_default() {
+            body.push_str(l);
+            body.push('\n');
+        }
+    }
+    Some(redactor.redact(&body).0)
+}
+
+fn header_text(diff: &Value, since: &str, report: &DeltaPackReport) -> String {
+    let s = &diff["summary"];
+    let mut out = String::new();
+    let _ = writeln!(out, "# Changes since {since}\n");
+    let _ = writeln!(
+        out,
+        "Only files added or modified since `{since}` follow; unchanged files are omitted, so keep the \
+         earlier paste in context. Rust files marked `changed items` show just the changed items \
+         (`// lines a-b` of the current file), not the whole file.\n"
+    );
+    let _ = writeln!(
+        out,
+        "- added: {}, modified: {}, renamed: {}, copied: {}, removed: {}",
+        s["added"],
+        s["modified"],
+        report.renamed.len(),
+        s["copied"],
+        report.removed.len()
+    );
+    let _ = writeln!(out, "- files in this pack: {} ({} as changed items only)", report.files, report.item_only);
+    if !report.removed.is_empty() {
+        let _ = writeln!(out, "\n## Removed\n");
+        for p in &report.removed {
+            let _ = writeln!(out, "- `{p}`");
+        }
+    }
+    if !report.renamed.is_empty() {
+        let _ = writeln!(out, "\n## Renamed\n");
+        for (from, to) in &report.renamed {
+            let _ = writeln!(out, "- `{from}` → `{to}`");
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::symbols::rust_symbols;
+
+    #[test]
+    fn packs_changed_items_and_lists_removals() {
+        let dir = std::env::temp_dir().join(format!("indexer-delta-test-{}", std::process::id()));
+        fs::create_dir_all(dir.join("src")).unwrap();
+        let old_src = "pub fn keep() {}\n\npub fn change(x: u8) {}\n\nfn body_only() { 1; }\n";
+        let new_src = "pub fn keep() {}\n\npub fn change(x: u16) {\n    let _ = x;\n}\n\nfn body_only() { 2; }\n";
+        fs::write(dir.join("src/a.rs"), new_src).unwrap();
+
+        let entry = |path: &str, sha1: &str, src: &str| FileIntentEntry {
+            path: path.into(),
+            lang: "rust".into(),
+            sha1: sha1.into(),
+            snippet: src.into(),
+            symbols: rust_symbols(src),
+            ..Default::default()
+        };
+        let old = vec![entry("src/a.rs", "1", old_src), entry("src/gone.rs", "2", "fn g() {}")];
+        let new = vec![entry("src/a.rs", "3", new_src), entry("src/new.rs", "4", "fn n() {}")];
+
+        let prefix = dir.join("out/delta_");
+        let r = build_delta_pack(&old, &new, &dir, prefix.to_str().unwrap(), "HEAD~1", &ChunkOptions::default()).unwrap();
+        assert_eq!((r.chunks, r.files, r.item_only), (1, 2, 1));
+        assert_eq!(r.removed, ["src/gone.rs"]);
+
+        let md = fs::read_to_string(dir.join("out/delta_1.md")).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+        assert!(md.contains("# Changes since HEAD~1"), "{md}");
+        assert!(md.contains("- `src/gone.rs`"), "{md}");
+        assert!(md.contains("// lines 3-5\npub fn change(x: u16) {"), "{md}");
+        assert!(md.contains("// line 7\nfn body_only() { 2; }"), "{md}");
+        assert!(!md.contains("pub fn keep()"), "{md}");
+        assert!(md.contains("fn n() {}"), "{md}");
+    }
+
+    #[test]
+    fn untar_unpacks_files_dirs_and_pax_paths_but_not_escapes() {
+        let entry = |kind: u8, name: &str, body: &[u8]| {
+            let mut h = vec![0u8; 512];
+            h[..name.len()].copy_from_slice(name.as_bytes());
+            h[124..135].copy_from_slice(format!("{:011o}", body.len()).as_bytes());
+            h[156] = kind;
+            h.extend_from_slice(body);
+            h.resize(h.len().div_ceil(512) * 512, 0);
+            h
+        };
+        let long = format!("src/{}.rs", "x".repeat(120));
+        let pax = format!("{} path={long}\n", long.len() + 10);
+        let tar = [
+            entry(b'g', "pax_global_header", b"52 comment=abc\n"),
+            entry(b'5', "src/", b""),
+            entry(b'0', "src/a.rs", b"fn a() {}\n"),
+            entry(b'x', "pax", pax.as_bytes()),
+            entry(b'0', "truncated-name", b"fn b() {}\n"),
+            entry(b'2', "src/link", b""),
+            vec![0; 1024],
+        ]
+        .concat();
+
+        let dir = std::env::temp_dir().join(format!("indexer-untar-test-{}", std::process::id()));
+        untar(&tar, &dir).unwrap();
+        let a = fs::read_to_string(dir.join("src/a.rs")).unwrap();
+        let b = fs::read_to_string(dir.join(&long)).unwrap();
+        let link = dir.join("src/link").exists();
+        let escape = untar(&entry(b'0', "../evil.rs", b""), &dir).is_err();
+        let _ = fs::remove_dir_all(&dir);
+        assert_eq!((a.as_str(), b.as_str(), link, escape), ("fn a() {}\n", "fn b() {}\n", false, true));
+    }
+}
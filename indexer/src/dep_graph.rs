@@ -0,0 +1,372 @@
+// indexer/src/dep_graph.rs
+//! Module dependency graph built from the index's skimmed `imports`.
+//!
+//! - Resolves Rust (`crate::`/`self::`/`super::` paths, `{}` groups),
+//!   Python (dotted modules) and JS/TS (relative specifiers) to indexed files.
+//! - Groups files into connected components (tightly-coupled clusters).
+//! - Orders each component topologically: definitions before their users,
+//!   falling back to path order inside cycles.
+//!
+//! Purely path/string based; unresolvable imports (std, third-party) are ignored.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use crate::helpers::{python_module_id, rust_module_id};
+
+/// Minimal view of an index entry needed to build the graph.
+#[derive(Clone, Debug)]
+pub struct DepNode<'a> {
+    pub path: &'a str,
+    pub lang: &'a str,
+    pub imports: &'a [String],
+}
+
+/// Directed graph over node indices: `deps[i]` = files that node `i` imports,
+/// `users[i]` = files that import node `i`.
+#[derive(Debug, Default)]
+pub struct DepGraph {
+    pub paths: Vec<String>,
+    pub deps: Vec<BTreeSet<usize>>,
+    pub users: Vec<BTreeSet<usize>>,
+}
+
+impl DepGraph {
+    pub fn build(nodes: &[DepNode<'_>]) -> Self {
+        let mut rust_ids: HashMap<String, usize> = HashMap::new();
+        let mut py_ids: HashMap<String, usize> = HashMap::new();
+        let mut by_path: HashMap<&str, usize> = HashMap::new();
+        for (i, n) in nodes.iter().enumerate() {
+            by_path.insert(n.path, i);
+            match n.lang {
+                "rust" => {
+                    rust_ids.insert(crate_rel_module(n.path), i);
+                }
+                "python" => {
+                    py_ids.insert(python_module_id(n.path), i);
+                }
+                _ => {}
+            }
+        }
+
+        let mut deps = vec![BTreeSet::new(); nodes.len()];
+        for (i, n) in nodes.iter().enumerate() {
+            for imp in n.imports {
+                let targets = match n.lang {
+                    "rust" => resolve_rust(n.path, imp, &rust_ids),
+                    "python" => resolve_python(imp, &py_ids),
+                    "js" | "ts" | "jsx" | "tsx" => resolve_web(n.path, imp, &by_path),
+                    _ => Vec::new(),
+                };
+                for t in targets {
+                    if t != i {
+                        deps[i].insert(t);
+                    }
+                }
+            }
+        }
+
+        let mut users = vec![BTreeSet::new(); nodes.len()];
+        for (i, d) in deps.iter().enumerate() {
+            for &j in d {
+                users[j].insert(i);
+            }
+        }
+
+        Self { paths: nodes.iter().map(|n| n.path.to_string()).collect(), deps, users }
+    }
+
+    /// Undirected neighbours (imports + importers) of node `i`.
+    pub fn neighbours(&self, i: usize) -> BTreeSet<usize> {
+        self.deps[i].union(&self.users[i]).copied().collect()
+    }
+
+    /// Node order that keeps each connected component contiguous
+    /// (components sorted by their smallest path) and, inside a component,
+    /// emits dependencies before dependents.
+    pub fn clustered_order(&self) -> Vec<usize> {
+        let n = self.paths.len();
+        let mut uf = UnionFind::new(n);
+        for (i, d) in self.deps.iter().enumerate() {
+            for &j in d {
+                uf.union(i, j);
+            }
+        }
+        let mut comps: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+        for i in 0..n {
+            comps.entry(uf.find(i)).or_default().push(i);
+        }
+        let mut comps: Vec<Vec<usize>> = comps.into_values().collect();
+        for c in &mut comps {
+            c.sort_by(|&a, &b| self.paths[a].cmp(&self.paths[b]));
+        }
+        comps.sort_by(|a, b| self.paths[a[0]].cmp(&self.paths[b[0]]));
+
+        let mut out = Vec::with_capacity(n);
+        for c in comps {
+            out.extend(self.topo_within(&c));
+        }
+        out
+    }
+
+    /// Kahn's algorithm restricted to `members` (already path-sorted), ties by path.
+    /// Nodes left in cycles are appended in path order.
+    fn topo_within(&self, members: &[usize]) -> Vec<usize> {
+        let set: BTreeSet<usize> = members.iter().copied().collect();
+        let mut pending: BTreeMap<usize, usize> = members
+            .iter()
+            .map(|&m| (m, self.deps[m].iter().filter(|d| set.contains(d)).count()))
+            .collect();
+        let mut out = Vec::with_capacity(members.len());
+        loop {
+            let ready = members
+                .iter()
+                .copied()
+                .find(|m| pending.get(m) == Some(&0));
+            let Some(next) = ready else { break };
+            pending.remove(&next);
+            out.push(next);
+            for m in &self.users[next] {
+                if let Some(c) = pending.get_mut(m) {
+                    *c = c.saturating_sub(1);
+                }
+            }
+        }
+        out.extend(members.iter().copied().filter(|m| pending.contains_key(m)));
+        out
+    }
+}
+
+/* ============================== Resolution ============================== */
+
+/// Rust module id relative to the nearest `src/` (so `pkg/src/a/b.rs` -> `a::b`).
+pub fn crate_rel_module(path: &str) -> String {
+    let p = path.replace('\\', "/");
+    match p.rfind("src/") {
+        Some(i) if i == 0 || p[..i].ends_with('/') => rust_module_id(&p[i..]),
+        _ => rust_module_id(&p),
+    }
+}
+
+fn resolve_rust(from_path: &str, imp: &str, ids: &HashMap<String, usize>) -> Vec<usize> {
+    let here = crate_rel_module(from_path);
+    let mut out = Vec::new();
+    for path in expand_use_tree(imp) {
+        let mut segs: Vec<&str> = path.split("::").map(str::trim).filter(|s| !s.is_empty()).collect();
+        // Anchor the path at the crate root.
+        let mut base: Vec<String> = Vec::new();
+        match segs.first().copied() {
+            Some("crate") => {
+                segs.remove(0);
+            }
+            Some("self") => {
+                segs.remove(0);
+                base = module_segments(&here);
+            }
+            Some("super") => {
+                base = module_segments(&here);
+                while segs.first() == Some(&"super") {
+                    segs.remove(0);
+                    base.pop();
+                }
+            }
+            _ => {
+                // Plain `use foo::Bar` in a 2018+ crate: either an external crate
+                // or (in lib.rs/main.rs) a child module. Try it as crate-relative.
+            }
+        }
+        let mut full: Vec<String> = base;
+        full.extend(segs.iter().map(|s| (*s).to_string()));
+        // Longest module prefix wins (`crate::scan::ScanOptions` -> `scan`).
+        for k in (1..=full.len()).rev() {
+            if let Some(&i) = ids.get(&full[..k].join("::")) {
+                out.push(i);
+                break;
+            }
+        }
+    }
+    out
+}
+
+fn module_segments(id: &str) -> Vec<String> {
+    if matches!(id, "crate" | "bin") {
+        return Vec::new();
+    }
+    id.split("::").map(str::to_string).collect()
+}
+
+/// `a::{b, c::{D, E}}` -> [`a::b`, `a::c::D`, `a::c::E`]; `a as b` -> `a`.
+fn expand_use_tree(s: &str) -> Vec<String> {
+    let s = s.trim();
+    let Some(open) = s.find('{') else {
+        let head = s.split(" as ").next().unwrap_or(s).trim();
+        return vec![head.to_string()];
+    };
+    let prefix = s[..open].trim_end_matches("::").trim();
+    let inner = s[open + 1..].trim_end().trim_end_matches('}');
+    let mut out = Vec::new();
+    let mut depth = 0i32;
+    let mut start = 0usize;
+    let bytes = inner.as_bytes();
+    for (i, &b) in bytes.iter().enumerate() {
+        match b {
+            b'{' => depth += 1,
+            b'}' => depth -= 1,
+            b',' if depth == 0 => {
+                push_group_item(&mut out, prefix, &inner[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    push_group_item(&mut out, prefix, &inner[start..]);
+    out
+}
+
+fn push_group_item(out: &mut Vec<String>, prefix: &str, item: &str) {
+    let item = item.trim();
+    if item.is_empty() {
+        return;
+    }
+    if item == "self" {
+        out.push(prefix.to_string());
+        return;
+    }
+    let joined = if prefix.is_empty() { item.to_string() } else { format!("{prefix}::{item}") };
+    out.extend(expand_use_tree(&joined));
+}
+
+fn resolve_python(imp: &str, ids: &HashMap<String, usize>) -> Vec<usize> {
+    let segs: Vec<&str> = imp.split('.').collect();
+    for k in (1..=segs.len()).rev() {
+        let key = segs[..k].join(".");
+        if let Some(&i) = ids.get(&key) {
+            return vec![i];
+        }
+        // Package import: `a.b` -> `a/b/__init__.py`
+        if let Some(&i) = ids.get(&format!("{key}.__init__")) {
+            return vec![i];
+        }
+    }
+    Vec::new()
+}
+
+fn resolve_web(from_path: &str, spec: &str, by_path: &HashMap<&str, usize>) -> Vec<usize> {
+    if !(spec.starts_with("./") || spec.starts_with("../")) {
+        return Vec::new();
+    }
+    let dir = from_path.rsplit_once('/').map(|(d, _)| d).unwrap_or("");
+    let mut parts: Vec<&str> = dir.split('/').filter(|s| !s.is_empty()).collect();
+    for seg in spec.split('/') {
+        match seg {
+            "." | "" => {}
+            ".." => {
+                parts.pop();
+            }
+            s => parts.push(s),
+        }
+    }
+    let base = parts.join("/");
+    let candidates = [
+        base.clone(),
+        format!("{base}.ts"),
+        format!("{base}.tsx"),
+        format!("{base}.js"),
+        format!("{base}.jsx"),
+        format!("{base}/index.ts"),
+        format!("{base}/index.js"),
+    ];
+    candidates
+        .iter()
+        .find_map(|c| by_path.get(c.as_str()).copied())
+        .into_iter()
+        .collect()
+}
+
+/* ============================== Union-find ============================== */
+
+struct UnionFind {
+    parent: Vec<usize>,
+}
+
+impl UnionFind {
+    fn new(n: usize) -> Self {
+        Self { parent: (0..n).collect() }
+    }
+    fn find(&mut self, x: usize) -> usize {
+        let mut r = x;
+        while self.parent[r] != r {
+            r = self.parent[r];
+        }
+        let mut c = x;
+        while self.parent[c] != r {
+            let next = self.parent[c];
+            self.parent[c] = r;
+            c = next;
+        }
+        r
+    }
+    fn union(&mut self, a: usize, b: usize) {
+        let (ra, rb) = (self.find(a), self.find(b));
+        if ra != rb {
+            // Smaller index as root keeps component keys deterministic.
+            let (lo, hi) = if ra < rb { (ra, rb) } else { (rb, ra) };
+            self.parent[hi] = lo;
+        }
+    }
+}
+
+/* ================================== Tests ================================== */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node<'a>(path: &'a str, lang: &'a str, imports: &'a [String]) -> DepNode<'a> {
+        DepNode { path, lang, imports }
+    }
+
+    #[test]
+    fn use_tree_expands_groups() {
+        let v = expand_use_tree("crate::{chunker, scan::{read_index, ScanOptions as O}, self}");
+        assert_eq!(v, vec!["crate::chunker", "crate::scan::read_index", "crate::scan::ScanOptions", "crate"]);
+    }
+
+    #[test]
+    fn crate_relative_module_ids() {
+        assert_eq!(crate_rel_module("indexer/src/scan.rs"), "scan");
+        assert_eq!(crate_rel_module("src/a/mod.rs"), "a");
+        assert_eq!(crate_rel_module("src/lib.rs"), "crate");
+    }
+
+    #[test]
+    fn deps_come_before_users_and_clusters_stay_together() {
+        let lib_imp = vec!["crate::util::slug".to_string()];
+        let scan_imp = vec!["crate::{util, helpers::infer_role}".to_string()];
+        let none: Vec<String> = Vec::new();
+        let nodes = vec![
+            node("src/a_scan.rs", "rust", &scan_imp),
+            node("src/helpers.rs", "rust", &none),
+            node("src/lonely.py", "python", &none),
+            node("src/main.rs", "rust", &lib_imp),
+            node("src/util.rs", "rust", &none),
+        ];
+        let g = DepGraph::build(&nodes);
+        assert!(g.deps[0].contains(&1) && g.deps[0].contains(&4));
+        let order: Vec<&str> = g.clustered_order().into_iter().map(|i| g.paths[i].as_str()).collect();
+        let pos = |p: &str| order.iter().position(|x| *x == p).unwrap();
+        assert!(pos("src/util.rs") < pos("src/a_scan.rs"));
+        assert!(pos("src/helpers.rs") < pos("src/a_scan.rs"));
+        assert!(pos("src/util.rs") < pos("src/main.rs"));
+        // the unrelated python file is its own component, not wedged inside the cluster
+        assert!(pos("src/lonely.py") == 0 || pos("src/lonely.py") == order.len() - 1);
+    }
+
+    #[test]
+    fn web_relative_imports_resolve() {
+        let imp = vec!["./util".to_string(), "react".to_string()];
+        let none: Vec<String> = Vec::new();
+        let nodes = vec![node("web/app.ts", "ts", &imp), node("web/util.ts", "ts", &none)];
+        let g = DepGraph::build(&nodes);
+        assert_eq!(g.deps[0].iter().copied().collect::<Vec<_>>(), vec![1]);
+    }
+}
@@ -1,14 +1,37 @@
 // indexer/src/diff.rs
 use serde_json::{json, Value};
-use std::collections::{BTreeMap, HashMap, BTreeSet};
+use std::collections::{BTreeMap, HashMap, HashSet, BTreeSet};
 
 use crate::file_intent_entry::{FileIntentEntry};
+use crate::symbols::{diff_symbols, SymbolChange};
+
+/// Tuning for [`diff_indexes_with_opts`].
+#[derive(Clone, Debug)]
+pub struct DiffOptions {
+    /// Minimum line-set Jaccard similarity (0..=1) for pairing a removed or
+    /// existing file with an added one as `renamed_with_changes` / `copied`.
+    pub similarity_threshold: f64,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self { similarity_threshold: 0.5 }
+    }
+}
+
+/// Compute a structured diff between two index snapshots (default options).
+pub fn diff_indexes(old: &[FileIntentEntry], new: &[FileIntentEntry]) -> Value {
+    diff_indexes_with_opts(old, new, &DiffOptions::default())
+}
 
 /// Compute a structured diff between two index snapshots.
 /// - Adds / Removes / Modifies (by sha1 change or signal deltas)
 /// - Renames (one-to-one sha1 match where old path disappeared and new path appeared)
+/// - Renames with changes (remaining removed/added pairs whose snippets are similar)
+/// - Copies (added files similar to a file that still exists at its old path)
+/// - Symbol deltas for modified and renamed Rust files (when both snapshots carry symbols)
 /// - Stable, path-sorted output for deterministic diffs
-pub fn diff_indexes(old: &[FileIntentEntry], new: &[FileIntentEntry]) -> Value {
+pub fn diff_indexes_with_opts(old: &[FileIntentEntry], new: &[FileIntentEntry], opts: &DiffOptions) -> Value {
     // Index by path
     let old_by_path: HashMap<&str, &FileIntentEntry> =
         old.iter().map(|e| (e.path.as_str(), e)).collect();
@@ -59,12 +82,42 @@ pub fn diff_indexes(old: &[FileIntentEntry], new: &[FileIntentEntry]) -> Value {
         renamed_to.insert(*to);
     }
 
+    // Similarity pass over what is left: removed x added -> renamed_with_changes,
+    // then existing x still-unpaired added -> copied.
+    let mut gone: Vec<&FileIntentEntry> = old
+        .iter()
+        .filter(|e| !new_by_path.contains_key(e.path.as_str()) && !renamed_from.contains(e.path.as_str()))
+        .collect();
+    let mut fresh: Vec<&FileIntentEntry> = new
+        .iter()
+        .filter(|e| !old_by_path.contains_key(e.path.as_str()) && !renamed_to.contains(e.path.as_str()))
+        .collect();
+    gone.sort_by(|a, b| a.path.cmp(&b.path));
+    fresh.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let similar_pairs = best_pairs(&gone, &fresh, opts.similarity_threshold, true);
+    for (from, to, _) in &similar_pairs {
+        renamed_from.insert(from.path.as_str());
+        renamed_to.insert(to.path.as_str());
+    }
+    fresh.retain(|e| !renamed_to.contains(e.path.as_str()));
+    let kept: Vec<&FileIntentEntry> = {
+        let mut v: Vec<&FileIntentEntry> = old.iter().filter(|e| new_by_path.contains_key(e.path.as_str())).collect();
+        v.sort_by(|a, b| a.path.cmp(&b.path));
+        v
+    };
+    let copy_pairs = best_pairs(&kept, &fresh, opts.similarity_threshold, false);
+    let copied_to: BTreeSet<&str> = copy_pairs.iter().map(|(_, to, _)| to.path.as_str()).collect();
+
     // Stable output containers
     let mut added: BTreeMap<String, Value> = BTreeMap::new();
     let mut removed: BTreeMap<String, Value> = BTreeMap::new();
     let mut modified: BTreeMap<String, Value> = BTreeMap::new();
     let mut renamed: Vec<Value> = Vec::new();
+    let mut renamed_with_changes: Vec<Value> = Vec::new();
+    let mut copied: Vec<Value> = Vec::new();
     let mut unchanged_count: usize = 0;
+    let mut symbol_changes: usize = 0;
 
     // Renamed list (already stable-sorted)
     for (from, to, ne) in &renamed_pairs {
@@ -76,10 +129,36 @@ pub fn diff_indexes(old: &[FileIntentEntry], new: &[FileIntentEntry]) -> Value {
         }));
     }
 
-    // Detect adds / modifies / unchanged (ignoring rename targets for "added")
+    for (from, to, score) in &similar_pairs {
+        let mut v = json!({
+            "from": from.path,
+            "to": to.path,
+            "similarity": round3(*score),
+            "sha1_before": from.sha1,
+            "sha1_after": to.sha1,
+            "size": to.size
+        });
+        if let (Some(before), Some(after)) = (&from.symbols, &to.symbols) {
+            let changes: Vec<SymbolChange> = diff_symbols(before, after);
+            symbol_changes += changes.len();
+            v["symbols"] = json!(changes);
+        }
+        renamed_with_changes.push(v);
+    }
+    for (from, to, score) in &copy_pairs {
+        copied.push(json!({
+            "from": from.path,
+            "to": to.path,
+            "similarity": round3(*score),
+            "sha1": to.sha1,
+            "size": to.size
+        }));
+    }
+
+    // Detect adds / modifies / unchanged (ignoring rename/copy targets for "added")
     for (path, new_e) in &new_by_path {
-        if renamed_to.contains(path) {
-            continue; // already accounted for in renamed[]
+        if renamed_to.contains(path) || copied_to.contains(path) {
+            continue; // already accounted for in renamed[] / renamed_with_changes[] / copied[]
         }
         match old_by_path.get(path) {
             None => {
@@ -88,7 +167,13 @@ pub fn diff_indexes(old: &[FileIntentEntry], new: &[FileIntentEntry]) -> Value {
             }
             Some(old_e) => {
                 if old_e.sha1 != new_e.sha1 {
-                    modified.insert((*path).to_string(), json_delta(path, old_e, new_e));
+                    let mut delta = json_delta(path, old_e, new_e);
+                    if let (Some(before), Some(after)) = (&old_e.symbols, &new_e.symbols) {
+                        let changes: Vec<SymbolChange> = diff_symbols(before, after);
+                        symbol_changes += changes.len();
+                        delta["symbols"] = json!(changes);
+                    }
+                    modified.insert((*path).to_string(), delta);
                 } else {
                     // Same sha1; still consider signal-only deltas (role/lang/module/tags/loc)
                     if signals_changed(old_e, new_e) {
@@ -123,7 +208,10 @@ pub fn diff_indexes(old: &[FileIntentEntry], new: &[FileIntentEntry]) -> Value {
         "removed": removed.len(),
         "modified": modified.len(),
         "renamed": renamed.len(),
-        "unchanged": unchanged_count
+        "renamed_with_changes": renamed_with_changes.len(),
+        "copied": copied.len(),
+        "unchanged": unchanged_count,
+        "symbol_changes": symbol_changes
     });
 
     json!({
@@ -132,16 +220,83 @@ pub fn diff_indexes(old: &[FileIntentEntry], new: &[FileIntentEntry]) -> Value {
         "added": added,
         "removed": removed,
         "modified": modified,
-        "renamed": renamed
+        "renamed": renamed,
+        "renamed_with_changes": renamed_with_changes,
+        "copied": copied
     })
 }
 
+/* ============================ Similarity pairing ============================ */
+
+/// Above this many candidate pairs the similarity pass is skipped (sha1 renames
+/// still apply) to keep huge reshuffles fast.
+const MAX_SIMILARITY_PAIRS: usize = 4_000_000;
+
+/// Distinct trimmed, non-blank snippet lines.
+fn line_set(e: &FileIntentEntry) -> HashSet<&str> {
+    e.snippet.lines().map(str::trim).filter(|l| !l.is_empty()).collect()
+}
+
+/// Jaccard index |a ∩ b| / |a ∪ b|; 0 when both are empty.
+fn jaccard(a: &HashSet<&str>, b: &HashSet<&str>) -> f64 {
+    let inter = a.intersection(b).count();
+    let union = a.len() + b.len() - inter;
+    if union == 0 { 0.0 } else { inter as f64 / union as f64 }
+}
+
+fn round3(x: f64) -> f64 {
+    (x * 1000.0).round() / 1000.0
+}
+
+/// Greedy best-first matching of `from` x `to` by snippet similarity.
+/// With `exclusive`, each `from` entry is used at most once (renames);
+/// otherwise one source may explain several copies. Each `to` is used once.
+fn best_pairs<'a>(
+    from: &[&'a FileIntentEntry],
+    to: &[&'a FileIntentEntry],
+    threshold: f64,
+    exclusive: bool,
+) -> Vec<(&'a FileIntentEntry, &'a FileIntentEntry, f64)> {
+    if from.is_empty() || to.is_empty() || from.len().saturating_mul(to.len()) > MAX_SIMILARITY_PAIRS {
+        return Vec::new();
+    }
+    let from_sets: Vec<HashSet<&str>> = from.iter().map(|e| line_set(e)).collect();
+    let to_sets: Vec<HashSet<&str>> = to.iter().map(|e| line_set(e)).collect();
+
+    let mut scored: Vec<(f64, usize, usize)> = Vec::new();
+    for (i, fs) in from_sets.iter().enumerate() {
+        for (j, ts) in to_sets.iter().enumerate() {
+            let score = jaccard(fs, ts);
+            if score >= threshold && score > 0.0 {
+                scored.push((score, i, j));
+            }
+        }
+    }
+    // Highest score first; ties resolved by path order (indices are path-sorted).
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)).then(a.2.cmp(&b.2)));
+
+    let mut used_from = vec![false; from.len()];
+    let mut used_to = vec![false; to.len()];
+    let mut out = Vec::new();
+    for (score, i, j) in scored {
+        if used_to[j] || (exclusive && used_from[i]) {
+            continue;
+        }
+        used_to[j] = true;
+        used_from[i] = true;
+        out.push((from[i], to[j], score));
+    }
+    out.sort_by(|a, b| (a.0.path.as_str(), a.1.path.as_str()).cmp(&(b.0.path.as_str(), b.1.path.as_str())));
+    out
+}
+
 /// Minimal JSON for a file to keep diff payloads lean.
 fn json_min(e: &FileIntentEntry) -> Value {
     json!({
         "path": e.path,
         "sha1": e.sha1,
         "size": e.size,
+        "lines_total": e.lines_total,
         "lang": e.lang,
         "role": e.role.to_string(),
     })
@@ -211,3 +366,176 @@ fn tags_removed(before: &[String], after: &[String]) -> Value {
     let rem = b.difference(&a).cloned().collect::<Vec<&str>>();
     json!(rem)
 }
+
+/* ============================ API changes report ============================ */
+
+/// Render the symbol deltas of a `diff_indexes` result as a readable Markdown
+/// "API changes" report. Public items are listed before private ones.
+pub fn api_changes_markdown(diff: &Value) -> String {
+    use std::fmt::Write as _;
+
+    let mut out = String::new();
+    let _ = writeln!(out, "# API changes\n");
+    let s = &diff["summary"];
+    let _ = writeln!(
+        out,
+        "> files: +{} −{} ~{} (renamed {}, renamed with changes {}, copied {})  •  symbol changes: {}\n",
+        s["added"], s["removed"], s["modified"], s["renamed"], s["renamed_with_changes"], s["copied"], s["symbol_changes"]
+    );
+
+    let mut any = false;
+    let modified = diff["modified"].as_array().into_iter().flatten();
+    let moved = diff["renamed_with_changes"].as_array().into_iter().flatten();
+    for file in modified.chain(moved) {
+        let Some(changes) = file["symbols"].as_array().filter(|c| !c.is_empty()) else { continue };
+        any = true;
+        match (file["from"].as_str(), file["to"].as_str()) {
+            (Some(from), Some(to)) => {
+                let _ = writeln!(out, "## `{from}` → `{to}` (similarity {})\n", file["similarity"]);
+            }
+            _ => {
+                let _ = writeln!(out, "## `{}`\n", file["path"].as_str().unwrap_or_default());
+            }
+        }
+        let mut sorted: Vec<&Value> = changes.iter().collect();
+        sorted.sort_by_key(|c| !c["public"].as_bool().unwrap_or(false));
+        for c in sorted {
+            let _ = writeln!(out, "{}", api_change_line(c));
+        }
+        let _ = writeln!(out);
+    }
+
+    let rust_files = |key: &str| -> Vec<String> {
+        diff[key]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|f| f["lang"] == "rust")
+            .filter_map(|f| f["path"].as_str().map(|p| format!("- `{p}`")))
+            .collect()
+    };
+    for (key, title) in [("added", "New Rust files"), ("removed", "Removed Rust files")] {
+        let lines = rust_files(key);
+        if !lines.is_empty() {
+            any = true;
+            let _ = writeln!(out, "## {title}\n\n{}\n", lines.join("\n"));
+        }
+    }
+
+    if !any {
+        let _ = writeln!(out, "_No symbol-level changes._");
+    }
+    out
+}
+
+fn api_change_line(c: &Value) -> String {
+    let vis = if c["public"].as_bool().unwrap_or(false) { "pub " } else { "" };
+    let kind = c["kind"].as_str().unwrap_or_default();
+    let name = c["name"].as_str().unwrap_or_default();
+    let list = |key: &str| -> String {
+        c[key]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .map(|v| format!("`{v}`"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let code = |key: &str| c[key].as_str().map(|v| format!("`{v}`")).unwrap_or_default();
+    match c["change"].as_str().unwrap_or_default() {
+        "added" => format!("- **added** {vis}{kind} `{name}` {}", code("after")).trim_end().to_string(),
+        "removed" => format!("- **removed** {vis}{kind} `{name}` {}", code("before")).trim_end().to_string(),
+        "signature" => format!("- **changed** {vis}{kind} `{name}`: {} → {}", code("before"), code("after")),
+        "fields" | "variants" => {
+            let mut parts = Vec::new();
+            for (key, label) in [("added", "+"), ("removed", "−"), ("retyped", "~")] {
+                let l = list(key);
+                if !l.is_empty() {
+                    parts.push(format!("{label} {l}"));
+                }
+            }
+            format!("- **{}** {vis}{kind} `{name}`: {}", c["change"].as_str().unwrap_or_default(), parts.join("; "))
+        }
+        other => format!("- {other} {vis}{kind} `{name}`"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::symbols::rust_symbols;
+
+    fn entry(path: &str, sha1: &str, src: &str) -> FileIntentEntry {
+        FileIntentEntry {
+            path: path.into(),
+            lang: "rust".into(),
+            sha1: sha1.into(),
+            symbols: rust_symbols(src),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn modified_rust_file_carries_symbol_deltas() {
+        let old = vec![entry("src/a.rs", "1", "pub fn go(x: u8) {}\npub struct S { pub a: u8 }\n")];
+        let new = vec![entry("src/a.rs", "2", "pub fn go(x: u16) {}\npub struct S { pub a: u8, pub b: u8 }\n")];
+        let d = diff_indexes(&old, &new);
+        assert_eq!(d["summary"]["symbol_changes"], 2);
+        let syms = d["modified"][0]["symbols"].as_array().unwrap();
+        assert_eq!(syms[0]["name"], "S");
+        assert_eq!(syms[0]["added"][0], "pub b: u8");
+        assert_eq!(syms[1]["change"], "signature");
+
+        let md = api_changes_markdown(&d);
+        assert!(md.contains("## `src/a.rs`"), "{md}");
+        assert!(md.contains("- **fields** pub struct `S`: + `pub b: u8`"), "{md}");
+        assert!(md.contains("- **changed** pub fn `go`: `fn go (x: u8)` → `fn go (x: u16)`"), "{md}");
+    }
+
+    #[test]
+    fn similar_files_pair_as_renames_and_copies() {
+        let body: String = (0..10).map(|i| format!("fn f{i}() {{}}\n")).collect();
+        let mut old_a = entry("src/old_name.rs", "1", "");
+        old_a.snippet = body.clone();
+        let mut old_b = entry("src/keep.rs", "2", "");
+        old_b.snippet = "struct Keep;\nfn a() {}\nfn b() {}\nfn c() {}\n".into();
+        let mut old_c = entry("src/dropped.rs", "3", "");
+        old_c.snippet = "unrelated\n".into();
+
+        let mut new_a = entry("src/new_name.rs", "4", "");
+        new_a.snippet = body.replace("fn f9() {}", "fn f9(x: u8) {}");
+        let mut new_copy = entry("src/keep_copy.rs", "5", "");
+        new_copy.snippet = "struct Keep;\nfn a() {}\nfn b() {}\nfn d() {}\n".into();
+        let mut new_add = entry("src/brand_new.rs", "6", "");
+        new_add.snippet = "something else\n".into();
+
+        let old = vec![old_a, old_b.clone(), old_c];
+        let new = vec![new_a, old_b, new_copy, new_add];
+        let d = diff_indexes(&old, &new);
+        assert_eq!(d["summary"]["renamed_with_changes"], 1);
+        let r = &d["renamed_with_changes"][0];
+        assert_eq!((r["from"].as_str(), r["to"].as_str()), (Some("src/old_name.rs"), Some("src/new_name.rs")));
+        assert_eq!(r["similarity"], 0.818);
+        assert_eq!(d["copied"][0]["from"], "src/keep.rs");
+        assert_eq!(d["copied"][0]["to"], "src/keep_copy.rs");
+        assert_eq!(d["copied"][0]["similarity"], 0.6);
+        assert_eq!(d["added"].as_array().unwrap().len(), 1);
+        assert_eq!(d["removed"][0]["path"], "src/dropped.rs");
+
+        let strict = diff_indexes_with_opts(&old, &new, &DiffOptions { similarity_threshold: 0.9 });
+        assert_eq!(strict["summary"]["renamed_with_changes"], 0);
+        assert_eq!(strict["summary"]["copied"], 0);
+        assert_eq!(strict["added"].as_array().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn older_snapshots_without_symbols_skip_deltas() {
+        let mut old = entry("src/a.rs", "1", "pub fn go() {}");
+        old.symbols = None;
+        let new = vec![entry("src/a.rs", "2", "pub fn go(x: u8) {}")];
+        let d = diff_indexes(&[old], &new);
+        assert!(d["modified"][0].get("symbols").is_none());
+        assert!(api_changes_markdown(&d).contains("_No symbol-level changes._"));
+    }
+}
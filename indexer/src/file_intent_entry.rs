@@ -17,6 +17,8 @@ use serde::{
 };
 use std::fmt;
 
+use crate::symbols::Symbol;
+
 /// Coarse role for retrieval/ranking. Keep small & stable.
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Role {
@@ -92,6 +94,11 @@ pub struct FileIntentEntry {
     pub lines_nonblank: usize,
     #[serde(deserialize_with = "de_string_from_any")] pub rel_dir: String,
     pub noise: bool,
+
+    /// Rust symbol table (`None` = not collected: non-Rust, unparsable, or an
+    /// older snapshot). Used for symbol-level diffs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub symbols: Option<Vec<Symbol>>,
 }
 
 impl Default for FileIntentEntry {
@@ -115,6 +122,7 @@ impl Default for FileIntentEntry {
             lines_nonblank: 0,
             rel_dir: String::new(),
             noise: false,
+            symbols: None,
         }
     }
 }
@@ -79,11 +79,9 @@ pub fn infer_role(path: &str, lang: &str, snippet: &str) -> Role {
 /// Best-effort module id (path → module), language-aware. Returns stable identifiers.
 pub fn infer_module_id(path: &str, lang: &str) -> String {
     let p = path.replace('\\', "/").trim_matches('/').to_string();
-    match lang.to_ascii_lowercase().as_str() {
-        "rust"   => rust_module_id(&p),
-        "python" => python_module_id(&p),
-        "ts" | "typescript" | "js" | "javascript" => web_module_id(&p),
-        _ => generic_module_id(&p),
+    match crate::lang::analyzer(lang) {
+        Some(a) => a.module_id(&p),
+        None => generic_module_id(&p),
     }
 }
 
@@ -135,23 +133,34 @@ pub fn generic_module_id(p: &str) -> String {
 /// Extract imports/exports cheaply from the snippet (no regex/AST).
 /// Returns (imports, exports). Deduplicated, order-preserving (first occurrence).
 pub fn skim_symbols(snippet: &str, lang: &str) -> (Vec<String>, Vec<String>) {
-    match lang.to_ascii_lowercase().as_str() {
-        "rust" => skim_rust(snippet),
-        "python" => skim_python(snippet),
-        "typescript" | "ts" | "javascript" | "js" => skim_js_ts(snippet),
-        _ => (Vec::new(), Vec::new()),
+    match crate::lang::analyzer(lang) {
+        Some(a) => a.skim_symbols(snippet),
+        None => (Vec::new(), Vec::new()),
     }
 }
 
 pub fn skim_rust(s: &str) -> (Vec<String>, Vec<String>) {
     let mut imports = Vec::new();
     let mut exports = Vec::new();
+    // Multi-line `use a::{\n b,\n c,\n};` is accumulated until the `;`.
+    let mut pending_use: Option<String> = None;
     for raw in s.lines() {
         let l = trim_inline_comment(raw.trim());
+        if let Some(acc) = pending_use.as_mut() {
+            acc.push_str(l);
+            if l.ends_with(';') {
+                let body = acc.trim_end_matches(';').replace(",}", "}").trim().to_string();
+                if !body.is_empty() { imports.push(body); }
+                pending_use = None;
+            }
+            continue;
+        }
         if l.starts_with("use ") {
             // use foo::bar::{Baz, Qux as Q};
             let body = l.trim_start_matches("use ").trim_end_matches(';').trim();
-            if !body.is_empty() { imports.push(body.to_string()); }
+            if !l.ends_with(';') {
+                pending_use = Some(body.to_string());
+            } else if !body.is_empty() { imports.push(body.to_string()); }
         } else if l.starts_with("pub ") {
             // pub(crate) fn ...  / pub struct ...  / pub enum ...  / pub trait ...  / pub mod ...
             let li = l.trim_start_matches("pub ").trim_start();
@@ -311,6 +320,12 @@ mod tests {
         assert_eq!(python_module_id("a/b/c.py"), "a.b.c");
     }
 
+    #[test]
+    fn skim_rust_joins_multiline_use() {
+        let (im, _) = skim_rust("use crate::{\n    chunker,\n    scan::ScanOptions,\n};\nuse std::fs;\n");
+        assert_eq!(im, vec!["crate::{chunker,scan::ScanOptions}".to_string(), "std::fs".to_string()]);
+    }
+
     #[test]
     fn skim_rust_symbols() {
         let (im, ex) = skim_rust("use crate::foo::Bar;\npub struct X {}\npub fn go() {}\n");
@@ -1,173 +1,1376 @@
 // indexer/src/index_v3.rs
-use std::{fs, path::{Path}, env};
-use anyhow::{Context, Result};
+use std::{collections::{BTreeMap, HashMap, HashSet}, fs, path::{Path, PathBuf}, env};
+use anyhow::{anyhow, bail, Context, Result};
 use base64::{engine::general_purpose::STANDARD as B64, Engine as _};
-use serde::Serialize;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use proc_macro2::Span;
+use crate::merkle;
+use crate::redact::Redactor;
 use crate::scan::read_index;
 use syn::{
-  ImplItem, ImplItemFn, Item, ItemEnum, ItemFn, ItemImpl, ItemStruct
+  spanned::Spanned, AttrStyle, Attribute, ImplItem, ImplItemFn, Item, ItemEnum, ItemFn, ItemImpl, ItemMacro,
+  ItemStruct, ItemTrait, TraitItem, Visibility,
 };
+use crate::types_view::norm_tokens;
 
-const CHUNK: usize = 16 * 1024;
+pub(crate) const CHUNK: usize = 16 * 1024;
 
-#[derive(Serialize)]
+/// How file bytes are cut into hashed chunks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Chunking {
+  /// Fixed-size chunks (default, 16 KB): any insertion shifts every later chunk.
+  Fixed { size: usize },
+  /// `FastCDC` over a gear rolling hash: cut points follow content, so chunks
+  /// away from an edit keep their hashes.
+  Cdc { min: usize, avg: usize, max: usize },
+}
+
+impl Default for Chunking {
+  fn default() -> Self { Chunking::Fixed { size: CHUNK } }
+}
+
+impl Chunking {
+  /// `FastCDC` with a 16 KB average (4 KB min, 64 KB max).
+  pub const CDC_DEFAULT: Chunking = Chunking::Cdc { min: 4 * 1024, avg: CHUNK, max: 64 * 1024 };
+
+  /// `(algo, chunk_size_bytes, cdc)` as recorded in the pack.
+  fn params(&self) -> (&'static str, usize, Option<CdcParams>) {
+    match *self {
+      Chunking::Fixed { size } => ("fixed", size, None),
+      Chunking::Cdc { min, avg, max } => (
+        "fastcdc",
+        avg,
+        Some(CdcParams { min_size: min, avg_size: avg, max_size: max, gear: "splitmix64".into(), gear_seed: GEAR_SEED }),
+      ),
+    }
+  }
+
+  /// `(offset, length)` of every chunk; empty input has no chunks.
+  pub fn split(&self, bytes: &[u8]) -> Vec<(usize, usize)> {
+    let mut out = Vec::new();
+    let mut off = 0usize;
+    while off < bytes.len() {
+      let len = match *self {
+        Chunking::Fixed { size } => size.max(1).min(bytes.len() - off),
+        Chunking::Cdc { min, avg, max } => fastcdc_cut(&bytes[off..], min, avg, max),
+      };
+      out.push((off, len));
+      off += len;
+    }
+    out
+  }
+}
+
+/// Options for [`build_index_v3_with`].
+#[derive(Clone, Debug, Default)]
+pub struct V3Options {
+  pub chunking: Chunking,
+  /// Write a manifest plus one shard per package / top-level dir.
+  pub shard: bool,
+  /// zstd-compress shards (needs the `zstd` feature; implies `shard`).
+  pub zstd: bool,
+  /// Single-line JSON instead of pretty-printed.
+  pub compact: bool,
+  /// Re-parse every file instead of reusing unchanged entries of the previous pack.
+  pub full: bool,
+}
+
+const GEAR_SEED: u64 = 0;
+
+/// 256 pseudo-random u64s from splitmix64, so the table is reproducible from
+/// the seed recorded in the pack.
+fn gear_table() -> [u64; 256] {
+  let mut t = [0u64; 256];
+  let mut x = GEAR_SEED;
+  for v in &mut t {
+    x = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = x;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    *v = z ^ (z >> 31);
+  }
+  t
+}
+
+/// Length of the first `FastCDC` chunk of `data` (normalized chunking, level 1).
+/// The gear hash shifts left, so its top bits depend on the last 64 bytes; the
+/// masks test top bits: one extra bit before `avg` (cuts less likely), one
+/// fewer after it (more likely), which keeps sizes close to `avg`.
+fn fastcdc_cut(data: &[u8], min: usize, avg: usize, max: usize) -> usize {
+  let n = data.len();
+  if n <= min {
+    return n;
+  }
+  let bits = avg.max(2).ilog2();
+  let top = |k: u32| if k == 0 { 0 } else { u64::MAX << (64 - k.min(64)) };
+  let (mask_s, mask_l) = (top(bits + 1), top(bits.saturating_sub(1)));
+  let gear = gear_table();
+  let (normal, end) = (avg.min(n), max.min(n));
+  let mut h = 0u64;
+  for (i, b) in data.iter().enumerate().take(end).skip(min) {
+    h = (h << 1).wrapping_add(gear[*b as usize]);
+    let mask = if i < normal { mask_s } else { mask_l };
+    if h & mask == 0 {
+      return i + 1;
+    }
+  }
+  end
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct IndexPack {
-  format: &'static str,
-  version: &'static str,
-  hash_algo: &'static str,
-  pack_id: String,
-  created_utc: String,
-  lang: LangMeta,
-  rules: Rules,
-  files: Vec<FileEntry>,
-}
-
-#[derive(Serialize)]
-struct LangMeta { primary: &'static str, dialect: &'static str }
-#[derive(Serialize)]
-struct Rules {
-  mode: &'static str,
-  patch_contract: PatchContract,
-}
-#[derive(Serialize)]
-struct PatchContract {
-  diff_format: &'static str,
-  limit_scope_to_verified_anchors: bool,
-}
-
-#[derive(Serialize)]
-struct FileEntry {
-  path: String,
-  language: String,
-  size_bytes: usize,
-  line_count: usize,
-  encoding: &'static str,
-  eol: &'static str,
-  file_sha256: String,
-  chunks: ChunkSet,
-  anchors: Vec<Anchor>,
-}
-#[derive(Serialize)]
-struct ChunkSet {
-  chunk_size_bytes: usize,
-  merkle_root: String,
-  list: Vec<Chunk>,
-}
-#[derive(Serialize)]
-struct Chunk { index: usize, offset: usize, length: usize, sha256: String }
-
-#[derive(Serialize)]
-struct Anchor {
-  kind: &'static str,
-  name: String,
-  visibility: String,
-  signature: Option<String>,
-  range: Range,
-  slice_sha256: String,
-  verbatim_b64: String,
-  schema: Option<Schema>,
-}
-#[derive(Serialize)]
-struct Range { start_line: usize, end_line: usize }
-#[derive(Serialize)]
-struct Schema {
-  fields: Option<Vec<Field>>,
-  variants: Option<Vec<String>>,
-  params: Option<Vec<(String, String)>>,
-  returns: Option<String>,
-}
-#[derive(Serialize)]
-struct Field { name: String, ty: String, public: bool }
-
-pub fn build_index_v3(index_path: &Path, project_root: &Path, out_path: &Path) -> Result<()> {
+  pub format: String,
+  pub version: String,
+  pub hash_algo: String,
+  /// sha256 over the generator, its options and every `path`/`file_sha256`
+  /// in path order: identical trees indexed the same way share it.
+  pub pack_id: String,
+  pub created_utc: String,
+  /// Absent in packs written before content addressing (their `pack_id` is a
+  /// placeholder).
+  #[serde(default)]
+  pub generator: Generator,
+  pub lang: LangMeta,
+  pub rules: Rules,
+  pub files: Vec<FileEntry>,
+  /// Indexed paths with no readable file (virtual paths, deleted files).
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub skipped: Vec<Skipped>,
+  /// Sharded layout: `files` is empty here and split across these.
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub shards: Vec<ShardRef>,
+}
+
+/// One shard of a sharded pack, as listed in the manifest.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ShardRef {
+  /// Package root (`crates/core/`) or top-level dir (`src/`, `./` for root files).
+  pub key: String,
+  /// Shard file, relative to the manifest (`index_v3.shards/src-1a2b3c4d.json.zst`).
+  pub file: String,
+  /// sha256 of the shard file as written (compressed, if it is).
+  pub sha256: String,
+  /// Paths of the files it holds, so one file's shard is found from the manifest.
+  pub files: Vec<String>,
+}
+
+/// Body of a shard file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Shard {
+  pub pack_id: String,
+  pub key: String,
+  pub files: Vec<FileEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Skipped { pub path: String, pub error: String }
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct Generator {
+  pub name: String,
+  pub version: String,
+  pub options: GeneratorOptions,
+}
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct GeneratorOptions {
+  pub chunking: String,
+  pub chunk_size_bytes: usize,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub cdc: Option<CdcParams>,
+  /// `Redactor::fingerprint` of the rules applied to `verbatim_b64`; empty in older packs.
+  #[serde(default)]
+  pub redaction: String,
+}
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LangMeta {
+  /// Language with the most bytes, docs and config (`md`, `toml`, ..) aside
+  /// unless there is nothing else.
+  pub primary: String,
+  /// `edition20xx` from the root Cargo.toml when `primary` is rust.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub dialect: Option<String>,
+  /// Bytes per indexed language.
+  #[serde(default)]
+  pub bytes: BTreeMap<String, usize>,
+}
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Rules {
+  pub mode: String,
+  pub patch_contract: PatchContract,
+}
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PatchContract {
+  pub diff_format: String,
+  pub limit_scope_to_verified_anchors: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileEntry {
+  pub path: String,
+  pub language: String,
+  pub size_bytes: usize,
+  pub line_count: usize,
+  pub encoding: String,
+  pub eol: String,
+  pub file_sha256: String,
+  pub chunks: ChunkSet,
+  pub anchors: Vec<Anchor>,
+  /// Why `anchors` is empty although the language has them (syntax error, bad UTF-8).
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub parse_error: Option<String>,
+}
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChunkSet {
+  /// `fixed` (cut every `chunk_size_bytes`) or `fastcdc` (content-defined, see `cdc`).
+  #[serde(default = "fixed_algo")]
+  pub algo: String,
+  /// Fixed chunk size, or the `FastCDC` target average.
+  pub chunk_size_bytes: usize,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub cdc: Option<CdcParams>,
+  /// Tree construction over the chunk hashes (see `merkle.rs`); empty in
+  /// packs that predate [`merkle::DUP_ODD`] (their root is `merkle::legacy_root`).
+  #[serde(default)]
+  pub merkle: String,
+  pub merkle_root: String,
+  pub list: Vec<Chunk>,
+}
+/// Everything needed to reproduce `FastCDC` cut points.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CdcParams {
+  pub min_size: usize,
+  pub avg_size: usize,
+  pub max_size: usize,
+  /// Gear table derivation: `splitmix64(seed)` x 256.
+  pub gear: String,
+  pub gear_seed: u64,
+}
+fn fixed_algo() -> String {
+  "fixed".into()
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Chunk { pub index: usize, pub offset: usize, pub length: usize, pub sha256: String }
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Anchor {
+  pub kind: String,
+  pub name: String,
+  /// Qualified path (`crate::scan::ScanOptions::default`,
+  /// `<crate::scan::ScanOptions as Default>::default`); empty in older packs.
+  #[serde(default)]
+  pub path: String,
+  /// Stable id from crate root + kind + `path` (never line numbers); empty in older packs.
+  #[serde(default)]
+  pub anchor_id: String,
+  pub visibility: String,
+  pub signature: Option<String>,
+  pub range: Range,
+  /// Byte offsets `[start, end)` of the slice in the source; `[0, 0]` in older packs.
+  #[serde(default)]
+  pub span: (usize, usize),
+  /// sha256 of the original source bytes (verification stays against the file).
+  pub slice_sha256: String,
+  /// base64 of the slice with secrets replaced by `«REDACTED:..»` placeholders.
+  pub verbatim_b64: String,
+  /// Placeholders substituted into `verbatim_b64`; omitted when nothing was redacted.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub redacted: Option<Vec<String>>,
+  pub schema: Option<Schema>,
+}
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Range { pub start_line: usize, pub end_line: usize }
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct Schema {
+  pub fields: Option<Vec<Field>>,
+  pub variants: Option<Vec<String>>,
+  pub params: Option<Vec<(String, String)>>,
+  pub returns: Option<String>,
+  // Kind-specific parts below are omitted when absent.
+  /// impl: the implemented trait (`Default`), if any.
+  #[serde(rename = "trait", skip_serializing_if = "Option::is_none")]
+  pub trait_: Option<String>,
+  /// impl: the implementing type.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub self_ty: Option<String>,
+  /// trait: supertrait bounds.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub supertraits: Option<Vec<String>>,
+  /// trait / impl: normalized method signatures.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub methods: Option<Vec<String>>,
+  /// trait / impl: associated types and consts (`type Item`, `const N: usize`).
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub assoc: Option<Vec<String>>,
+  /// const / static: declared type; type alias: aliased type.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub ty: Option<String>,
+  /// static: `static mut`.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub mutable: Option<bool>,
+  /// `macro_rules!`: matcher of each rule.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub rules: Option<Vec<String>>,
+  /// mod: `kind name` of each direct child item.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub items: Option<Vec<String>>,
+  /// Doc comment text (`///` / `#[doc]`), one line per doc attribute.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub doc: Option<String>,
+  /// Other outer attributes, normalized (`#[derive(Debug, Clone)]`).
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub attrs: Option<Vec<String>>,
+}
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Field { pub name: String, pub ty: String, pub public: bool }
+
+/* ===================== reading and validating packs ===================== */
+
+/// `format` and `version` written by this build.
+pub const FORMAT: &str = "LLM-CODE-INDEX";
+pub const VERSION: &str = "3.0";
+
+/// Read an `index_v3.json`, merging the shards of a sharded pack into `files`.
+/// Only parses; see [`validate`] for the checks.
+pub fn load_pack(path: &Path) -> Result<IndexPack> {
+  PackReader::open(path)?.into_pack()
+}
+
+/// A pack opened by its manifest; shards are parsed only when asked for.
+pub struct PackReader {
+  dir: PathBuf,
+  manifest: IndexPack,
+}
+
+impl PackReader {
+  pub fn open(path: &Path) -> Result<Self> {
+    let manifest = read_pack_part(path, None)?;
+    Ok(PackReader { dir: path.parent().unwrap_or(Path::new(".")).to_path_buf(), manifest })
+  }
+
+  /// Top-level fields; `files` is empty when the pack is sharded.
+  pub fn manifest(&self) -> &IndexPack {
+    &self.manifest
+  }
+
+  /// One file's entry, parsing at most the shard that holds it.
+  pub fn file(&self, path: &str) -> Result<Option<FileEntry>> {
+    if let Some(f) = self.manifest.file(path) {
+      return Ok(Some(f.clone()));
+    }
+    let Some(shard) = self.manifest.shards.iter().find(|s| s.files.iter().any(|p| p == path)) else {
+      return Ok(None);
+    };
+    Ok(self.shard(shard)?.files.into_iter().find(|f| f.path == path))
+  }
+
+  /// Parse one shard, checking its hash and that it belongs to this pack.
+  pub fn shard(&self, shard: &ShardRef) -> Result<Shard> {
+    let s: Shard = read_pack_part(&self.dir.join(&shard.file), Some(&shard.sha256))?;
+    if s.pack_id != self.manifest.pack_id {
+      bail!("shard {} belongs to pack {}, not {}", shard.file, s.pack_id, self.manifest.pack_id);
+    }
+    Ok(s)
+  }
+
+  /// The whole pack with every shard's files in `files` (and `shards` empty),
+  /// as the single-file layout would have it.
+  pub fn into_pack(self) -> Result<IndexPack> {
+    let mut pack = self.manifest.clone();
+    for s in std::mem::take(&mut pack.shards) {
+      pack.files.extend(self.shard(&s)?.files);
+    }
+    Ok(pack)
+  }
+}
+
+/// Read a manifest or shard: checks `sha256` over the bytes on disk when
+/// given, inflates `*.zst`, then parses the JSON.
+pub(crate) fn read_pack_part<T: DeserializeOwned>(path: &Path, sha256: Option<&str>) -> Result<T> {
+  let raw = fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+  if sha256.is_some_and(|want| hex256(&raw) != want) {
+    bail!("{} does not match the sha256 in its manifest", path.display());
+  }
+  let raw = if path.extension().is_some_and(|e| e == "zst") { zstd_decode(&raw)? } else { raw };
+  serde_json::from_slice(&raw).with_context(|| format!("parsing {}", path.display()))
+}
+
+#[cfg(feature = "zstd")]
+fn zstd_decode(raw: &[u8]) -> Result<Vec<u8>> {
+  zstd::decode_all(raw).context("zstd decode")
+}
+#[cfg(not(feature = "zstd"))]
+fn zstd_decode(_: &[u8]) -> Result<Vec<u8>> {
+  bail!("this build has no zstd support (rebuild with `--features zstd`)")
+}
+#[cfg(feature = "zstd")]
+fn zstd_encode(raw: &[u8]) -> Result<Vec<u8>> {
+  zstd::encode_all(raw, 0).context("zstd encode")
+}
+#[cfg(not(feature = "zstd"))]
+fn zstd_encode(_: &[u8]) -> Result<Vec<u8>> {
+  bail!("this build has no zstd support (rebuild with `--features zstd`)")
+}
+
+impl IndexPack {
+  pub fn file(&self, path: &str) -> Option<&FileEntry> {
+    self.files.iter().find(|f| f.path == path)
+  }
+}
+
+impl Anchor {
+  /// Slice bytes as stored (with placeholders when `redacted` is set).
+  pub fn verbatim(&self) -> Result<Vec<u8>> {
+    B64.decode(&self.verbatim_b64).with_context(|| format!("decoding verbatim_b64 of {} {}", self.kind, self.path))
+  }
+
+  pub fn verbatim_text(&self) -> Result<String> {
+    String::from_utf8(self.verbatim()?).with_context(|| format!("{} {} is not UTF-8", self.kind, self.path))
+  }
+
+  /// True when the stored slice is the original source: not redacted and
+  /// hashing to `slice_sha256`.
+  pub fn verbatim_is_exact(&self) -> bool {
+    self.redacted.is_none() && self.verbatim().is_ok_and(|b| hex256(b) == self.slice_sha256)
+  }
+}
+
+/// A structural problem found by [`validate`]; `at` locates it
+/// (`files[2].anchors[0].range`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Problem {
+  pub at: String,
+  pub message: String,
+}
+
+/// Check a pack's own consistency without touching the tree: format and major
+/// version, hash formats, chunk layout and merkle root, anchor ranges, ids and
+/// slice hashes. Empty means well-formed; `indexer verify` compares with disk.
+/// Fields older 3.0 writers did not emit (generator, merkle scheme, anchor
+/// ids) are checked only when present.
+pub fn validate(pack: &IndexPack) -> Vec<Problem> {
+  let mut out = Vec::new();
+  let mut bad = |at: String, message: String| out.push(Problem { at, message });
+  if pack.format != FORMAT {
+    bad("format".into(), format!("expected {FORMAT}, found {}", pack.format));
+  }
+  if pack.version.split('.').next() != Some("3") {
+    bad("version".into(), format!("unsupported version {}", pack.version));
+  }
+  if pack.hash_algo != "sha256" {
+    bad("hash_algo".into(), format!("unsupported hash {}", pack.hash_algo));
+  }
+  if pack.pack_id.is_empty() || (pack.generator != Generator::default() && !is_hex(&pack.pack_id, 64)) {
+    bad("pack_id".into(), "not a sha256 hex digest".into());
+  }
+  let mut paths = std::collections::HashSet::new();
+  for (i, f) in pack.files.iter().enumerate() {
+    let at = format!("files[{i}]");
+    if f.path.is_empty() || !paths.insert(f.path.as_str()) {
+      bad(format!("{at}.path"), format!("empty or duplicate path `{}`", f.path));
+    }
+    if !is_hex(&f.file_sha256, 64) {
+      bad(format!("{at}.file_sha256"), "not a sha256 hex digest".into());
+    }
+    validate_chunks(&f.chunks, f.size_bytes, &format!("{at}.chunks"), &mut bad);
+    if f.parse_error.is_some() && !f.anchors.is_empty() {
+      bad(format!("{at}.anchors"), "anchors present next to a parse_error".into());
+    }
+    let mut ids = std::collections::HashSet::new();
+    for (j, a) in f.anchors.iter().enumerate() {
+      let at = format!("{at}.anchors[{j}]");
+      if a.kind.is_empty() || a.name.is_empty() {
+        bad(at.clone(), "empty kind or name".into());
+      }
+      if !a.anchor_id.is_empty() && (!is_hex(&a.anchor_id, 16) || !ids.insert(a.anchor_id.as_str())) {
+        bad(format!("{at}.anchor_id"), format!("malformed or duplicate id `{}`", a.anchor_id));
+      }
+      let Range { start_line, end_line } = a.range;
+      if start_line == 0 || start_line > end_line || end_line > f.line_count {
+        bad(format!("{at}.range"), format!("[{start_line}, {end_line}] outside 1..={}", f.line_count));
+      }
+      if a.span.0 > a.span.1 || a.span.1 > f.size_bytes {
+        bad(format!("{at}.span"), format!("[{}, {}] outside 0..={}", a.span.0, a.span.1, f.size_bytes));
+      }
+      if !is_hex(&a.slice_sha256, 64) {
+        bad(format!("{at}.slice_sha256"), "not a sha256 hex digest".into());
+      } else if a.verbatim().is_err() {
+        bad(format!("{at}.verbatim_b64"), "not base64".into());
+      } else if a.redacted.is_none() && !a.verbatim_is_exact() {
+        bad(format!("{at}.verbatim_b64"), "does not hash to slice_sha256".into());
+      }
+    }
+  }
+  out
+}
+
+fn validate_chunks(c: &ChunkSet, size_bytes: usize, at: &str, bad: &mut impl FnMut(String, String)) {
+  match (c.algo.as_str(), &c.cdc) {
+    ("fixed", _) if c.chunk_size_bytes > 0 => {}
+    ("fastcdc", Some(p)) if 0 < p.min_size && p.min_size <= p.avg_size && p.avg_size <= p.max_size => {}
+    _ => bad(format!("{at}.algo"), format!("bad chunking `{}` (size {}, cdc {:?})", c.algo, c.chunk_size_bytes, c.cdc)),
+  }
+  let mut off = 0;
+  for (k, ch) in c.list.iter().enumerate() {
+    if ch.index != k || ch.offset != off || ch.length == 0 || !is_hex(&ch.sha256, 64) {
+      bad(format!("{at}.list[{k}]"), "index, offset, length or sha256 out of place".into());
+      return;
+    }
+    off += ch.length;
+  }
+  if off != size_bytes {
+    bad(format!("{at}.list"), format!("chunks cover {off} of {size_bytes} bytes"));
+  }
+  let digests: Vec<merkle::Hash> = c.list.iter().filter_map(|ch| hex::decode(&ch.sha256).ok()?.try_into().ok()).collect();
+  match c.root_of(&digests) {
+    Some(root) if root == c.merkle_root => {}
+    Some(_) => bad(format!("{at}.merkle_root"), "does not match the chunk list".into()),
+    None => bad(format!("{at}.merkle"), format!("unknown scheme `{}`", c.merkle)),
+  }
+}
+
+impl ChunkSet {
+  /// Root over `digests` under this set's merkle scheme, if known.
+  pub fn root_of(&self, digests: &[merkle::Hash]) -> Option<String> {
+    match self.merkle.as_str() {
+      "" => Some(merkle::legacy_root(digests)),
+      merkle::DUP_ODD => Some(merkle::uncounted_root(digests)),
+      merkle::SCHEME => Some(merkle::root(digests)),
+      _ => None,
+    }
+  }
+
+  /// Recorded chunk hashes, in index order; `None` if any is not a sha256.
+  pub fn digests(&self) -> Option<Vec<merkle::Hash>> {
+    self.list.iter().map(|ch| hex::decode(&ch.sha256).ok()?.try_into().ok()).collect()
+  }
+
+  /// The chunking that produced `list`, if this build can reproduce it.
+  pub fn chunking(&self) -> Option<Chunking> {
+    match (self.algo.as_str(), &self.cdc) {
+      ("fixed", _) if self.chunk_size_bytes == 0 => Some(Chunking::default()),
+      ("fixed", _) => Some(Chunking::Fixed { size: self.chunk_size_bytes }),
+      ("fastcdc", Some(c)) => Some(Chunking::Cdc { min: c.min_size, avg: c.avg_size, max: c.max_size }),
+      _ => None,
+    }
+  }
+}
+
+fn is_hex(s: &str, len: usize) -> bool {
+  s.len() == len && s.bytes().all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+}
+
+pub fn build_index_v3(index_path: &Path, project_root: &Path, out_path: &Path) -> Result<V3Report> {
+  build_index_v3_with(index_path, project_root, out_path, &V3Options::default())
+}
+
+/// What [`build_index_v3_with`] wrote; `degraded` lists `(path, error)` for
+/// files kept without anchors or left out.
+#[derive(Debug, Default)]
+pub struct V3Report {
+  pub files: usize,
+  /// Shard files written (0 for the single-file layout).
+  pub shards: usize,
+  /// Entries taken unchanged from the previous pack.
+  pub reused: usize,
+  /// Changes against the previous pack, when there was one (also written
+  /// to `index_v3.delta.json`).
+  pub delta: Option<PackDelta>,
+  pub degraded: Vec<(String, String)>,
+}
+
+/// Build the pack best-effort: a file that can't be read is listed under
+/// `skipped`; one that can't be decoded or parsed keeps its hashes and chunks,
+/// gets a `parse_error` and no anchors. Only index/output I/O fails the build.
+pub fn build_index_v3_with(index_path: &Path, project_root: &Path, out_path: &Path, opts: &V3Options) -> Result<V3Report> {
   let entries = read_index(index_path).context("read_index")?; // JSONL or JSON array
+  let redactor = Redactor::load(project_root)?;
+  let (chunking, chunk_size_bytes, cdc) = opts.chunking.params();
+  let generator = Generator {
+    name: env!("CARGO_PKG_NAME").into(),
+    version: env!("CARGO_PKG_VERSION").into(),
+    options: GeneratorOptions { chunking: chunking.into(), chunk_size_bytes, cdc, redaction: redactor.fingerprint() },
+  };
+  let previous = if out_path.exists() { load_pack(out_path).ok() } else { None };
+  // Same generator (version, options, redaction rules): unchanged files keep their entries.
+  let reuse = previous.as_ref().is_some_and(|p| !opts.full && p.generator == generator);
+  let prev_files: HashMap<&str, &FileEntry> = previous.iter().flat_map(|p| &p.files).map(|f| (f.path.as_str(), f)).collect();
   let mut files = Vec::new();
+  let mut skipped = Vec::new();
+  let mut report = V3Report::default();
   for e in entries {
     let abs = project_root.join(&e.path);
-    let src = fs::read_to_string(&abs)
-      .with_context(|| format!("read {}", abs.display()))?;
-    let bytes = src.as_bytes();
-    let file_sha256 = hex256(bytes);
+    // Virtual paths (`index.html#script-1`) and vanished files land here.
+    let bytes = match fs::read(&abs) {
+      Ok(b) => b,
+      Err(err) => {
+        let error = format!("read failed: {err}");
+        report.degraded.push((e.path.clone(), error.clone()));
+        skipped.push(Skipped { path: e.path.clone(), error });
+        continue;
+      }
+    };
+    let file_sha256 = hex256(&bytes);
+    // Entries under an older merkle scheme are rebuilt so proofs stay available.
+    let unchanged = prev_files
+      .get(e.path.as_str())
+      .filter(|f| reuse && f.file_sha256 == file_sha256 && f.language == e.lang && f.chunks.merkle == merkle::SCHEME);
+    if let Some(&old) = unchanged {
+      if let Some(err) = &old.parse_error {
+        report.degraded.push((e.path.clone(), err.clone()));
+      }
+      files.push(old.clone());
+      report.reused += 1;
+      continue;
+    }
     let size_bytes = bytes.len();
-    let line_count = src.lines().count();
-    let (chunks, merkle_root) = chunk_and_merkle(bytes);
-    let anchors = if e.lang.eq_ignore_ascii_case("rust") || e.path.ends_with(".rs") {
-      extract_rust_anchors(&src)?
-    } else { Vec::new() };
+    let chunks = chunk_set(&bytes, opts.chunking);
+    let (encoding, line_count, anchors) = match std::str::from_utf8(&bytes) {
+      Ok(src) => {
+        let anchors = file_anchors(&e.lang, src, &e.path, &redactor);
+        ("utf-8", src.lines().count(), anchors)
+      }
+      Err(_) => ("binary", memchr::memchr_iter(b'\n', &bytes).count(), Err("not valid UTF-8".to_string())),
+    };
+    let (anchors, parse_error) = match anchors {
+      Ok(a) => (a, None),
+      Err(err) => {
+        report.degraded.push((e.path.clone(), err.clone()));
+        (Vec::new(), Some(err))
+      }
+    };
     files.push(FileEntry {
       path: e.path.clone(),
       language: e.lang.clone(),
       size_bytes,
       line_count,
-      encoding: "utf-8",
-      eol: "lf",
+      encoding: encoding.into(),
+      eol: "lf".into(),
       file_sha256,
-      chunks: ChunkSet { chunk_size_bytes: CHUNK, merkle_root, list: chunks },
+      chunks,
       anchors,
+      parse_error,
     });
   }
+  report.files = files.len();
   let pack = IndexPack {
-    format: "LLM-CODE-INDEX",
-    version: "3.0",
-    hash_algo: "sha256",
-    pack_id: crate::util::prefixed_filename("PACK", "uuid"), // placeholder slug
+    format: FORMAT.into(),
+    version: VERSION.into(),
+    hash_algo: "sha256".into(),
+    pack_id: pack_id(&generator, &files, &skipped)?,
     created_utc: crate::util::now_timestamp(),
-    lang: LangMeta { primary: "rust", dialect: "edition2021" },
+    lang: lang_meta(&files, project_root),
+    generator,
     rules: Rules {
-      mode: "strict",
-      patch_contract: PatchContract { diff_format: "unified", limit_scope_to_verified_anchors: true },
+      mode: "strict".into(),
+      patch_contract: PatchContract { diff_format: "unified".into(), limit_scope_to_verified_anchors: true },
     },
     files,
+    skipped,
+    shards: Vec::new(),
+  };
+  report.delta = write_delta(previous.as_ref(), &pack, out_path, opts.compact)?;
+  let shard_dir = out_path.with_extension("shards");
+  if opts.shard || opts.zstd {
+    report.shards = write_sharded(pack, out_path, &shard_dir, opts)?;
+  } else {
+    crate::util::safe_write(out_path, to_json(&pack, opts.compact)?)?;
+    // A previous sharded run's shards are no longer referenced.
+    if shard_dir.is_dir() {
+      fs::remove_dir_all(&shard_dir).with_context(|| format!("removing {}", shard_dir.display()))?;
+    }
+  }
+  Ok(report)
+}
+
+/// What changed between two packs, for agents holding `from_pack_id`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PackDelta {
+  pub from_pack_id: String,
+  pub to_pack_id: String,
+  pub created_utc: String,
+  pub files: FilesDelta,
+  /// Anchor changes in files present in both packs.
+  pub anchors: Vec<AnchorDelta>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct FilesDelta {
+  pub added: Vec<String>,
+  pub removed: Vec<String>,
+  /// Different `file_sha256`.
+  pub changed: Vec<String>,
+  pub unchanged: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnchorDelta {
+  /// File path.
+  pub file: String,
+  pub anchor_id: String,
+  pub kind: String,
+  pub path: String,
+  /// `added`, `removed`, `changed` (new slice) or `moved` (same slice, new range).
+  pub change: String,
+  /// Range in the new pack (absent for `removed`).
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub range: Option<Range>,
+}
+
+/// Write `index_v3.delta.json` against `previous`; without one, drop a stale delta.
+fn write_delta(previous: Option<&IndexPack>, pack: &IndexPack, out_path: &Path, compact: bool) -> Result<Option<PackDelta>> {
+  let delta_path = out_path.with_extension("delta.json");
+  let Some(prev) = previous else {
+    if delta_path.exists() {
+      fs::remove_file(&delta_path).with_context(|| format!("removing {}", delta_path.display()))?;
+    }
+    return Ok(None);
+  };
+  let delta = pack_delta(prev, pack);
+  crate::util::safe_write(&delta_path, to_json(&delta, compact)?)?;
+  Ok(Some(delta))
+}
+
+fn pack_delta(prev: &IndexPack, next: &IndexPack) -> PackDelta {
+  let old: BTreeMap<&str, &FileEntry> = prev.files.iter().map(|f| (f.path.as_str(), f)).collect();
+  let new: BTreeMap<&str, &FileEntry> = next.files.iter().map(|f| (f.path.as_str(), f)).collect();
+  let mut files = FilesDelta::default();
+  let mut anchors = Vec::new();
+  for (path, f) in &new {
+    let Some(o) = old.get(path) else {
+      files.added.push((*path).to_string());
+      continue;
+    };
+    if o.file_sha256 == f.file_sha256 {
+      files.unchanged += 1;
+    } else {
+      files.changed.push((*path).to_string());
+    }
+    if o.anchors != f.anchors {
+      anchors.extend(anchor_delta(path, &o.anchors, &f.anchors));
+    }
+  }
+  files.removed = old.keys().filter(|p| !new.contains_key(*p)).map(|p| (*p).to_string()).collect();
+  PackDelta {
+    from_pack_id: prev.pack_id.clone(),
+    to_pack_id: next.pack_id.clone(),
+    created_utc: next.created_utc.clone(),
+    files,
+    anchors,
+  }
+}
+
+fn anchor_delta(file: &str, old: &[Anchor], new: &[Anchor]) -> Vec<AnchorDelta> {
+  let before: HashMap<&str, &Anchor> = old.iter().map(|a| (a.anchor_id.as_str(), a)).collect();
+  let mut out = Vec::new();
+  let mut entry = |a: &Anchor, change: &str, range: Option<Range>| {
+    out.push(AnchorDelta {
+      file: file.to_string(),
+      anchor_id: a.anchor_id.clone(),
+      kind: a.kind.clone(),
+      path: a.path.clone(),
+      change: change.to_string(),
+      range,
+    });
+  };
+  for a in new {
+    match before.get(a.anchor_id.as_str()) {
+      None => entry(a, "added", Some(a.range.clone())),
+      Some(o) if o.slice_sha256 != a.slice_sha256 => entry(a, "changed", Some(a.range.clone())),
+      Some(o) if o.range != a.range => entry(a, "moved", Some(a.range.clone())),
+      Some(_) => {}
+    }
+  }
+  let after: std::collections::HashSet<&str> = new.iter().map(|a| a.anchor_id.as_str()).collect();
+  for a in old.iter().filter(|a| !after.contains(a.anchor_id.as_str())) {
+    entry(a, "removed", None);
+  }
+  out
+}
+
+fn to_json<T: Serialize>(v: &T, compact: bool) -> Result<Vec<u8>> {
+  Ok(if compact { serde_json::to_vec(v)? } else { serde_json::to_vec_pretty(v)? })
+}
+
+/// Split `pack.files` by package root / top-level dir into `shard_dir`, then
+/// write the manifest to `out_path`. Shards of earlier runs are removed.
+fn write_sharded(mut pack: IndexPack, out_path: &Path, shard_dir: &Path, opts: &V3Options) -> Result<usize> {
+  let packages = crate::changelog::package_roots_of(pack.files.iter().map(|f| f.path.as_str()));
+  let mut groups: BTreeMap<String, Vec<FileEntry>> = BTreeMap::new();
+  for f in std::mem::take(&mut pack.files) {
+    groups.entry(crate::changelog::group_key(&f.path, &packages)).or_default().push(f);
+  }
+  let dir_name = shard_dir.file_name().and_then(|n| n.to_str()).unwrap_or("index_v3.shards");
+  let mut written = std::collections::HashSet::new();
+  for (key, files) in groups {
+    let slug: String = key.trim_end_matches('/').chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect();
+    let name = format!("{}-{}.json{}", if slug == "_" { "root" } else { &slug }, &hex256(&key)[..8], if opts.zstd { ".zst" } else { "" });
+    let paths = files.iter().map(|f| f.path.clone()).collect();
+    let json = to_json(&Shard { pack_id: pack.pack_id.clone(), key: key.clone(), files }, opts.compact)?;
+    let bytes = if opts.zstd { zstd_encode(&json)? } else { json };
+    crate::util::safe_write(&shard_dir.join(&name), &bytes)?;
+    pack.shards.push(ShardRef { key, file: format!("{dir_name}/{name}"), sha256: hex256(&bytes), files: paths });
+    written.insert(name);
+  }
+  for e in fs::read_dir(shard_dir).into_iter().flatten().flatten() {
+    if !e.file_name().to_str().is_some_and(|n| written.contains(n)) {
+      let _ = fs::remove_file(e.path());
+    }
+  }
+  crate::util::safe_write(out_path, to_json(&pack, opts.compact)?)?;
+  Ok(written.len())
+}
+
+/// Content address of a pack; `created_utc` and anchors (derived from the
+/// file bytes by this generator version and its redaction rules, both in
+/// `generator`) stay out of it.
+fn pack_id(generator: &Generator, files: &[FileEntry], skipped: &[Skipped]) -> Result<String> {
+  let mut lines: Vec<String> = files.iter().map(|f| format!("{}\t{}", f.path, f.file_sha256)).collect();
+  lines.extend(skipped.iter().map(|s| format!("{}\t-", s.path)));
+  lines.sort_unstable();
+  let mut h = Sha256::new();
+  h.update(serde_json::to_vec(generator)?);
+  for l in &lines {
+    h.update(b"\n");
+    h.update(l.as_bytes());
+  }
+  Ok(hex::encode(h.finalize()))
+}
+
+fn lang_meta(files: &[FileEntry], project_root: &Path) -> LangMeta {
+  let mut bytes: BTreeMap<String, usize> = BTreeMap::new();
+  for f in files {
+    *bytes.entry(f.language.to_ascii_lowercase()).or_default() += f.size_bytes;
+  }
+  let pick = |code_only: bool| {
+    bytes
+      .iter()
+      .filter(|(l, _)| !code_only || !(crate::scan::lang_is_doc_or_config(l) || l.as_str() == "txt"))
+      .max_by(|a, b| a.1.cmp(b.1).then(b.0.cmp(a.0)))
+      .map(|(l, _)| l.clone())
+  };
+  let primary = pick(true).or_else(|| pick(false)).unwrap_or_else(|| "unknown".into());
+  let dialect = (primary == "rust")
+    .then(|| fs::read_to_string(project_root.join("Cargo.toml")).ok())
+    .flatten()
+    .and_then(|toml| cargo_edition(&toml))
+    .map(|e| format!("edition{e}"));
+  LangMeta { primary, dialect, bytes }
+}
+
+/// `edition` of `[package]` (or `[workspace.package]` for a virtual manifest).
+/// A package without one is on Cargo's default, 2015.
+fn cargo_edition(toml: &str) -> Option<String> {
+  let (mut section, mut package, mut workspace) = ("", None, None);
+  let mut has_package = false;
+  for line in toml.lines() {
+    let line = line.split('#').next().unwrap_or_default().trim();
+    if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+      section = name.trim();
+      has_package |= section == "package";
+      continue;
+    }
+    let Some((key, value)) = line.split_once('=') else { continue };
+    let value = value.trim().trim_matches('"').to_string();
+    match (section, key.trim()) {
+      ("package", "edition") => package = Some(value),
+      ("workspace.package", "edition") => workspace = Some(value),
+      _ => {}
+    }
+  }
+  // `edition.workspace = true` inherits from `[workspace.package]`.
+  let inherits = toml.lines().any(|l| l.trim().replace(' ', "").starts_with("edition.workspace=true"));
+  match package {
+    Some(e) => Some(e),
+    None if inherits || !has_package => workspace,
+    None => Some("2015".into()),
+  }
+}
+
+/// Anchors for one file, with any error from parsing, slicing or redaction
+/// turned into a `parse_error` message. Slicing is checked (`str::get`), so a
+/// bad offset is an error here rather than a panic.
+fn file_anchors(lang: &str, src: &str, path: &str, redactor: &Redactor) -> std::result::Result<Vec<Anchor>, String> {
+  let run = || -> Result<Vec<Anchor>> {
+    let mut anchors = extract_anchors(lang, src, path)?;
+    for a in &mut anchors {
+      redact_anchor(redactor, a)?;
+    }
+    Ok(anchors)
   };
-  let json = serde_json::to_string_pretty(&pack)?;
-  crate::util::safe_write(out_path, json)?;
+  run().map_err(|e| format!("{e:#}"))
+}
+
+/// Scrub secrets from the anchor's verbatim slice. Hashes and ranges are left
+/// alone so patch verification still runs against the real file.
+fn redact_anchor(redactor: &Redactor, a: &mut Anchor) -> Result<()> {
+  let raw = B64.decode(&a.verbatim_b64).context("decode verbatim_b64")?;
+  let text = String::from_utf8(raw).context("anchor slice is not utf-8")?;
+  let (clean, hits) = redactor.redact(&text);
+  if !hits.is_empty() {
+    a.verbatim_b64 = B64.encode(clean);
+    a.redacted = Some(hits.into_iter().map(|h| h.placeholder).collect());
+  }
   Ok(())
 }
 
-fn hex256(data: impl AsRef<[u8]>) -> String {
+/// `src[start..end]`, or an error when the offsets fall outside `src` or off
+/// a char boundary.
+fn slice_of(src: &str, start: usize, end: usize) -> Result<&str> {
+  src.get(start..end).ok_or_else(|| anyhow!("byte range {start}..{end} is not a slice of the file ({} bytes)", src.len()))
+}
+
+pub(crate) fn hex256(data: impl AsRef<[u8]>) -> String {
   let mut h = Sha256::new(); h.update(data.as_ref()); hex::encode(h.finalize())
 }
-fn chunk_and_merkle(bytes: &[u8]) -> (Vec<Chunk>, String) {
+fn chunk_set(bytes: &[u8], chunking: Chunking) -> ChunkSet {
   let mut list = Vec::new();
-  let mut digests = Vec::new();
-  let mut i = 0usize; let mut off = 0usize;
-  while off < bytes.len() {
-    let end = (off + CHUNK).min(bytes.len());
-    let slice = &bytes[off..end];
-    let d = hex256(slice);
-    list.push(Chunk { index: i, offset: off, length: slice.len(), sha256: d.clone() });
-    digests.push(hex::decode(d).unwrap());
-    off = end; i += 1;
-  }
-  // simple pairwise Merkle over raw digests (sha256)
-  let mut layer = digests;
-  while layer.len() > 1 {
-    let mut next = Vec::new();
-    for pair in layer.chunks(2) {
-      let combined = if pair.len() == 2 { [pair[0].as_slice(), pair[1].as_slice()].concat() } else { pair[0].clone() };
-      next.push(Sha256::digest(&combined).to_vec());
-    }
-    layer = next;
-  }
-  let root = layer.first().map(|d| hex::encode(d)).unwrap_or_else(|| hex256(&[]));
-  (list, root)
-}
-
-fn extract_rust_anchors(src: &str) -> Result<Vec<Anchor>> {
-  let file = syn::parse_file(src).context("parse rust")?;
+  let mut digests: Vec<merkle::Hash> = Vec::new();
+  for (i, (off, len)) in chunking.split(bytes).into_iter().enumerate() {
+    let d = Sha256::digest(&bytes[off..off + len]);
+    list.push(Chunk { index: i, offset: off, length: len, sha256: hex::encode(d) });
+    digests.push(d.into());
+  }
+  let (algo, chunk_size_bytes, cdc) = chunking.params();
+  ChunkSet { algo: algo.into(), chunk_size_bytes, cdc, merkle: merkle::SCHEME.into(), merkle_root: merkle::root(&digests), list }
+}
+
+/// Where an anchor sits now, for `indexer verify` to match against a pack.
+pub(crate) struct AnchorSlice {
+  pub kind: String,
+  pub name: String,
+  pub anchor_id: String,
+  pub start_line: usize,
+  pub end_line: usize,
+  pub slice_sha256: String,
+}
+
+/// Anchors of `src` at `file` as `indexer v3` would emit them (identity, hash and range only).
+/// `lang` is the index language; empty falls back to the extension.
+pub(crate) fn anchor_slices(lang: &str, src: &str, file: &str) -> Result<Vec<AnchorSlice>> {
+  Ok(extract_anchors(lang, src, file)?
+    .into_iter()
+    .map(|a| AnchorSlice {
+      kind: a.kind,
+      name: a.name,
+      anchor_id: a.anchor_id,
+      start_line: a.range.start_line,
+      end_line: a.range.end_line,
+      slice_sha256: a.slice_sha256,
+    })
+    .collect())
+}
+
+/// Anchors for a file in any language with an extractor; others have none.
+/// Anchors from the analyzer for `lang`, else the one claiming `file`'s path.
+fn extract_anchors(lang: &str, src: &str, file: &str) -> Result<Vec<Anchor>> {
+  crate::lang::analyzer(lang)
+    .and_then(|a| a.anchors(src, file))
+    .or_else(|| crate::lang::analyzer_for_path(Path::new(file)).and_then(|a| a.anchors(src, file)))
+    .unwrap_or_else(|| Ok(Vec::new()))
+}
+
+pub(crate) fn extract_rust_anchors(src: &str, file: &str) -> Result<Vec<Anchor>> {
+  let file_ast = syn::parse_file(src).context("parse rust")?;
+  let (root, module) = module_path_of(file);
   let mut out = Vec::new();
-  for item in file.items {
+  walk_items(src, file_ast.items, &module, &mut out)?;
+  assign_ids(&root, &mut out);
+  Ok(out)
+}
+
+/// `anchor_id` = sha256 of `root|kind|path`, first 16 hex. Same kind + path
+/// twice in a file (cfg-gated twins, several inherent impl blocks, overloads):
+/// later ones get `#2`, `#3`, .. in the key, in source order.
+pub fn assign_ids(root: &str, anchors: &mut [Anchor]) {
+  let mut seen: HashMap<(String, String), usize> = HashMap::new();
+  for a in anchors {
+    let n = seen.entry((a.kind.clone(), a.path.clone())).or_insert(0);
+    *n += 1;
+    let key = if *n == 1 { format!("{root}|{}|{}", a.kind, a.path) } else { format!("{root}|{}|{}#{n}", a.kind, a.path) };
+    a.anchor_id = hex256(key)[..16].to_string();
+  }
+}
+
+/// Anchor over `src[start..end]` for line-based extractors (built-in or a
+/// registered `lang::LanguageAnalyzer`); ids are set
+/// later by [`assign_ids`].
+#[allow(clippy::too_many_arguments)]
+pub fn span_anchor(
+  src: &str,
+  kind: &str,
+  name: &str,
+  path: String,
+  public: bool,
+  signature: String,
+  (start, end): (usize, usize),
+  schema: Schema,
+) -> Option<Anchor> {
+  let slice = src.get(start..end).filter(|s| !s.trim().is_empty())?;
+  Some(Anchor {
+    kind: kind.into(),
+    name: name.into(),
+    path,
+    anchor_id: String::new(),
+    visibility: if public { "pub".into() } else { "priv".into() },
+    signature: Some(signature),
+    range: line_range(src, start, end),
+    span: (start, end),
+    slice_sha256: hex256(slice),
+    verbatim_b64: B64.encode(slice),
+    redacted: None,
+    schema: Some(schema),
+  })
+}
+
+/// `(crate root dir, module path)` from a file path, relative to the nearest
+/// `src/`: `crates/core/src/scan/mod.rs` -> (`crates/core`, `crate::scan`).
+/// Files outside any `src/` (tests, examples, build.rs) keep their full path.
+fn module_path_of(file: &str) -> (String, String) {
+  let p = file.trim_end_matches(".rs").replace('\\', "/");
+  let (root, rel) = match p.rfind("src/") {
+    Some(i) if i == 0 || p[..i].ends_with('/') => (p[..i].trim_end_matches('/').to_string(), p[i + 4..].to_string()),
+    _ => (String::new(), p.clone()),
+  };
+  let mut segs: Vec<String> = rel.split('/').filter(|s| !s.is_empty()).map(|s| s.replace(['-', '.'], "_")).collect();
+  if segs.last().is_some_and(|s| s == "mod") || (segs.len() == 1 && (segs[0] == "lib" || segs[0] == "main")) {
+    segs.pop();
+  }
+  let module = std::iter::once("crate".to_string()).chain(segs).collect::<Vec<_>>().join("::");
+  (root, module)
+}
+
+/// Token string without layout spaces (`Vec<String>`, `&mut T`, `From<u8>`).
+fn tight(s: &str) -> String {
+  let chars: Vec<char> = s.chars().collect();
+  let glue = |c: char| matches!(c, '<' | '>' | ':' | '&' | '(' | ')' | '[' | ']' | ',' | '\'' | '*');
+  let mut out = String::new();
+  for (i, &c) in chars.iter().enumerate() {
+    if c == ' ' {
+      let prev = out.chars().last();
+      let next = chars.get(i + 1).copied();
+      if prev.is_some_and(|p| glue(p) && p != ',') || next.is_some_and(glue) {
+        continue;
+      }
+    }
+    out.push(c);
+  }
+  out
+}
+
+/// Qualified path of an impl (`crate::m::Foo<T>` or `<crate::m::Foo as
+/// From<u8>>`). Types and traits stay as written, generics included; only
+/// names declared in this module (`local`) get the module prefix, and leading
+/// `self::` / `super::` are resolved against it. `Vec<u8>`, a generic `T`
+/// or `std::fmt::Display` are left alone.
+fn impl_path(module: &str, local: &HashSet<String>, i: &ItemImpl) -> String {
+  let generics: HashSet<String> = i.generics.type_params().map(|t| t.ident.to_string()).collect();
+  let self_q = match &*i.self_ty {
+    syn::Type::Path(tp) if tp.qself.is_none() => qualify(module, local, &generics, &tp.path),
+    other => tight(&norm_tokens(other)),
+  };
+  match &i.trait_ {
+    Some((bang, path, _)) => format!("<{self_q} as {}{}>", if bang.is_some() { "!" } else { "" }, qualify(module, local, &generics, path)),
+    None => self_q,
+  }
+}
+
+fn qualify(module: &str, local: &HashSet<String>, generics: &HashSet<String>, path: &syn::Path) -> String {
+  if path.leading_colon.is_some() {
+    return tight(&norm_tokens(path));
+  }
+  let mut base = module.to_string();
+  let mut segs = path.segments.iter().peekable();
+  let mut relative = false;
+  while let Some(s) = segs.peek() {
+    match s.ident.to_string().as_str() {
+      "self" => {}
+      "super" => base.truncate(base.rfind("::").unwrap_or(base.len())),
+      _ => break,
+    }
+    relative = true;
+    segs.next();
+  }
+  let first = segs.peek().map(|s| s.ident.to_string()).unwrap_or_default();
+  let rest = segs.map(|s| tight(&norm_tokens(s))).collect::<Vec<_>>().join("::");
+  if relative || (local.contains(&first) && !generics.contains(&first)) {
+    format!("{base}::{rest}")
+  } else {
+    rest
+  }
+}
+
+/// Anchors for `items` in `module`, descending into inline `mod { .. }` blocks
+/// (tests included).
+fn walk_items(src: &str, items: Vec<Item>, module: &str, out: &mut Vec<Anchor>) -> Result<()> {
+  // Names an impl in this module may refer to unqualified.
+  let local: HashSet<String> = items
+    .iter()
+    .filter_map(|item| match item {
+      Item::Struct(i) => Some(i.ident.to_string()),
+      Item::Enum(i) => Some(i.ident.to_string()),
+      Item::Union(i) => Some(i.ident.to_string()),
+      Item::Type(i) => Some(i.ident.to_string()),
+      Item::Trait(i) => Some(i.ident.to_string()),
+      Item::Mod(i) => Some(i.ident.to_string()),
+      _ => None,
+    })
+    .collect();
+  for item in items {
+    let first = out.len();
+    let lead = leading_of(&item);
+    let is_impl = matches!(item, Item::Impl(_));
     match item {
       Item::Struct(s) => out.push(struct_anchor(src, s)?),
       Item::Enum(e)   => out.push(enum_anchor(src, e)?),
-      Item::Impl(i)   => out.extend(impl_anchors(src, i)?),
+      Item::Impl(i)   => {
+        let path = impl_path(module, &local, &i);
+        out.extend(impl_block_anchor(src, &i));
+        out.extend(impl_anchors(src, i)?);
+        for a in &mut out[first..] {
+          a.path = if a.kind == "impl" { path.clone() } else { format!("{path}::{}", a.name) };
+        }
+      }
       Item::Fn(f)     => out.push(fn_anchor(src, f)?),
+      Item::Trait(t)  => out.extend(trait_anchor(src, &t)),
+      Item::Const(c)  => out.extend(item_anchor(src, "const", c.ident.to_string(), &c.vis, c.const_token.span, &c, None,
+        Schema { ty: Some(norm_tokens(&*c.ty)), ..Default::default() })),
+      Item::Static(st) => out.extend(item_anchor(src, "static", st.ident.to_string(), &st.vis, st.static_token.span, &st, None,
+        Schema {
+          ty: Some(norm_tokens(&*st.ty)),
+          mutable: Some(matches!(st.mutability, syn::StaticMutability::Mut(_))),
+          ..Default::default()
+        })),
+      Item::Type(t)   => out.extend(item_anchor(src, "type", t.ident.to_string(), &t.vis, t.type_token.span, &t, None,
+        Schema { ty: Some(norm_tokens(&*t.ty)), ..Default::default() })),
+      Item::Union(u)  => out.extend(item_anchor(src, "union", u.ident.to_string(), &u.vis, u.union_token.span, &u, None,
+        Schema { fields: Some(named_fields(&u.fields.named)), ..Default::default() })),
+      Item::Macro(m)  => out.extend(macro_rules_anchor(src, &m)),
+      Item::Mod(m)    => {
+        let Some((_, children)) = m.content.clone() else { continue };
+        let listed = children.iter().filter_map(item_label).collect();
+        let inner = format!("{module}::{}", m.ident);
+        out.extend(item_anchor(src, "mod", m.ident.to_string(), &m.vis, m.mod_token.span, &m, None,
+          Schema { items: Some(listed), ..Default::default() }));
+        if let Some(a) = out.get_mut(first) {
+          a.path.clone_from(&inner);
+          if let Some((attrs, vis)) = &lead {
+            lead_in(src, a, attrs, vis);
+          }
+        }
+        walk_items(src, children, &inner, out)?;
+        continue;
+      }
       _ => {}
     }
+    // The item's own anchor comes first (an impl's methods follow its block).
+    if let (Some((attrs, vis)), Some(a)) = (&lead, out.get_mut(first)) {
+      if !is_impl || a.kind == "impl" {
+        lead_in(src, a, attrs, vis);
+      }
+    }
+    for a in &mut out[first..] {
+      if a.path.is_empty() {
+        a.path = format!("{module}::{}", a.name);
+      }
+    }
   }
-  Ok(out)
+  Ok(())
+}
+
+/// Outer attributes (docs included) and visibility written before an item.
+fn leading_of(item: &Item) -> Option<(Vec<Attribute>, Visibility)> {
+  let (attrs, vis) = match item {
+    Item::Struct(i) => (&i.attrs, i.vis.clone()),
+    Item::Enum(i) => (&i.attrs, i.vis.clone()),
+    Item::Fn(i) => (&i.attrs, i.vis.clone()),
+    Item::Trait(i) => (&i.attrs, i.vis.clone()),
+    Item::Const(i) => (&i.attrs, i.vis.clone()),
+    Item::Static(i) => (&i.attrs, i.vis.clone()),
+    Item::Type(i) => (&i.attrs, i.vis.clone()),
+    Item::Union(i) => (&i.attrs, i.vis.clone()),
+    Item::Mod(i) => (&i.attrs, i.vis.clone()),
+    Item::Impl(i) => (&i.attrs, Visibility::Inherited),
+    Item::Macro(i) => (&i.attrs, Visibility::Inherited),
+    _ => return None,
+  };
+  Some((attrs.iter().filter(|a| matches!(a.style, AttrStyle::Outer)).cloned().collect(), vis))
+}
+
+/// Widen an anchor to start at its first outer attribute / doc comment / `pub`
+/// (and any `unsafe`, `async`, `default` .. between them and the keyword), and
+/// record `doc` and `attrs` in its schema.
+fn lead_in(src: &str, a: &mut Anchor, attrs: &[Attribute], vis: &Visibility) {
+  let mut start = a.span.0;
+  for at in attrs {
+    start = start.min(span_start_offset(src, at.span()).unwrap_or(start));
+  }
+  if !matches!(vis, Visibility::Inherited) {
+    start = start.min(span_start_offset(src, vis.span()).unwrap_or(start));
+  }
+  // Qualifiers sit between visibility and keyword (`pub async fn`, `unsafe impl`):
+  // widen to the start of the keyword's line when only words precede it there.
+  let Some(before) = src.get(..start) else { return };
+  let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+  let lead = &before[line_start..];
+  if lead.chars().all(|c| c.is_alphanumeric() || c.is_whitespace() || "_\"()".contains(c)) {
+    start = line_start + (lead.len() - lead.trim_start().len());
+  }
+  let end = a.span.1;
+  if let Some(slice) = src.get(start..end).filter(|_| start < a.span.0) {
+    a.span.0 = start;
+    a.range = line_range(src, start, end);
+    a.slice_sha256 = hex256(slice);
+    a.verbatim_b64 = B64.encode(slice);
+  }
+
+  let doc: Vec<String> = attrs
+    .iter()
+    .filter(|at| at.path().is_ident("doc"))
+    .filter_map(|at| match &at.meta {
+      syn::Meta::NameValue(nv) => match &nv.value {
+        syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(s), .. }) => Some(s.value()),
+        _ => None,
+      },
+      _ => None,
+    })
+    .map(|l| l.strip_prefix(' ').map(str::to_string).unwrap_or(l))
+    .collect();
+  let others: Vec<String> = attrs.iter().filter(|at| !at.path().is_ident("doc")).map(|at| tight(&norm_tokens(at))).collect();
+  if let Some(schema) = a.schema.as_mut() {
+    schema.doc = (!doc.is_empty()).then(|| doc.join("\n"));
+    schema.attrs = (!others.is_empty()).then_some(others);
+  }
+}
+
+/// Anchor spanning `start` (the item keyword) to the end of `whole`; `None`
+/// when spans can't be mapped back onto `src`.
+#[allow(clippy::too_many_arguments)]
+fn item_anchor(
+  src: &str,
+  kind: &'static str,
+  name: String,
+  vis: &syn::Visibility,
+  start: Span,
+  whole: &dyn Spanned,
+  signature: Option<String>,
+  schema: Schema,
+) -> Option<Anchor> {
+  let start = span_start_offset(src, start)?;
+  let end = span_end_offset(src, whole.span())?;
+  let slice = src.get(start..end).filter(|s| !s.is_empty())?;
+  Some(Anchor {
+    kind: kind.into(),
+    name,
+    visibility: if matches!(vis, syn::Visibility::Public(_)) { "pub".into() } else { "priv".into() },
+    signature,
+    range: line_range(src, start, end),
+    span: (start, end),
+    slice_sha256: hex256(slice),
+    verbatim_b64: B64.encode(slice),
+    redacted: None,
+    path: String::new(),
+    anchor_id: String::new(),
+    schema: Some(schema),
+  })
+}
+
+fn named_fields(named: &syn::punctuated::Punctuated<syn::Field, syn::token::Comma>) -> Vec<Field> {
+  named.iter().map(|f| Field {
+    name: f.ident.as_ref().map(|i| i.to_string()).unwrap_or_default(),
+    ty: norm_tokens(&f.ty),
+    public: matches!(f.vis, syn::Visibility::Public(_)),
+  }).collect()
+}
+
+/// `kind name` for a module's child listing.
+fn item_label(item: &Item) -> Option<String> {
+  Some(match item {
+    Item::Struct(s) => format!("struct {}", s.ident),
+    Item::Enum(e) => format!("enum {}", e.ident),
+    Item::Fn(f) => format!("fn {}", f.sig.ident),
+    Item::Trait(t) => format!("trait {}", t.ident),
+    Item::Impl(i) => format!("impl {}", impl_name(i)),
+    Item::Const(c) => format!("const {}", c.ident),
+    Item::Static(s) => format!("static {}", s.ident),
+    Item::Type(t) => format!("type {}", t.ident),
+    Item::Union(u) => format!("union {}", u.ident),
+    Item::Mod(m) => format!("mod {}", m.ident),
+    Item::Macro(m) => format!("macro {}", m.ident.as_ref()?),
+    _ => return None,
+  })
+}
+
+fn trait_anchor(src: &str, t: &ItemTrait) -> Option<Anchor> {
+  let mut methods = Vec::new();
+  let mut assoc = Vec::new();
+  for it in &t.items {
+    match it {
+      TraitItem::Fn(f) => methods.push(crate::functions_view::norm_sig(&f.sig)),
+      TraitItem::Type(ty) => assoc.push(format!("type {}", ty.ident)),
+      TraitItem::Const(c) => assoc.push(format!("const {}: {}", c.ident, norm_tokens(&c.ty))),
+      _ => {}
+    }
+  }
+  let supertraits = t.supertraits.iter().map(norm_tokens).collect();
+  item_anchor(src, "trait", t.ident.to_string(), &t.vis, t.trait_token.span, t, None, Schema {
+    supertraits: Some(supertraits),
+    methods: Some(methods),
+    assoc: Some(assoc),
+    ..Default::default()
+  })
+}
+
+/// `Type` for inherent impls, `<Type as Trait>` for trait impls.
+fn impl_name(i: &ItemImpl) -> String {
+  let self_ty = norm_tokens(&*i.self_ty);
+  match &i.trait_ {
+    Some((bang, path, _)) => format!("<{self_ty} as {}{}>", if bang.is_some() { "!" } else { "" }, norm_tokens(path)),
+    None => self_ty,
+  }
+}
+
+/// The impl block as a whole (its methods get their own `fn` anchors).
+fn impl_block_anchor(src: &str, i: &ItemImpl) -> Option<Anchor> {
+  let mut methods = Vec::new();
+  let mut assoc = Vec::new();
+  for it in &i.items {
+    match it {
+      ImplItem::Fn(f) => methods.push(crate::functions_view::norm_sig(&f.sig)),
+      ImplItem::Type(ty) => assoc.push(format!("type {} = {}", ty.ident, norm_tokens(&ty.ty))),
+      ImplItem::Const(c) => assoc.push(format!("const {}: {}", c.ident, norm_tokens(&c.ty))),
+      _ => {}
+    }
+  }
+  let schema = Schema {
+    trait_: i.trait_.as_ref().map(|(_, p, _)| norm_tokens(p)),
+    self_ty: Some(norm_tokens(&*i.self_ty)),
+    methods: Some(methods),
+    assoc: Some(assoc),
+    ..Default::default()
+  };
+  // Impl blocks have no visibility of their own.
+  item_anchor(src, "impl", impl_name(i), &syn::Visibility::Inherited, i.impl_token.span, i, None, schema)
+}
+
+fn macro_rules_anchor(src: &str, m: &ItemMacro) -> Option<Anchor> {
+  let name = m.ident.as_ref()?.to_string();
+  if !m.mac.path.is_ident("macro_rules") {
+    return None;
+  }
+  // Each rule is `(matcher) => {body}`: keep the matcher groups.
+  let tokens: Vec<proc_macro2::TokenTree> = m.mac.tokens.clone().into_iter().collect();
+  let rules = tokens
+    .iter()
+    .enumerate()
+    .filter(|(k, t)| matches!(t, proc_macro2::TokenTree::Group(_)) && (*k == 0 || matches!(&tokens[k - 1], proc_macro2::TokenTree::Punct(p) if p.as_char() == ';')))
+    .map(|(_, t)| crate::types_view::norm_tokens(t))
+    .collect();
+  let is_exported = m.attrs.iter().any(|a| a.path().is_ident("macro_export"));
+  let vis = if is_exported { syn::Visibility::Public(Default::default()) } else { syn::Visibility::Inherited };
+  let start = m.mac.path.segments.first()?.ident.span();
+  item_anchor(src, "macro", name, &vis, start, m, None, Schema { rules: Some(rules), ..Default::default() })
 }
 
 fn struct_anchor(src: &str, s: ItemStruct) -> Result<Anchor> {
@@ -209,7 +1412,7 @@ fn struct_anchor(src: &str, s: ItemStruct) -> Result<Anchor> {
       find_balanced_block(src, "struct", &name).context("struct slice (fallback)")?
     }
   };
-  let slice = &src[start..end];
+  let slice = slice_of(src, start, end)?;
   let fields = match &s.fields {
     syn::Fields::Named(named) => named.named.iter().map(|f| Field {
       name: f.ident.as_ref().map(|i| i.to_string()).unwrap_or_default(),
@@ -219,14 +1422,18 @@ fn struct_anchor(src: &str, s: ItemStruct) -> Result<Anchor> {
     _ => vec![],
   };
   Ok(Anchor {
-    kind: "struct",
+    kind: "struct".into(),
     name,
     visibility: if matches!(s.vis, syn::Visibility::Public(_)) { "pub".into() } else { "priv".into() },
     signature: None,
     range: line_range(src, start, end),
+    span: (start, end),
     slice_sha256: hex256(slice),
     verbatim_b64: B64.encode(slice),
-    schema: Some(Schema { fields: Some(fields), variants: None, params: None, returns: None }),
+    redacted: None,
+    path: String::new(),
+    anchor_id: String::new(),
+    schema: Some(Schema { fields: Some(fields), variants: None, params: None, returns: None, ..Default::default() }),
   })
 }
 fn enum_anchor(src: &str, e: ItemEnum) -> Result<Anchor> {
@@ -237,38 +1444,51 @@ fn enum_anchor(src: &str, e: ItemEnum) -> Result<Anchor> {
     span_start_offset(src, e.brace_token.span.close()),
   ) {
     let end = close.saturating_add(1);
-    let slice = &src[start..end];
+    let slice = slice_of(src, start, end)?;
     let variants = e.variants.iter().map(|v| v.ident.to_string()).collect::<Vec<_>>();
     return Ok(Anchor {
-      kind: "enum",
+      kind: "enum".into(),
       name,
       visibility: if matches!(e.vis, syn::Visibility::Public(_)) { "pub".into() } else { "priv".into() },
       signature: None,
       range: line_range(src, start, end),
+      span: (start, end),
       slice_sha256: hex256(slice),
       verbatim_b64: B64.encode(slice),
-      schema: Some(Schema { fields: None, variants: Some(variants), params: None, returns: None }),
+      redacted: None,
+      path: String::new(),
+      anchor_id: String::new(),
+      schema: Some(Schema { fields: None, variants: Some(variants), params: None, returns: None, ..Default::default() }),
     });
   }
   // Fallback
   let (start, end) = find_balanced_block(src, "enum", &name).context("enum slice (fallback)")?;
-  let slice = &src[start..end];
+  let slice = slice_of(src, start, end)?;
   let variants = e.variants.iter().map(|v| v.ident.to_string()).collect::<Vec<_>>();
   Ok(Anchor {
-    kind: "enum",
+    kind: "enum".into(),
     name,
     visibility: if matches!(e.vis, syn::Visibility::Public(_)) { "pub".into() } else { "priv".into() },
     signature: None,
     range: line_range(src, start, end),
+    span: (start, end),
     slice_sha256: hex256(slice),
     verbatim_b64: B64.encode(slice),
-    schema: Some(Schema { fields: None, variants: Some(variants), params: None, returns: None }),
+    redacted: None,
+    path: String::new(),
+    anchor_id: String::new(),
+    schema: Some(Schema { fields: None, variants: Some(variants), params: None, returns: None, ..Default::default() }),
   })
 }
 fn impl_anchors(src: &str, i: ItemImpl) -> Result<Vec<Anchor>> {
   let mut out = Vec::new();
   for item in i.items {
-    if let ImplItem::Fn(f) = item { out.push(fn_anchor_from_impl(src, &f)?); }
+    if let ImplItem::Fn(f) = item {
+      let mut a = fn_anchor_from_impl(src, &f)?;
+      let attrs: Vec<Attribute> = f.attrs.iter().filter(|at| matches!(at.style, AttrStyle::Outer)).cloned().collect();
+      lead_in(src, &mut a, &attrs, &f.vis);
+      out.push(a);
+    }
   }
   Ok(out)
 }
@@ -281,21 +1501,25 @@ fn fn_anchor(src: &str, f: ItemFn) -> Result<Anchor> {
   ) {
     let fn_off = span_start_offset(src, f.sig.fn_token.span).unwrap_or(open_off.saturating_sub(128));
     let end_after_close = close_off.saturating_add(1);
-    let slice = &src[fn_off..end_after_close];
+    let slice = slice_of(src, fn_off, end_after_close)?;
     let is_pub = matches!(f.vis, syn::Visibility::Public(_));
     let ret = match &f.sig.output {
       syn::ReturnType::Default => "()".into(),
       syn::ReturnType::Type(_, t) => crate::types_view::norm_tokens(&**t),
     };
     return Ok(Anchor {
-      kind: "fn",
+      kind: "fn".into(),
       name: f.sig.ident.to_string(),
       visibility: if is_pub { "pub".into() } else { "priv".into() },
       signature: Some(crate::functions_view::norm_sig(&f.sig)),
       range: line_range(src, fn_off, end_after_close),
+      span: (fn_off, end_after_close),
       slice_sha256: hex256(slice),
       verbatim_b64: B64.encode(slice),
-      schema: Some(Schema { fields: None, variants: None, params: None, returns: Some(ret) }),
+      redacted: None,
+      path: String::new(),
+      anchor_id: String::new(),
+      schema: Some(Schema { fields: None, variants: None, params: None, returns: Some(ret), ..Default::default() }),
     });
   }
   // Fallback: signature-driven finder
@@ -311,21 +1535,25 @@ fn fn_anchor_from_impl(src: &str, f: &ImplItemFn) -> Result<Anchor> {
   ) {
     let fn_off = span_start_offset(src, f.sig.fn_token.span).unwrap_or(open_off.saturating_sub(128));
     let end_after_close = close_off.saturating_add(1);
-    let slice = &src[fn_off..end_after_close];
+    let slice = slice_of(src, fn_off, end_after_close)?;
     let is_pub = matches!(f.vis, syn::Visibility::Public(_));
     let ret = match &f.sig.output {
       syn::ReturnType::Default => "()".into(),
       syn::ReturnType::Type(_, t) => crate::types_view::norm_tokens(&**t),
     };
     return Ok(Anchor {
-      kind: "fn",
+      kind: "fn".into(),
       name: f.sig.ident.to_string(),
       visibility: if is_pub { "pub".into() } else { "priv".into() },
       signature: Some(crate::functions_view::norm_sig(&f.sig)),
       range: line_range(src, fn_off, end_after_close),
+      span: (fn_off, end_after_close),
       slice_sha256: hex256(slice),
       verbatim_b64: B64.encode(slice),
-      schema: Some(Schema { fields: None, variants: None, params: None, returns: Some(ret) }),
+      redacted: None,
+      path: String::new(),
+      anchor_id: String::new(),
+      schema: Some(Schema { fields: None, variants: None, params: None, returns: Some(ret), ..Default::default() }),
     });
   }
   // Fallback: signature-driven finder
@@ -352,21 +1580,25 @@ fn fn_anchor_sig(src: &str, sig: &syn::Signature, is_pub: bool) -> Result<Anchor
     }
     None => find_balanced_block(src, "fn", &name)?,
   };
-  let slice = &src[start..end];
+  let slice = slice_of(src, start, end)?;
   let ret = match &sig.output {
     syn::ReturnType::Default => "()".into(),
     syn::ReturnType::Type(_, t) => crate::types_view::norm_tokens(&**t),
   };
   Ok(Anchor {
-    kind: "fn",
+    kind: "fn".into(),
     name,
     visibility: if is_pub { "pub".into() } else { "priv".into() },
     signature: Some(crate::functions_view::norm_sig(sig)),
     range: line_range(src, start, end),
+    span: (start, end),
     slice_sha256: hex256(slice),
     verbatim_b64: B64.encode(slice),
+    redacted: None,
+    path: String::new(),
+    anchor_id: String::new(),
     // Keep params None; the normalized signature already carries full arg info.
-    schema: Some(Schema { fields: None, variants: None, params: None, returns: Some(ret) }),
+    schema: Some(Schema { fields: None, variants: None, params: None, returns: Some(ret), ..Default::default() }),
   })
 }
 
@@ -376,12 +1608,19 @@ fn span_start_offset(src: &str, sp: Span) -> Option<usize> {
   Some(offset_from_line_col(src, loc.line, loc.column))
 }
 
+/// Byte offset just past the end of a span.
+fn span_end_offset(src: &str, sp: Span) -> Option<usize> {
+  let loc = sp.end();
+  Some(offset_from_line_col(src, loc.line, loc.column))
+}
+
 fn offset_from_line_col(src: &str, line_1based: usize, col_0based: usize) -> usize {
   let mut off = 0usize;
   let mut line = 1usize;
   for l in src.split_inclusive('\n') {
     if line == line_1based {
-      return off + col_0based.min(l.len());
+      // Span columns count chars, not bytes.
+      return off + l.char_indices().nth(col_0based).map_or(l.len(), |(i, _)| i);
     }
     off += l.len();
     line += 1;
@@ -454,7 +1693,7 @@ fn line_range(src: &str, start: usize, end: usize) -> Range {
   #[inline]
   fn line_of(src: &str, pos: usize) -> usize {
     // Count '\n' strictly before pos → 1-based line number.
-    src[..pos].as_bytes().iter().filter(|&&b| b == b'\n').count() + 1
+    src.as_bytes()[..pos.min(src.len())].iter().filter(|&&b| b == b'\n').count() + 1
   }
   Range {
     start_line: line_of(src, start),
@@ -698,3 +1937,441 @@ fn consume_balanced_block(bytes: &[u8], start_brace: usize) -> Result<(usize, us
   }
   anyhow::bail!("unbalanced braces while consuming block")
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn hashes(bytes: &[u8], c: Chunking) -> Vec<String> {
+    c.split(bytes).into_iter().map(|(o, l)| hex256(&bytes[o..o + l])).collect()
+  }
+
+  #[test]
+  fn cdc_keeps_chunk_hashes_after_an_insert() {
+    // Deterministic pseudo-random text, ~200 KB.
+    let mut x = 7u64;
+    let src: String = (0..200_000)
+      .map(|_| {
+        x = x.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1_442_695_040_888_963_407);
+        (b'a' + ((x >> 33) % 26) as u8) as char
+      })
+      .collect();
+    let edited = format!("// one more line\n{src}");
+
+    let cdc = Chunking::CDC_DEFAULT;
+    let parts = cdc.split(src.as_bytes());
+    assert!(parts.iter().all(|&(_, l)| l <= 64 * 1024));
+    assert!(parts[..parts.len() - 1].iter().all(|&(_, l)| l >= 4 * 1024));
+    assert_eq!(parts.iter().map(|p| p.1).sum::<usize>(), src.len());
+
+    let (before, after) = (hashes(src.as_bytes(), cdc), hashes(edited.as_bytes(), cdc));
+    let kept = before.iter().filter(|h| after.contains(h)).count();
+    assert!(kept + 1 >= before.len(), "only {kept}/{} chunks survived", before.len());
+
+    let fixed = Chunking::default();
+    let (before, after) = (hashes(src.as_bytes(), fixed), hashes(edited.as_bytes(), fixed));
+    assert_eq!(before.iter().filter(|h| after.contains(h)).count(), 0);
+  }
+
+  #[test]
+  fn anchors_cover_every_item_kind_and_inline_mods() {
+    let src = r#"pub trait Shape: Clone {
+    type Unit;
+    fn area(&self) -> f64;
+}
+
+impl Shape for Sq {
+    type Unit = u8;
+    fn area(&self) -> f64 { 1.0 }
+}
+
+pub const MAX: usize = 3;
+static mut HITS: u32 = 0;
+pub type Id = u64;
+union Bits { i: u32, f: f32 }
+
+macro_rules! twice {
+    ($e:expr) => { $e * 2 };
+    () => { 0 };
+}
+
+mod inner {
+    pub fn helper() {}
+}
+
+#[cfg(test)]
+mod tests {
+    fn case() {}
+}
+"#;
+    let anchors = extract_rust_anchors(src, "src/geo.rs").unwrap();
+    let find = |kind: &str, name: &str| {
+      anchors.iter().find(|a| a.kind == kind && a.name == name).unwrap_or_else(|| panic!("no {kind} {name}"))
+    };
+    let text = |a: &Anchor| String::from_utf8(B64.decode(&a.verbatim_b64).unwrap()).unwrap();
+
+    let t = find("trait", "Shape");
+    let ts = t.schema.as_ref().unwrap();
+    assert_eq!(ts.methods.as_deref(), Some(&["fn area (& self) -> f64".to_string()][..]));
+    assert_eq!(ts.supertraits.as_deref(), Some(&["Clone".to_string()][..]));
+    assert_eq!((t.range.start_line, t.range.end_line), (1, 4));
+
+    let i = find("impl", "<Sq as Shape>");
+    let is = i.schema.as_ref().unwrap();
+    assert_eq!((is.trait_.as_deref(), is.self_ty.as_deref()), (Some("Shape"), Some("Sq")));
+    assert!(text(i).starts_with("impl Shape for Sq {") && text(i).ends_with('}'));
+    find("fn", "area");
+
+    assert_eq!(find("const", "MAX").schema.as_ref().unwrap().ty.as_deref(), Some("usize"));
+    assert_eq!(find("static", "HITS").schema.as_ref().unwrap().mutable, Some(true));
+    assert_eq!(text(find("type", "Id")), "pub type Id = u64;");
+    assert_eq!(find("union", "Bits").schema.as_ref().unwrap().fields.as_ref().unwrap().len(), 2);
+
+    let m = find("macro", "twice");
+    assert_eq!(m.schema.as_ref().unwrap().rules.as_ref().unwrap().len(), 2);
+    assert!(text(m).starts_with("macro_rules! twice"));
+
+    assert_eq!(find("mod", "inner").schema.as_ref().unwrap().items.as_deref(), Some(&["fn helper".to_string()][..]));
+    find("fn", "helper");
+    find("mod", "tests");
+    find("fn", "case");
+  }
+
+  #[test]
+  fn anchors_have_qualified_paths_and_line_independent_ids() {
+    assert_eq!(module_path_of("src/lib.rs"), (String::new(), "crate".into()));
+    assert_eq!(module_path_of("crates/core/src/scan/mod.rs"), ("crates/core".into(), "crate::scan".into()));
+    assert_eq!(module_path_of("src/a/b-c.rs").1, "crate::a::b_c");
+
+    let src = "pub struct ScanOptions;\nimpl ScanOptions { pub fn new() -> Self { Self } }\n\
+      impl Default for ScanOptions { fn default() -> Self { Self } }\n\
+      impl From<u8> for ScanOptions { fn from(_: u8) -> Self { Self } }\n\
+      impl ScanOptions { fn reset(&mut self) {} }\nmod inner { fn parse() {} }\nfn parse() {}\n";
+    let anchors = extract_rust_anchors(src, "src/scan.rs").unwrap();
+    let paths: Vec<(&str, &str)> = anchors.iter().map(|a| (a.kind.as_str(), a.path.as_str())).collect();
+    for want in [
+      ("struct", "crate::scan::ScanOptions"),
+      ("fn", "crate::scan::ScanOptions::new"),
+      ("impl", "<crate::scan::ScanOptions as Default>"),
+      ("fn", "<crate::scan::ScanOptions as Default>::default"),
+      ("fn", "<crate::scan::ScanOptions as From<u8>>::from"),
+      ("mod", "crate::scan::inner"),
+      ("fn", "crate::scan::inner::parse"),
+      ("fn", "crate::scan::parse"),
+    ] {
+      assert!(paths.contains(&want), "{want:?} not in {paths:?}");
+    }
+
+    let ids: Vec<&str> = anchors.iter().map(|a| a.anchor_id.as_str()).collect();
+    let mut unique = ids.clone();
+    unique.sort_unstable();
+    unique.dedup();
+    assert_eq!(unique.len(), ids.len(), "two inherent impls must not share an id");
+
+    // Shifting every item down keeps ids; another crate root changes them.
+    let moved = extract_rust_anchors(&format!("\n\n{src}"), "src/scan.rs").unwrap();
+    assert_eq!(moved.iter().map(|a| a.anchor_id.as_str()).collect::<Vec<_>>(), ids);
+    let other = extract_rust_anchors(src, "tools/src/scan.rs").unwrap();
+    assert_ne!(other[0].anchor_id, anchors[0].anchor_id);
+  }
+
+  #[test]
+  fn impl_paths_keep_foreign_and_generic_types_as_written() {
+    let src = "pub struct Bar<T>(T);\npub trait Foo<X> {}\n\
+      impl Foo<u8> for Vec<u8> {}\nimpl<T> Foo<T> for T {}\nimpl<T: Clone> Bar<T> { fn get(&self) {} }\n\
+      impl std::fmt::Display for Bar<u8> {}\nmod inner { impl super::Bar<i32> {} impl self::Q {} struct Q; }\n";
+    let anchors = extract_rust_anchors(src, "src/m.rs").unwrap();
+    let impls: Vec<&str> = anchors.iter().filter(|a| a.kind == "impl").map(|a| a.path.as_str()).collect();
+    assert_eq!(impls, [
+      "<Vec<u8> as crate::m::Foo<u8>>",
+      "<T as crate::m::Foo<T>>",
+      "crate::m::Bar<T>",
+      "<crate::m::Bar<u8> as std::fmt::Display>",
+      "crate::m::Bar<i32>",
+      "crate::m::inner::Q",
+    ]);
+    assert!(anchors.iter().any(|a| a.path == "crate::m::Bar<T>::get"));
+  }
+
+  #[test]
+  fn anchors_start_at_docs_and_attrs() {
+    let src = "use x;\n\n/// Adds.\n///\n/// Twice.\n#[inline]\n#[cfg(feature = \"a\")]\npub async fn add() {}\n\n\
+      impl S {\n    /// New.\n    pub(crate) const fn new() -> Self { S }\n}\n";
+    let anchors = extract_rust_anchors(src, "src/lib.rs").unwrap();
+    let add = anchors.iter().find(|a| a.name == "add").unwrap();
+    let text = String::from_utf8(B64.decode(&add.verbatim_b64).unwrap()).unwrap();
+    assert!(text.starts_with("/// Adds.\n///\n/// Twice.\n#[inline]") && text.ends_with("pub async fn add() {}"), "{text}");
+    assert_eq!((add.range.start_line, add.range.end_line), (3, 8));
+    let schema = add.schema.as_ref().unwrap();
+    assert_eq!(schema.doc.as_deref(), Some("Adds.\n\nTwice."));
+    assert_eq!(schema.attrs.as_deref(), Some(&["#[inline]".to_string(), "#[cfg(feature = \"a\")]".to_string()][..]));
+
+    let new = anchors.iter().find(|a| a.name == "new").unwrap();
+    let text = String::from_utf8(B64.decode(&new.verbatim_b64).unwrap()).unwrap();
+    assert_eq!(text, "/// New.\n    pub(crate) const fn new() -> Self { S }");
+    assert!(new.schema.as_ref().unwrap().attrs.is_none());
+  }
+
+  #[test]
+  fn bad_files_degrade_without_failing_the_pack() {
+    let dir = std::env::temp_dir().join(format!("indexer-v3-degrade-{}", std::process::id()));
+    fs::create_dir_all(dir.join("src")).unwrap();
+    fs::write(dir.join("src/ok.rs"), "pub fn ok() {}\n").unwrap();
+    fs::write(dir.join("src/broken.rs"), "pub fn broken( {\n").unwrap();
+    fs::write(dir.join("src/latin1.rs"), b"// caf\xe9\nfn x() {}\n").unwrap();
+    let index: String = ["src/ok.rs", "src/broken.rs", "src/latin1.rs", "index.html#script-1"]
+      .iter()
+      .map(|p| format!("{{\"path\":\"{p}\",\"lang\":\"rust\"}}\n"))
+      .collect();
+    fs::write(dir.join("index.jsonl"), index).unwrap();
+    let out = dir.join("index_v3.json");
+    let report = build_index_v3(&dir.join("index.jsonl"), &dir, &out).unwrap();
+    let pack: serde_json::Value = serde_json::from_str(&fs::read_to_string(&out).unwrap()).unwrap();
+    let _ = fs::remove_dir_all(&dir);
+
+    assert_eq!(report.files, 3);
+    let degraded: Vec<&str> = report.degraded.iter().map(|(p, _)| p.as_str()).collect();
+    assert_eq!(degraded, ["src/broken.rs", "src/latin1.rs", "index.html#script-1"]);
+    let files = pack["files"].as_array().unwrap();
+    assert!(files[0].get("parse_error").is_none());
+    assert_eq!(files[0]["anchors"].as_array().unwrap().len(), 1);
+    for f in &files[1..] {
+      assert!(f["parse_error"].is_string() && f["anchors"].as_array().unwrap().is_empty(), "{f}");
+      assert_eq!(f["chunks"]["list"].as_array().unwrap().len(), 1);
+    }
+    assert_eq!(pack["skipped"][0]["path"], "index.html#script-1");
+    // Offsets off a char boundary degrade the file instead of panicking.
+    assert!(slice_of("café", 0, 4).is_err());
+    assert_eq!(slice_of("café", 0, 5).unwrap(), "café");
+  }
+
+  #[test]
+  fn identical_trees_share_a_pack_id() {
+    let build_with = |name: &str, py: &str, opts: &V3Options, rules: &str| {
+      let dir = std::env::temp_dir().join(format!("indexer-v3-id-{name}-{}", std::process::id()));
+      fs::create_dir_all(dir.join("src")).unwrap();
+      fs::write(dir.join("Cargo.toml"), "[package]\nname = \"x\"\nedition = \"2024\" # new\n").unwrap();
+      fs::write(dir.join("src/lib.rs"), "pub fn a() {}\n").unwrap();
+      fs::write(dir.join("tool.py"), py).unwrap();
+      if !rules.is_empty() {
+        fs::write(dir.join(crate::redact::REDACT_FILE), rules).unwrap();
+      }
+      let index = "{\"path\":\"tool.py\",\"lang\":\"python\"}\n{\"path\":\"src/lib.rs\",\"lang\":\"rust\"}\n\
+        {\"path\":\"Cargo.toml\",\"lang\":\"toml\"}\n";
+      fs::write(dir.join("index.jsonl"), index).unwrap();
+      build_index_v3_with(&dir.join("index.jsonl"), &dir, &dir.join("v3.json"), opts).unwrap();
+      let pack: serde_json::Value = serde_json::from_str(&fs::read_to_string(dir.join("v3.json")).unwrap()).unwrap();
+      let _ = fs::remove_dir_all(&dir);
+      pack
+    };
+    let build = |name: &str, py: &str, opts: &V3Options| build_with(name, py, opts, "");
+    let fixed = V3Options::default();
+    let (a, b) = (build("a", "x = 1\n", &fixed), build("b", "x = 1\n", &fixed));
+    assert_eq!(a["pack_id"], b["pack_id"]);
+    assert_eq!(a["pack_id"].as_str().unwrap().len(), 64);
+    assert_ne!(a["pack_id"], build("c", "x = 2\n", &fixed)["pack_id"]);
+    assert_ne!(a["pack_id"], build("d", "x = 1\n", &V3Options { chunking: Chunking::CDC_DEFAULT, ..V3Options::default() })["pack_id"]);
+    // Other redaction rules would redact the anchors differently.
+    let redacted = build_with("r", "x = 1\n", &fixed, "internal: corp-[a-z]+\n");
+    assert_ne!(a["pack_id"], redacted["pack_id"]);
+    assert_ne!(a["generator"]["options"]["redaction"], redacted["generator"]["options"]["redaction"]);
+
+    assert_eq!(a["lang"]["primary"], "rust");
+    assert_eq!(a["lang"]["dialect"], "edition2024");
+    assert_eq!(a["lang"]["bytes"]["python"], 6);
+    assert_eq!(a["generator"]["version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(a["generator"]["options"]["chunking"], "fixed");
+    let py = build("e", "def f():\n    return 1\n", &fixed);
+    assert_eq!(py["lang"]["primary"], "python");
+    assert!(py["lang"].get("dialect").is_none());
+  }
+
+  #[test]
+  fn cargo_edition_reads_package_and_workspace() {
+    assert_eq!(cargo_edition("[package]\nedition = \"2021\"\n").as_deref(), Some("2021"));
+    assert_eq!(cargo_edition("[package]\nname = \"x\"\n[dependencies]\nedition = \"1\"\n").as_deref(), Some("2015"));
+    let ws = "[workspace.package]\nedition = \"2024\"\n";
+    assert_eq!(cargo_edition(ws).as_deref(), Some("2024"));
+    assert_eq!(cargo_edition(&format!("{ws}[package]\nedition.workspace = true\n")).as_deref(), Some("2024"));
+    assert_eq!(cargo_edition("[workspace]\nmembers = []\n"), None);
+  }
+
+  #[test]
+  fn packs_load_validate_and_match_the_published_schema() {
+    let dir = std::env::temp_dir().join(format!("indexer-v3-load-{}", std::process::id()));
+    fs::create_dir_all(dir.join("src")).unwrap();
+    fs::write(dir.join("src/lib.rs"), "/// A.\npub fn a() -> u8 {\n    1\n}\n\npub struct S { pub x: u8 }\n").unwrap();
+    fs::write(dir.join("index.jsonl"), "{\"path\":\"src/lib.rs\",\"lang\":\"rust\"}\n").unwrap();
+    let out = dir.join("index_v3.json");
+    build_index_v3(&dir.join("index.jsonl"), &dir, &out).unwrap();
+    let raw: serde_json::Value = serde_json::from_str(&fs::read_to_string(&out).unwrap()).unwrap();
+    let pack = load_pack(&out).unwrap();
+    let _ = fs::remove_dir_all(&dir);
+
+    assert_eq!(serde_json::to_value(&pack).unwrap(), raw, "load_pack must round-trip");
+    assert_eq!(validate(&pack), []);
+    let a = &pack.file("src/lib.rs").unwrap().anchors[0];
+    assert_eq!(a.verbatim_text().unwrap(), "/// A.\npub fn a() -> u8 {\n    1\n}");
+    assert!(a.verbatim_is_exact());
+
+    let mut bad = pack.clone();
+    bad.version = "4.0".into();
+    let f = &mut bad.files[0];
+    f.chunks.list[0].sha256 = "0".repeat(64);
+    f.anchors[0].range.end_line = 99;
+    f.anchors[1].verbatim_b64 = B64.encode("pub struct T;");
+    f.anchors[1].anchor_id = f.anchors[0].anchor_id.clone();
+    let at: Vec<String> = validate(&bad).into_iter().map(|p| p.at).collect();
+    assert_eq!(at, [
+      "version",
+      "files[0].chunks.merkle_root",
+      "files[0].anchors[0].range",
+      "files[0].anchors[1].anchor_id",
+      "files[0].anchors[1].verbatim_b64",
+    ]);
+
+    // Every field the schema requires is written.
+    let schema: serde_json::Value = serde_json::from_str(include_str!("../../docs/index_v3.schema.json")).unwrap();
+    let required = |v: &serde_json::Value| v["required"].as_array().unwrap().iter().map(|k| k.as_str().unwrap().to_string()).collect::<Vec<_>>();
+    let has_all = |obj: &serde_json::Value, def: &serde_json::Value| required(def).iter().all(|k| obj.get(k).is_some());
+    assert!(has_all(&raw, &schema));
+    assert!(has_all(&raw["files"][0], &schema["$defs"]["file"]));
+    assert!(has_all(&raw["files"][0]["chunks"], &schema["$defs"]["chunks"]));
+    assert!(has_all(&raw["files"][0]["anchors"][0], &schema["$defs"]["anchor"]));
+
+    // A 3.0 pack from before generator blocks, merkle schemes and anchor ids
+    // still loads and validates.
+    let mut old = raw.clone();
+    old["pack_id"] = "PACK_uuid".into();
+    for key in ["generator", "skipped"] {
+      old.as_object_mut().unwrap().remove(key);
+    }
+    old["lang"] = serde_json::json!({ "primary": "rust", "dialect": "edition2021" });
+    let chunks = &mut old["files"][0]["chunks"];
+    let digests: Vec<merkle::Hash> = pack.files[0].chunks.digests().unwrap();
+    chunks["merkle_root"] = merkle::legacy_root(&digests).into();
+    for key in ["algo", "merkle", "cdc"] {
+      chunks.as_object_mut().unwrap().remove(key);
+    }
+    for a in old["files"][0]["anchors"].as_array_mut().unwrap() {
+      for key in ["path", "anchor_id", "redacted"] {
+        a.as_object_mut().unwrap().remove(key);
+      }
+    }
+    let old: IndexPack = serde_json::from_value(old).unwrap();
+    assert_eq!((old.files[0].chunks.algo.as_str(), old.files[0].chunks.merkle.as_str()), ("fixed", ""));
+    assert_eq!(validate(&old), []);
+  }
+
+  #[test]
+  fn sharded_packs_split_by_package_and_load_per_file() {
+    let dir = std::env::temp_dir().join(format!("indexer-v3-shard-{}", std::process::id()));
+    for (path, body) in [
+      ("crates/core/Cargo.toml", "[package]\nname = \"core\"\n"),
+      ("crates/core/src/lib.rs", "pub fn core() {}\n"),
+      ("src/main.rs", "fn main() {}\n"),
+      ("build.rs", "fn main() {}\n"),
+    ] {
+      fs::create_dir_all(dir.join(path).parent().unwrap()).unwrap();
+      fs::write(dir.join(path), body).unwrap();
+    }
+    let index: String = ["crates/core/Cargo.toml", "crates/core/src/lib.rs", "src/main.rs", "build.rs"]
+      .iter()
+      .map(|p| format!("{{\"path\":\"{p}\",\"lang\":\"{}\"}}\n", if p.ends_with(".rs") { "rust" } else { "toml" }))
+      .collect();
+    fs::write(dir.join("index.jsonl"), index).unwrap();
+    let (single, out) = (dir.join("single.json"), dir.join("index_v3.json"));
+    build_index_v3(&dir.join("index.jsonl"), &dir, &single).unwrap();
+    let opts = V3Options { shard: true, compact: true, ..V3Options::default() };
+    let report = build_index_v3_with(&dir.join("index.jsonl"), &dir, &out, &opts).unwrap();
+
+    let reader = PackReader::open(&out).unwrap();
+    let m = reader.manifest();
+    let keys: Vec<&str> = m.shards.iter().map(|s| s.key.as_str()).collect();
+    assert_eq!((report.shards, m.files.len(), keys), (3, 0, vec!["./", "crates/core/", "src/"]));
+    assert!(!fs::read_to_string(&out).unwrap().contains('\n'), "compact manifest");
+    let lib = reader.file("crates/core/src/lib.rs").unwrap().unwrap();
+    assert_eq!(lib.anchors[0].name, "core");
+    assert!(reader.file("nope.rs").unwrap().is_none());
+
+    // Merged, it is the single-file pack with the same id and files.
+    let (merged, whole) = (load_pack(&out).unwrap(), load_pack(&single).unwrap());
+    assert_eq!(merged.pack_id, whole.pack_id);
+    let by_path = |p: &IndexPack| p.files.iter().map(|f| (f.path.clone(), f.clone())).collect::<BTreeMap<_, _>>();
+    assert_eq!(by_path(&merged), by_path(&whole));
+    assert_eq!(validate(&merged), []);
+    assert!(crate::verify::verify_pack(&out, &dir).unwrap().safe);
+
+    // A shard edited behind the manifest's back is refused.
+    let shard = dir.join(&m.shards[2].file);
+    fs::write(&shard, fs::read_to_string(&shard).unwrap().replace("main", "mian")).unwrap();
+    assert!(reader.file("src/main.rs").is_err());
+
+    // Back to one file: the shard dir goes away.
+    build_index_v3(&dir.join("index.jsonl"), &dir, &out).unwrap();
+    assert!(!dir.join("index_v3.shards").exists());
+    let _ = fs::remove_dir_all(&dir);
+  }
+
+  #[cfg(feature = "zstd")]
+  #[test]
+  fn zstd_shards_round_trip() {
+    let dir = std::env::temp_dir().join(format!("indexer-v3-zstd-{}", std::process::id()));
+    fs::create_dir_all(dir.join("src")).unwrap();
+    fs::write(dir.join("src/lib.rs"), "pub fn a() {}\n".repeat(50)).unwrap();
+    fs::write(dir.join("index.jsonl"), "{\"path\":\"src/lib.rs\",\"lang\":\"rust\"}\n").unwrap();
+    let out = dir.join("index_v3.json");
+    build_index_v3_with(&dir.join("index.jsonl"), &dir, &out, &V3Options { zstd: true, ..V3Options::default() }).unwrap();
+    let pack = load_pack(&out).unwrap();
+    let file = PackReader::open(&out).unwrap().manifest().shards[0].file.clone();
+    let _ = fs::remove_dir_all(&dir);
+    assert!(file.ends_with(".json.zst"), "{file}");
+    assert_eq!(pack.files[0].path, "src/lib.rs");
+    assert_eq!(validate(&pack), []);
+  }
+
+  #[test]
+  fn rebuilds_reuse_unchanged_files_and_write_a_delta() {
+    let dir = std::env::temp_dir().join(format!("indexer-v3-delta-{}", std::process::id()));
+    fs::create_dir_all(dir.join("src")).unwrap();
+    fs::write(dir.join("src/a.rs"), "pub fn keep() {}\n").unwrap();
+    fs::write(dir.join("src/b.rs"), "pub fn moved() {}\n\npub fn edited() {}\n\npub fn dropped() {}\n").unwrap();
+    fs::write(dir.join("src/old.rs"), "fn old() {}\n").unwrap();
+    let index = |paths: &[&str]| {
+      let lines: String = paths.iter().map(|p| format!("{{\"path\":\"{p}\",\"lang\":\"rust\"}}\n")).collect();
+      fs::write(dir.join("index.jsonl"), lines).unwrap();
+    };
+    let out = dir.join("index_v3.json");
+    index(&["src/a.rs", "src/b.rs", "src/old.rs"]);
+    let first = build_index_v3(&dir.join("index.jsonl"), &dir, &out).unwrap();
+    assert!(first.delta.is_none() && !dir.join("index_v3.delta.json").exists());
+
+    fs::write(dir.join("src/b.rs"), "\npub fn moved() {}\n\npub fn edited() { () }\n\npub fn fresh() {}\n").unwrap();
+    fs::write(dir.join("src/new.rs"), "fn new() {}\n").unwrap();
+    index(&["src/a.rs", "src/b.rs", "src/new.rs"]);
+    let second = build_index_v3(&dir.join("index.jsonl"), &dir, &out).unwrap();
+    assert_eq!(second.reused, 1);
+    let delta: PackDelta = read_pack_part(&dir.join("index_v3.delta.json"), None).unwrap();
+    assert_eq!(Some(&delta), second.delta.as_ref());
+    assert_eq!(delta.to_pack_id, load_pack(&out).unwrap().pack_id);
+    assert_eq!(
+      (delta.files.added, delta.files.removed, delta.files.changed, delta.files.unchanged),
+      (vec!["src/new.rs".to_string()], vec!["src/old.rs".to_string()], vec!["src/b.rs".to_string()], 1)
+    );
+    let changes: Vec<(&str, &str)> = delta.anchors.iter().map(|a| (a.path.as_str(), a.change.as_str())).collect();
+    assert_eq!(changes, [
+      ("crate::b::moved", "moved"),
+      ("crate::b::edited", "changed"),
+      ("crate::b::fresh", "added"),
+      ("crate::b::dropped", "removed"),
+    ]);
+
+    // Reuse gives the same files as a full rebuild; other options reuse nothing.
+    let reused = load_pack(&out).unwrap().files;
+    let full = build_index_v3_with(&dir.join("index.jsonl"), &dir, &out, &V3Options { full: true, ..V3Options::default() }).unwrap();
+    assert_eq!((full.reused, load_pack(&out).unwrap().files), (0, reused));
+    let cdc = V3Options { chunking: Chunking::CDC_DEFAULT, ..V3Options::default() };
+    assert_eq!(build_index_v3_with(&dir.join("index.jsonl"), &dir, &out, &cdc).unwrap().reused, 0);
+    let _ = fs::remove_dir_all(&dir);
+  }
+}
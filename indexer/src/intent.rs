@@ -16,6 +16,7 @@ pub fn guess_summary(path: &str, snippet: &str, lang: &str) -> String {
     // Trim snippet to a reasonable scan window.
     let scan = trim_window(snippet, MAX_SCAN_BYTES);
     let sl = scan.to_ascii_lowercase();
+    let analyzer = crate::lang::analyzer(lang);
 
     // --- 0) Project-specific short-circuits (our own modules) ---
     if pl.ends_with("map_view.rs")    { return s("Builds semantic project map (markdown)."); }
@@ -43,7 +44,7 @@ pub fn guess_summary(path: &str, snippet: &str, lang: &str) -> String {
 
     // --- 2) Entrypoints ---
     if is_rust_bin_entry(&pl, &sl)       { return s("Entrypoint for this Rust binary."); }
-    if is_python_entry(lang, &sl)        { return s("Python script entrypoint."); }
+    if let Some(hint) = analyzer.as_ref().and_then(|a| a.entry_hint(&pl, &sl)) { return hint; }
     if ends_with(&pl, "lib.rs")          { return s("Root library file for this Rust crate."); }
 
     // --- 3) Tests ---
@@ -77,12 +78,9 @@ pub fn guess_summary(path: &str, snippet: &str, lang: &str) -> String {
         return s("Filesystem / IO utilities.");
     }
 
-    // --- 6) Language-specific nudges ---
-    if eq_ic(lang, "rust") && contains(&pl, "/types") {
-        return s("Type definitions / data models.");
-    }
-    if eq_ic(lang, "rust") && contains(&pl, "/util") {
-        return s("Utility helpers for the crate.");
+    // --- 6) Language-specific nudges (`LanguageAnalyzer::summary_hint`) ---
+    if let Some(hint) = analyzer.as_ref().and_then(|a| a.summary_hint(&pl, &sl)) {
+        return hint;
     }
 
     // --- 7) Doc comment or Markdown heading extraction (better fallback) ---
@@ -118,9 +116,6 @@ fn any_in(hay: &str, needles: &[&str]) -> bool {
     needles.iter().any(|n| hay.contains(n))
 }
 
-#[inline]
-fn eq_ic(a: &str, b: &str) -> bool { a.eq_ignore_ascii_case(b) }
-
 #[inline]
 fn normalize_path(p: &str) -> String { p.replace('\\', "/") }
 
@@ -155,8 +150,16 @@ fn is_rust_bin_entry(pl: &str, sl: &str) -> bool {
     ends_with(pl, "src/main.rs") || contains(pl, "/bin/") || sl.contains("fn main(")
 }
 
-fn is_python_entry(lang: &str, sl: &str) -> bool {
-    eq_ic(lang, "python") && sl.contains("if __name__ == '__main__'")
+/// Summary hint for Rust files (lowercased path).
+pub(crate) fn rust_hint(pl: &str) -> Option<String> {
+    if contains(pl, "/types") { return Some(s("Type definitions / data models.")); }
+    if contains(pl, "/util")  { return Some(s("Utility helpers for the crate.")); }
+    None
+}
+
+/// Entrypoint hint for Python files (lowercased snippet).
+pub(crate) fn python_entry_hint(sl: &str) -> Option<String> {
+    contains(sl, "if __name__ == '__main__'").then(|| s("Python script entrypoint."))
 }
 
 fn is_test_file(pl: &str, sl: &str) -> bool {
@@ -265,6 +268,15 @@ mod tests {
         assert!(sum.to_lowercase().contains("entrypoint"));
     }
 
+    #[test]
+    fn python_entry_wins_over_later_heuristics() {
+        // `__main__` is an entrypoint check (section 2): it beats the test-file
+        // and feature-path heuristics that follow it.
+        let src = "import unittest\n\nif __name__ == '__main__':\n    unittest.main()\n";
+        assert_eq!(guess_summary("tools/ui/test_cli.py", src, "python"), "Python script entrypoint.");
+        assert_eq!(guess_summary("tools/ui/test_cli.py", src, "py"), "Python script entrypoint.");
+    }
+
     #[test]
     fn detects_test_file() {
         let sum = guess_summary("src/foo_test.rs", "#[test]\nfn t(){}", "rust");
@@ -0,0 +1,361 @@
+// indexer/src/lang.rs
+//! Language analyzers: everything language-specific behind one trait.
+//!
+//! - `LanguageAnalyzer` bundles detection (path → label), snippet line scoring,
+//!   leading doc extraction, import/export skimming, module id derivation,
+//!   entrypoint and summary hints, the Markdown fence name and (optionally)
+//!   v3 anchors.
+//! - A process-wide registry holds the built-in languages; `register` adds
+//!   more and `unregister` drops one. Later registrations are consulted
+//!   first, so a library user can both add a language and override a
+//!   built-in one.
+//! - `util::ext_to_lang`, `snippet`, `helpers::{infer_module_id, skim_symbols}`,
+//!   `intent::guess_summary`, the chunker's fences and `index_v3` anchors all
+//!   dispatch through here.
+
+use crate::index_v3::Anchor;
+use anyhow::Result;
+use std::path::Path;
+use std::sync::{Arc, OnceLock, RwLock};
+
+/* ================================= Trait ================================== */
+
+/// One language. Only `name` and `detect` are required; every other method
+/// defaults to the generic behaviour used for unknown languages.
+pub trait LanguageAnalyzer: Send + Sync {
+    /// Canonical label stored in the index (`rust`, `python`, `ts`, ..).
+    fn name(&self) -> &'static str;
+
+    /// Other labels that resolve to this analyzer (`rs`, `typescript`, ..).
+    fn aliases(&self) -> &[&'static str] {
+        &[]
+    }
+
+    /// Whether `path` is a file of this language (extension or file name).
+    fn detect(&self, path: &Path) -> bool;
+
+    /// Snippet score for a trimmed line: 0 drops it, higher keeps it first.
+    fn score_line(&self, line: &str) -> u8 {
+        crate::snippet::score_generic(line, &line.to_ascii_lowercase())
+    }
+
+    /// Doc/comment block at the head of the file, one entry per line.
+    fn leading_doc(&self, head: &str) -> Option<Vec<String>> {
+        crate::snippet::leading_generic_head(head)
+    }
+
+    /// `(imports, exports)` skimmed from a snippet.
+    fn skim_symbols(&self, _snippet: &str) -> (Vec<String>, Vec<String>) {
+        (Vec::new(), Vec::new())
+    }
+
+    /// Module id for a `/`-separated relative path.
+    fn module_id(&self, path: &str) -> String {
+        crate::helpers::generic_module_id(path)
+    }
+
+    /// Entrypoint summary (`__main__` scripts, ..), consulted by
+    /// `intent::guess_summary` with the other entrypoint checks, ahead of the
+    /// test and path heuristics. Both arguments are lowercased.
+    fn entry_hint(&self, _path: &str, _snippet: &str) -> Option<String> {
+        None
+    }
+
+    /// Language-specific summary, consulted by `intent::guess_summary` after
+    /// the path-based heuristics. Both arguments are lowercased.
+    fn summary_hint(&self, _path: &str, _snippet: &str) -> Option<String> {
+        None
+    }
+
+    /// Info string for Markdown code fences.
+    fn fence(&self) -> &str {
+        self.name()
+    }
+
+    /// v3 anchors for `src` at `file`, or `None` when the language has no
+    /// extractor. Build anchors with `index_v3::span_anchor` and finish with
+    /// `index_v3::assign_ids`.
+    fn anchors(&self, _src: &str, _file: &str) -> Option<Result<Vec<Anchor>>> {
+        None
+    }
+}
+
+/* ================================ Registry ================================ */
+
+type Shared = Arc<dyn LanguageAnalyzer>;
+
+fn registry() -> &'static RwLock<Vec<Shared>> {
+    static REGISTRY: OnceLock<RwLock<Vec<Shared>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| RwLock::new(builtins()))
+}
+
+/// Add an analyzer. It takes precedence over everything registered before it,
+/// built-ins included.
+pub fn register(analyzer: impl LanguageAnalyzer + 'static) {
+    registry().write().unwrap_or_else(std::sync::PoisonError::into_inner).insert(0, Arc::new(analyzer));
+}
+
+/// Remove the analyzer most recently registered under `name`, built-ins
+/// included. Returns whether one was found.
+pub fn unregister(name: &str) -> bool {
+    let mut reg = registry().write().unwrap_or_else(std::sync::PoisonError::into_inner);
+    let found = reg.iter().position(|a| a.name() == name);
+    found.map(|i| reg.remove(i)).is_some()
+}
+
+/// Every analyzer, in lookup order.
+pub fn analyzers() -> Vec<Shared> {
+    registry().read().unwrap_or_else(std::sync::PoisonError::into_inner).clone()
+}
+
+/// Analyzer for a language label or alias (case-insensitive).
+pub fn analyzer(lang: &str) -> Option<Shared> {
+    let lang = lang.trim();
+    if lang.is_empty() {
+        return None;
+    }
+    let reg = registry().read().unwrap_or_else(std::sync::PoisonError::into_inner);
+    reg.iter()
+        .find(|a| a.name().eq_ignore_ascii_case(lang) || a.aliases().iter().any(|x| x.eq_ignore_ascii_case(lang)))
+        .cloned()
+}
+
+/// Analyzer that claims `path`.
+pub fn analyzer_for_path(path: &Path) -> Option<Shared> {
+    let reg = registry().read().unwrap_or_else(std::sync::PoisonError::into_inner);
+    reg.iter().find(|a| a.detect(path)).cloned()
+}
+
+/* ================================ Built-ins =============================== */
+
+#[derive(Clone, Copy)]
+enum Style {
+    Rust,
+    Python,
+    Script,
+    /// tsx/jsx: script anchors, generic snippet heuristics and module ids.
+    Jsx,
+    Go,
+    Config,
+    Markdown,
+    Plain,
+}
+
+/// A built-in language: labels, file patterns and which heuristics it uses.
+struct Builtin {
+    name: &'static str,
+    aliases: &'static [&'static str],
+    /// Matched against the lowercased file name as `.<ext>` suffixes.
+    exts: &'static [&'static str],
+    file_names: &'static [&'static str],
+    fence: &'static str,
+    style: Style,
+}
+
+#[rustfmt::skip]
+fn builtins() -> Vec<Shared> {
+    use Style::{Config, Go, Jsx, Markdown, Plain, Python, Rust, Script};
+    let b = |name, aliases, exts, file_names, fence, style| -> Shared {
+        Arc::new(Builtin { name, aliases, exts, file_names, fence, style })
+    };
+    vec![
+        b("rust",     &["rs"],                  &["rs", "rs.in"],       &[],           "rust",       Rust),
+        b("make",     &[],                      &["mk"],                &["makefile"], "make",       Plain),
+        b("toml",     &[],                      &["toml"],              &[],           "toml",       Config),
+        b("json",     &["jsonl"],               &["json", "jsonl"],     &[],           "json",       Config),
+        b("yaml",     &["yml"],                 &["yml", "yaml"],       &[],           "yaml",       Config),
+        b("md",       &["markdown"],            &["md"],                &[],           "md",         Markdown),
+        b("sh",       &["bash", "zsh"],         &["sh"],                &[],           "bash",       Plain),
+        b("python",   &["py"],                  &["py"],                &[],           "python",     Python),
+        b("ts",       &["typescript"],          &["ts"],                &[],           "ts",         Script),
+        b("tsx",      &[],                      &["tsx"],               &[],           "tsx",        Jsx),
+        b("js",       &["javascript"],          &["js"],                &[],           "javascript", Script),
+        b("jsx",      &[],                      &["jsx"],               &[],           "jsx",        Jsx),
+        b("go",       &["golang"],              &["go"],                &[],           "go",         Go),
+        b("java",     &[],                      &["java"],              &[],           "java",       Plain),
+        b("kotlin",   &[],                      &["kt"],                &[],           "kotlin",     Plain),
+        b("cpp",      &["cc", "cxx", "hpp"],    &["cpp", "cc", "cxx"],  &[],           "cpp",        Plain),
+        b("c",        &["h"],                   &["c"],                 &[],           "c",          Plain),
+        b("c_header", &[],                      &["h", "hpp"],          &[],           "c_header",   Plain),
+        b("sql",      &[],                      &["sql"],               &[],           "sql",        Plain),
+    ]
+}
+
+impl LanguageAnalyzer for Builtin {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn aliases(&self) -> &[&'static str] {
+        self.aliases
+    }
+
+    fn detect(&self, path: &Path) -> bool {
+        let fname = path.file_name().and_then(|s| s.to_str()).unwrap_or("").to_ascii_lowercase();
+        self.file_names.contains(&fname.as_str())
+            || self.exts.iter().any(|e| fname.len() > e.len() + 1 && fname.ends_with(e) && fname[..fname.len() - e.len()].ends_with('.'))
+    }
+
+    fn score_line(&self, line: &str) -> u8 {
+        use crate::snippet as s;
+        let ll = line.to_ascii_lowercase();
+        match self.style {
+            Style::Rust => s::score_rust(line, &ll),
+            Style::Python => s::score_python(line, &ll),
+            Style::Script => s::score_js_ts(line, &ll),
+            Style::Go => s::score_go(line, &ll),
+            Style::Config => s::score_config(line, &ll),
+            Style::Markdown => s::score_md(line, &ll),
+            Style::Jsx | Style::Plain => s::score_generic(line, &ll),
+        }
+    }
+
+    fn leading_doc(&self, head: &str) -> Option<Vec<String>> {
+        use crate::snippet as s;
+        match self.style {
+            Style::Rust => s::leading_rust_docs(head),
+            Style::Python => s::leading_python_docs(head),
+            Style::Script => s::leading_js_docs(head),
+            Style::Markdown => s::leading_md_head(head),
+            Style::Jsx | Style::Go | Style::Config | Style::Plain => s::leading_generic_head(head),
+        }
+    }
+
+    fn skim_symbols(&self, snippet: &str) -> (Vec<String>, Vec<String>) {
+        use crate::helpers as h;
+        match self.style {
+            Style::Rust => h::skim_rust(snippet),
+            Style::Python => h::skim_python(snippet),
+            Style::Script => h::skim_js_ts(snippet),
+            _ => (Vec::new(), Vec::new()),
+        }
+    }
+
+    fn module_id(&self, path: &str) -> String {
+        use crate::helpers as h;
+        match self.style {
+            Style::Rust => h::rust_module_id(path),
+            Style::Python => h::python_module_id(path),
+            Style::Script => h::web_module_id(path),
+            _ => h::generic_module_id(path),
+        }
+    }
+
+    fn entry_hint(&self, _path: &str, snippet: &str) -> Option<String> {
+        match self.style {
+            Style::Python => crate::intent::python_entry_hint(snippet),
+            _ => None,
+        }
+    }
+
+    fn summary_hint(&self, path: &str, _snippet: &str) -> Option<String> {
+        match self.style {
+            Style::Rust => crate::intent::rust_hint(path),
+            _ => None,
+        }
+    }
+
+    fn fence(&self) -> &str {
+        self.fence
+    }
+
+    fn anchors(&self, src: &str, file: &str) -> Option<Result<Vec<Anchor>>> {
+        match self.style {
+            Style::Rust => Some(crate::index_v3::extract_rust_anchors(src, file)),
+            Style::Python => Some(Ok(crate::lang_anchors::python(src, file))),
+            Style::Script | Style::Jsx => Some(Ok(crate::lang_anchors::script(src, file))),
+            Style::Go => Some(Ok(crate::lang_anchors::go(src, file))),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index_v3::{assign_ids, span_anchor, Schema};
+
+    #[test]
+    fn builtins_resolve_by_path_and_alias() {
+        let name = |p: &str| analyzer_for_path(Path::new(p)).map(|a| a.name());
+        assert_eq!(name("src/lib.rs"), Some("rust"));
+        assert_eq!(name("build/Makefile"), Some("make"));
+        assert_eq!(name("web/app.d.ts"), Some("ts"));
+        assert_eq!(name("include/x.hpp"), Some("c_header"));
+        assert_eq!(name("notes.txt"), None);
+        assert_eq!(analyzer("TypeScript").map(|a| a.name()), Some("ts"));
+        assert_eq!(analyzer("golang").map(|a| a.fence().to_string()).as_deref(), Some("go"));
+        assert!(analyzer("").is_none());
+    }
+
+    #[test]
+    fn tsx_and_jsx_keep_generic_heuristics_but_get_script_anchors() {
+        let src = "/** Button */\nexport function Button() {\n  return <b/>;\n}\nimport x from 'y';\n";
+        for lang in ["tsx", "jsx"] {
+            let a = analyzer(lang).unwrap();
+            let ts = analyzer("ts").unwrap();
+            assert_eq!(a.score_line("export function Button() {"), 0, "{lang}");
+            assert_eq!(ts.score_line("export function Button() {"), 8);
+            assert_eq!(a.skim_symbols(src), (Vec::new(), Vec::new()), "{lang}");
+            assert_eq!(a.module_id("web/app/button.tsx"), crate::helpers::generic_module_id("web/app/button.tsx"));
+            assert_eq!(a.fence(), lang);
+            let anchors = crate::index_v3::anchor_slices(lang, src, "web/button.x").unwrap();
+            assert_eq!(anchors.len(), 1, "{lang}");
+        }
+    }
+
+    struct Zig;
+
+    impl LanguageAnalyzer for Zig {
+        fn name(&self) -> &'static str {
+            "zig-test"
+        }
+        fn detect(&self, path: &Path) -> bool {
+            path.extension().is_some_and(|e| e == "zigtest")
+        }
+        fn score_line(&self, line: &str) -> u8 {
+            if line.starts_with("pub fn ") { 9 } else { 0 }
+        }
+        fn module_id(&self, path: &str) -> String {
+            path.trim_end_matches(".zigtest").replace('/', ".")
+        }
+        fn fence(&self) -> &str {
+            "zig"
+        }
+        fn anchors(&self, src: &str, _file: &str) -> Option<Result<Vec<Anchor>>> {
+            let end = src.find('}').map_or(src.len(), |i| i + 1);
+            let mut out: Vec<Anchor> =
+                span_anchor(src, "fn", "main", "main".into(), true, "pub fn main() void".into(), (0, end), Schema::default()).into_iter().collect();
+            assign_ids("", &mut out);
+            Some(Ok(out))
+        }
+    }
+
+    /// Drops the test analyzer again, even when an assertion fails, so it
+    /// never outlives the test in the shared registry.
+    struct Unregister(&'static str);
+
+    impl Drop for Unregister {
+        fn drop(&mut self) {
+            unregister(self.0);
+        }
+    }
+
+    #[test]
+    fn registered_analyzers_drive_every_stage() {
+        register(Zig);
+        let _guard = Unregister("zig-test");
+        let src = "pub fn main() void {}\nconst x = 1;\n";
+        assert_eq!(crate::util::ext_to_lang(Path::new("src/main.zigtest")), "zig-test");
+        assert_eq!(crate::helpers::infer_module_id("src/app/main.zigtest", "zig-test"), "src.app.main");
+        assert_eq!(crate::snippet::extract_relevant_snippet(src, "zig-test"), "pub fn main() void {}\nconst x = 1;");
+        assert_eq!(crate::chunker::fence_lang("ZIG-TEST"), "zig");
+        let anchors = crate::index_v3::anchor_slices("zig-test", src, "src/main.zigtest").unwrap();
+        assert_eq!(anchors.len(), 1);
+        assert_eq!(analyzers()[0].name(), "zig-test");
+
+        assert!(unregister("zig-test"));
+        assert!(!unregister("zig-test"));
+        assert_eq!(crate::util::ext_to_lang(Path::new("src/main.zigtest")), "txt");
+    }
+}
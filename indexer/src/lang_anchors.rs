@@ -0,0 +1,747 @@
+// indexer/src/lang_anchors.rs
+//! Line-based v3 anchor extractors for Python, TypeScript/JavaScript and Go.
+//!
+//! - No parser: strings and comments are masked first (same byte offsets,
+//!   delimiters kept), so brackets, keywords and `:` are only seen in code.
+//! - Python: `def` / `async def` / `class` by logical line and indentation;
+//!   methods and nested classes get `Class.name`, defs inside functions are skipped.
+//! - TS/JS: `function`, `class` (and its methods), `interface`, `type`, `enum`
+//!   and exported `const`/`let`/`var`, by brace matching.
+//! - Go: `func`, methods (`Type.Name`) and `type` specs, grouped or not.
+//! - Ranges start at decorators and doc comments (docstring, `/** */`, `//`),
+//!   like Rust anchors start at `///` and attributes.
+
+use crate::index_v3::{assign_ids, span_anchor, Anchor, Schema};
+use regex::Regex;
+use std::sync::OnceLock;
+
+/* ================================ Masking ================================ */
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Syntax {
+    Python,
+    Script,
+    Go,
+}
+
+/// `src` with string and comment bodies blanked (newlines kept), plus for
+/// each line whether it starts inside a multi-line string or comment.
+fn mask(src: &str, syntax: Syntax) -> (Vec<u8>, Vec<bool>) {
+    let b = src.as_bytes();
+    let mut out = b.to_vec();
+    let mut inside_at_line = vec![false];
+    let mut i = 0;
+    while i < b.len() {
+        let c = b[i];
+        let next = b.get(i + 1).copied();
+        // (opening delimiter length, end of the token, closing delimiter length)
+        let (open, end, close) = match c {
+            b'\n' => {
+                inside_at_line.push(false);
+                i += 1;
+                continue;
+            }
+            b'#' if syntax == Syntax::Python => (1, memchr::memchr(b'\n', &b[i..]).map_or(b.len(), |n| i + n), 0),
+            b'/' if syntax != Syntax::Python && next == Some(b'/') => (2, memchr::memchr(b'\n', &b[i..]).map_or(b.len(), |n| i + n), 0),
+            b'/' if syntax != Syntax::Python && next == Some(b'*') => match find(b, i + 2, b"*/") {
+                Some(n) => (2, n + 2, 2),
+                None => (2, b.len(), 0),
+            },
+            b'"' | b'\'' if syntax == Syntax::Python && b[i..].starts_with(&[c, c, c]) => match find_unescaped(b, i + 3, &[c, c, c]) {
+                Some(n) => (3, n + 3, 3),
+                None => (3, b.len(), 0),
+            },
+            b'`' if syntax == Syntax::Go => match memchr::memchr(b'`', &b[i + 1..]) {
+                Some(n) => (1, i + n + 2, 1),
+                None => (1, b.len(), 0),
+            },
+            b'`' if syntax == Syntax::Script => template_end(b, i + 1),
+            b'"' | b'\'' => line_string_end(b, i + 1, c),
+            _ => {
+                i += 1;
+                continue;
+            }
+        };
+        // Delimiters stay, so a string-only line is not blank.
+        for x in &mut out[i + open..end - close] {
+            if *x != b'\n' {
+                *x = b' ';
+            }
+        }
+        inside_at_line.extend(b[i..end].iter().filter(|&&x| x == b'\n').map(|_| true));
+        i = end;
+    }
+    (out, inside_at_line)
+}
+
+fn find(b: &[u8], from: usize, pat: &[u8]) -> Option<usize> {
+    b.get(from..)?.windows(pat.len()).position(|w| w == pat).map(|n| from + n)
+}
+
+fn find_unescaped(b: &[u8], mut from: usize, pat: &[u8]) -> Option<usize> {
+    while from < b.len() {
+        if b[from] == b'\\' {
+            from += 2;
+        } else if b[from..].starts_with(pat) {
+            return Some(from);
+        } else {
+            from += 1;
+        }
+    }
+    None
+}
+
+/// One-line string opened before `i`: `(1, end, 1)`, or `(1, line end, 0)`
+/// when it is not closed on its line.
+fn line_string_end(b: &[u8], mut i: usize, quote: u8) -> (usize, usize, usize) {
+    while i < b.len() && b[i] != b'\n' {
+        match b[i] {
+            b'\\' => i += 2,
+            c if c == quote => return (1, i + 1, 1),
+            _ => i += 1,
+        }
+    }
+    (1, i.min(b.len()), 0)
+}
+
+/// Template literal opened before `i`, skipping `${ .. }` with nested braces.
+fn template_end(b: &[u8], mut i: usize) -> (usize, usize, usize) {
+    let mut depth = 0usize;
+    while i < b.len() {
+        match b[i] {
+            b'\\' => i += 1,
+            b'`' if depth == 0 => return (1, i + 1, 1),
+            b'$' if b.get(i + 1) == Some(&b'{') => {
+                depth += 1;
+                i += 1;
+            }
+            b'{' if depth > 0 => depth += 1,
+            b'}' if depth > 0 => depth -= 1,
+            _ => {}
+        }
+        i += 1;
+    }
+    (1, b.len(), 0)
+}
+
+/// Byte offset of each line start.
+fn line_starts(src: &str) -> Vec<usize> {
+    std::iter::once(0).chain(memchr::memchr_iter(b'\n', src.as_bytes()).map(|i| i + 1)).collect()
+}
+
+/// End of line `l` (before `\n` / `\r\n`).
+fn line_end(src: &str, starts: &[usize], l: usize) -> usize {
+    let end = starts.get(l + 1).map_or(src.len(), |&s| s - 1);
+    if end > starts[l] && src.as_bytes()[end - 1] == b'\r' { end - 1 } else { end }
+}
+
+/// `src[from..to]`, or "" when the offsets are off a char boundary: the
+/// scanners work on bytes, so slicing text out of `src` never panics.
+fn part(src: &str, from: usize, to: usize) -> &str {
+    src.get(from..to).unwrap_or_default()
+}
+
+fn squash(s: &str) -> String {
+    s.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Offset just past the bracket matching the one at `open` (masked text).
+fn match_bracket(m: &[u8], open: usize) -> usize {
+    let mut depth = 0usize;
+    for (i, &c) in m.iter().enumerate().skip(open) {
+        match c {
+            b'{' | b'(' | b'[' => depth += 1,
+            b'}' | b')' | b']' => {
+                depth = depth.saturating_sub(1);
+                if depth == 0 {
+                    return i + 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    m.len()
+}
+
+/// `(root, rel)` split at the last `src/` segment, as for Rust module paths.
+fn split_root(file: &str) -> (String, String) {
+    let p = file.replace('\\', "/");
+    match p.rfind("src/") {
+        Some(i) if i == 0 || p[..i].ends_with('/') => (p[..i].trim_end_matches('/').to_string(), p[i + 4..].to_string()),
+        _ => (String::new(), p),
+    }
+}
+
+/* ================================ Python ================================ */
+
+struct Logical {
+    first: usize,
+    last: usize,
+    indent: usize,
+    /// Masked text of the logical line, lines joined.
+    text: String,
+}
+
+fn python_lines(src: &str, m: &[u8], inside: &[bool], starts: &[usize]) -> Vec<Logical> {
+    let mut out: Vec<Logical> = Vec::new();
+    let mut depth = 0i32;
+    let mut cont = false;
+    for l in 0..starts.len() {
+        let text = String::from_utf8_lossy(&m[starts[l]..line_end(src, starts, l)]).into_owned();
+        let joins = depth > 0 || cont || inside.get(l).copied().unwrap_or(false);
+        for c in text.bytes() {
+            match c {
+                b'(' | b'[' | b'{' => depth += 1,
+                b')' | b']' | b'}' => depth = (depth - 1).max(0),
+                _ => {}
+            }
+        }
+        cont = text.trim_end().ends_with('\\');
+        match out.last_mut() {
+            Some(prev) if joins => {
+                prev.last = l;
+                prev.text.push('\n');
+                prev.text.push_str(&text);
+            }
+            _ => {
+                let body = text.trim_start();
+                if body.is_empty() || body.starts_with('#') {
+                    continue;
+                }
+                let indent = text.len() - body.len();
+                out.push(Logical { first: l, last: l, indent, text });
+            }
+        }
+    }
+    out
+}
+
+fn python_header() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^\s*(?:async\s+)?(def|class)\s+([^\W\d]\w*)").unwrap())
+}
+
+/// Anchors for a Python source at `file` (`pkg/mod.py` -> `pkg.mod.Class.method`).
+pub(crate) fn python(src: &str, file: &str) -> Vec<Anchor> {
+    let (m, inside) = mask(src, Syntax::Python);
+    let starts = line_starts(src);
+    let lines = python_lines(src, &m, &inside, &starts);
+    let (root, rel) = split_root(file);
+    let rel = rel.trim_end_matches(".pyi").trim_end_matches(".py");
+    let module: Vec<&str> = rel.split('/').filter(|s| !s.is_empty() && *s != "__init__").collect();
+
+    // (indent, name, is_class) of the enclosing defs.
+    let mut scope: Vec<(usize, String, bool)> = Vec::new();
+    let mut out = Vec::new();
+    for (k, l) in lines.iter().enumerate() {
+        while scope.last().is_some_and(|s| s.0 >= l.indent) {
+            scope.pop();
+        }
+        let Some(c) = python_header().captures(&l.text) else { continue };
+        let (kw, name) = (&c[1], c[2].to_string());
+        let in_fn = scope.iter().any(|s| !s.2);
+        scope.push((l.indent, name.clone(), kw == "class"));
+        if in_fn {
+            continue;
+        }
+        // Decorators directly above, at the same indent.
+        let mut first = k;
+        while first > 0 && lines[first - 1].indent == l.indent && lines[first - 1].text.trim_start().starts_with('@') {
+            first -= 1;
+        }
+        let body: Vec<&Logical> = lines[k + 1..].iter().take_while(|b| b.indent > l.indent).collect();
+        let last = body.last().map_or(l.last, |b| b.last);
+        let start = starts[lines[first].first] + lines[first].indent;
+        let end = line_end(src, &starts, last);
+
+        let head_start = starts[l.first] + l.indent;
+        let colon = header_colon(&m[head_start..line_end(src, &starts, l.last)]).map_or(line_end(src, &starts, l.last), |n| head_start + n);
+        let signature = squash(part(src, head_start, colon));
+        let attrs: Vec<String> = lines[first..k].iter().map(|d| squash(part(src, starts[d.first], line_end(src, &starts, d.last)))).collect();
+        let doc = body.first().and_then(|b| docstring(part(src, starts[b.first], line_end(src, &starts, b.last))));
+        let methods = (kw == "class").then(|| {
+            body.iter()
+                .filter(|b| python_header().captures(&b.text).is_some_and(|c| &c[1] == "def") && b.indent == body[0].indent)
+                .map(|b| {
+                    let s = starts[b.first] + b.indent;
+                    let colon = header_colon(&m[s..line_end(src, &starts, b.last)]).map_or(line_end(src, &starts, b.last), |n| s + n);
+                    squash(part(src, s, colon))
+                })
+                .collect::<Vec<_>>()
+        });
+        let path = module.iter().map(ToString::to_string).chain(scope.iter().map(|s| s.1.clone())).collect::<Vec<_>>().join(".");
+        let schema = Schema {
+            doc,
+            attrs: (!attrs.is_empty()).then_some(attrs),
+            methods: methods.filter(|m| !m.is_empty()),
+            ..Default::default()
+        };
+        let kind = if kw == "class" { "class" } else { "fn" };
+        let public = !name.starts_with('_') || (name.starts_with("__") && name.ends_with("__"));
+        out.extend(span_anchor(src, kind, &name, path, public, signature, (start, end), schema));
+    }
+    assign_ids(&root, &mut out);
+    out
+}
+
+/// Offset of the `:` ending a `def` / `class` header (first one outside brackets).
+fn header_colon(m: &[u8]) -> Option<usize> {
+    let mut depth = 0i32;
+    for (i, &c) in m.iter().enumerate() {
+        match c {
+            b'(' | b'[' | b'{' => depth += 1,
+            b')' | b']' | b'}' => depth -= 1,
+            b':' if depth == 0 => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Text of a docstring statement (`"""..."""`, `'...'`, with `r`/`u` prefix).
+fn docstring(stmt: &str) -> Option<String> {
+    let s = stmt.trim().trim_start_matches(['r', 'R', 'u', 'U']);
+    let q = ["\"\"\"", "'''", "\"", "'"].into_iter().find(|q| s.starts_with(q))?;
+    let inner = s[q.len()..].strip_suffix(q)?;
+    let lines: Vec<&str> = inner.lines().collect();
+    let indent = lines.iter().skip(1).filter(|l| !l.trim().is_empty()).map(|l| l.len() - l.trim_start().len()).min().unwrap_or(0);
+    let text = lines
+        .iter()
+        .enumerate()
+        .map(|(i, l)| if i == 0 { l.trim() } else { l.get(indent..).unwrap_or("").trim_end() })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let text = text.trim().to_string();
+    (!text.is_empty()).then_some(text)
+}
+
+/* ============================ Brace languages ============================ */
+
+/// Leading doc / decorator lines above line `l`: `(first line, doc, attrs)`.
+/// Script takes one `/** .. */` block and `@decorators`; Go takes `//` lines
+/// (`//go:` directives become attrs).
+fn lead_in(src: &str, starts: &[usize], l: usize, syntax: Syntax) -> (usize, Option<String>, Vec<String>) {
+    let text = |k: usize| part(src, starts[k], line_end(src, starts, k)).trim();
+    let (mut first, mut doc, mut attrs) = (l, Vec::new(), Vec::new());
+    while first > 0 {
+        let t = text(first - 1);
+        if syntax == Syntax::Go && t.starts_with("//") {
+            match t.strip_prefix("//go:") {
+                Some(_) => attrs.insert(0, t.to_string()),
+                None => doc.insert(0, t.trim_start_matches('/').strip_prefix(' ').unwrap_or(t.trim_start_matches('/')).to_string()),
+            }
+        } else if syntax == Syntax::Script && t.starts_with('@') {
+            attrs.insert(0, squash(t));
+        } else if syntax == Syntax::Script && t.ends_with("*/") && doc.is_empty() {
+            let mut open = first - 1;
+            while open > 0 && !text(open).starts_with("/*") {
+                open -= 1;
+            }
+            if !text(open).starts_with("/**") {
+                break;
+            }
+            doc = (open..first).map(|k| text(k).trim_start_matches("/**").trim_end_matches("*/").trim_start_matches('*').trim().to_string()).collect();
+            first = open;
+            continue;
+        } else {
+            break;
+        }
+        first -= 1;
+    }
+    let doc = doc.join("\n").trim().to_string();
+    (first, (!doc.is_empty()).then_some(doc), attrs)
+}
+
+/// End of the statement starting at `from`: after a `;` outside brackets, or
+/// at a line end outside brackets that does not continue on the next line.
+fn statement_end(m: &[u8], from: usize) -> usize {
+    let mut depth = 0i32;
+    let mut i = from;
+    while i < m.len() {
+        match m[i] {
+            b'(' | b'[' | b'{' => depth += 1,
+            b')' | b']' | b'}' => depth -= 1,
+            b';' if depth <= 0 => return i + 1,
+            b'\n' if depth <= 0 => {
+                let line = String::from_utf8_lossy(&m[from..i]);
+                let tail = line.trim_end();
+                let next = String::from_utf8_lossy(&m[i + 1..]);
+                let next = next.trim_start();
+                let open_tail = ["=", "|", "&", ",", "(", "<", "=>", "?", ":", "+", "-"].iter().any(|t| tail.ends_with(t));
+                let open_next = ["|", "&", ".", "?", ":", "=", ")", "]", "}"].iter().any(|t| next.starts_with(t));
+                if !(open_tail || open_next) {
+                    return i;
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    m.len()
+}
+
+/// End of a declaration whose body is the first `{` after `from` outside
+/// brackets and generics that is not a type literal (`: {`, `| {`,
+/// `interface{`, ..); `;` first means no body.
+fn body_end(m: &[u8], from: usize) -> (usize, usize) {
+    let mut depth = 0i32;
+    let mut i = from;
+    while i < m.len() {
+        match m[i] {
+            b'(' | b'[' => depth += 1,
+            b'<' if m.get(i + 1) != Some(&b'-') => depth += 1,
+            b')' | b']' => depth -= 1,
+            b'>' if i > 0 && m[i - 1] != b'=' => depth -= 1,
+            b';' if depth <= 0 => return (i, i + 1),
+            b'{' if depth <= 0 => {
+                let before = String::from_utf8_lossy(&m[from..i]);
+                let before = before.trim_end();
+                let literal = [":", "|", "&", "<", ",", "=", "interface", "struct"].iter().any(|t| before.ends_with(t));
+                if !literal {
+                    return (i, match_bracket(m, i));
+                }
+                i = match_bracket(m, i);
+                continue;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    (m.len(), m.len())
+}
+
+fn script_decl() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(
+            r"^(export\s+(?:default\s+)?)?(?:declare\s+)?(?:abstract\s+)?(?:async\s+)?(function\s*\*?|(?:class|interface|type|const\s+enum|enum|const|let|var)\b)\s*([A-Za-z_$][\w$]*)?",
+        )
+        .unwrap()
+    })
+}
+
+fn script_member() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"^((?:(?:public|private|protected|static|async|readonly|abstract|override|declare|get|set)\s+)*)\*?\s*(#?[A-Za-z_$][\w$]*)\s*(?:<[^(]*>)?\s*\(")
+            .unwrap()
+    })
+}
+
+/// Anchors for a TypeScript / JavaScript source (`src/app/x.ts` -> `src/app/x#Class.method`).
+pub(crate) fn script(src: &str, file: &str) -> Vec<Anchor> {
+    let (m, _) = mask(src, Syntax::Script);
+    let starts = line_starts(src);
+    let (root, rel) = split_root(file);
+    let module = rel.rsplit_once('.').map_or(rel.as_str(), |(stem, _)| stem).to_string();
+    let depth = Depth::new(&m, &starts, false);
+    let mut out = Vec::new();
+    let mut l = 0;
+    while l < starts.len() {
+        let (s, e) = (starts[l], line_end(src, &starts, l));
+        let text = String::from_utf8_lossy(&m[s..e]);
+        let body = text.trim_start();
+        let at = s + (text.len() - body.len());
+        if depth.at(at) != 0 {
+            l += 1;
+            continue;
+        }
+        let Some(c) = script_decl().captures(body) else {
+            l += 1;
+            continue;
+        };
+        let exported = c.get(1).is_some();
+        let kw = c[2].split_whitespace().last().unwrap_or_default().trim_start_matches("function").trim_end_matches('*');
+        let kw = if kw.is_empty() { "function" } else { kw }.to_string();
+        let default = c.get(1).is_some_and(|e| e.as_str().contains("default"));
+        if (matches!(kw.as_str(), "const" | "let" | "var") && !exported) || (c.get(3).is_none() && !default) {
+            l += 1;
+            continue;
+        }
+        let name = c.get(3).map_or("default", |n| n.as_str()).to_string();
+        let (head_end, end) = match kw.as_str() {
+            "type" | "const" | "let" | "var" => {
+                let end = statement_end(&m, at);
+                (end, end)
+            }
+            _ => body_end(&m, at),
+        };
+        let kind = match kw.as_str() {
+            "function" => "fn",
+            "let" | "var" => "const",
+            k => k,
+        };
+        let (first, doc, attrs) = lead_in(src, &starts, l, Syntax::Script);
+        let path = format!("{module}#{name}");
+        let members = if kind == "class" && head_end < end {
+            script_members(src, &depth, (head_end + 1, end - 1), &format!("{path}."))
+        } else {
+            Vec::new()
+        };
+        let methods: Vec<String> = members.iter().filter_map(|a| a.signature.clone()).collect();
+        let signature = squash(part(src, at, head_end.min(end)));
+        let start = if first < l { indent_at(src, &starts, first) } else { at };
+        let schema = Schema {
+            doc,
+            attrs: (!attrs.is_empty()).then_some(attrs),
+            methods: (!methods.is_empty()).then_some(methods),
+            ..Default::default()
+        };
+        let end = trim_end(src, end);
+        out.extend(span_anchor(src, kind, &name, path, exported, signature, (start, end), schema));
+        out.extend(members);
+        l = line_of(&starts, end.max(at)) + 1;
+    }
+    assign_ids(&root, &mut out);
+    out
+}
+
+/// Methods of a class body `m[from..to]`.
+fn script_members(src: &str, depth: &Depth, (from, to): (usize, usize), prefix: &str) -> Vec<Anchor> {
+    let (m, starts) = (depth.m, depth.starts);
+    let base = depth.at(from);
+    let mut out = Vec::new();
+    let mut l = line_of(starts, from);
+    while l < starts.len() && starts[l] < to {
+        let (s, e) = (starts[l].max(from), line_end(src, starts, l).min(to));
+        let text = String::from_utf8_lossy(&m[s..e]);
+        let body = text.trim_start();
+        let at = s + (text.len() - body.len());
+        let Some(c) = script_member().captures(body).filter(|_| depth.at(at) == base) else {
+            l += 1;
+            continue;
+        };
+        let name = c[2].to_string();
+        if matches!(name.as_str(), "if" | "for" | "while" | "switch" | "catch" | "return" | "function") {
+            l += 1;
+            continue;
+        }
+        let public = !(c[1].contains("private") || c[1].contains("protected") || name.starts_with('#'));
+        let (head_end, end) = body_end(m, at);
+        let (first, doc, attrs) = lead_in(src, starts, l, Syntax::Script);
+        let start = if first < l { indent_at(src, starts, first) } else { at };
+        let schema = Schema { doc, attrs: (!attrs.is_empty()).then_some(attrs), ..Default::default() };
+        let end = trim_end(src, end.min(to));
+        out.extend(span_anchor(src, "fn", &name, format!("{prefix}{name}"), public, squash(part(src, at, head_end.min(end))), (start, end), schema));
+        l = line_of(starts, end.max(at)) + 1;
+    }
+    out
+}
+
+/// Bracket depth of masked text, precomputed per line: `{}` only, or `{}`
+/// and `()` (Go's `type ( .. )` groups).
+struct Depth<'a> {
+    m: &'a [u8],
+    starts: &'a [usize],
+    at_line: Vec<usize>,
+    parens: bool,
+}
+
+impl<'a> Depth<'a> {
+    fn new(m: &'a [u8], starts: &'a [usize], parens: bool) -> Self {
+        let mut d = Depth { m, starts, at_line: Vec::with_capacity(starts.len()), parens };
+        let mut depth = 0;
+        for l in 0..starts.len() {
+            d.at_line.push(depth);
+            let end = starts.get(l + 1).copied().unwrap_or(m.len());
+            depth = d.step(depth, starts[l], end);
+        }
+        d
+    }
+
+    fn step(&self, mut depth: usize, from: usize, to: usize) -> usize {
+        for &c in &self.m[from..to] {
+            match c {
+                b'{' => depth += 1,
+                b'(' if self.parens => depth += 1,
+                b'}' => depth = depth.saturating_sub(1),
+                b')' if self.parens => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+        }
+        depth
+    }
+
+    fn at(&self, offset: usize) -> usize {
+        let l = line_of(self.starts, offset);
+        self.step(self.at_line[l], self.starts[l], offset)
+    }
+}
+
+/// First non-blank byte of line `l`.
+fn indent_at(src: &str, starts: &[usize], l: usize) -> usize {
+    let line = part(src, starts[l], line_end(src, starts, l));
+    starts[l] + (line.len() - line.trim_start().len())
+}
+
+fn line_of(starts: &[usize], offset: usize) -> usize {
+    starts.partition_point(|&s| s <= offset).saturating_sub(1)
+}
+
+/// Drop trailing whitespace (the newline a statement end may sit on).
+fn trim_end(src: &str, end: usize) -> usize {
+    part(src, 0, end.min(src.len())).trim_end().len()
+}
+
+fn go_func() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^func\s*(?:\(\s*(?:\w+\s+)?\*?\s*(\w+)(?:\[[^\]]*\])?\s*\))?\s*(\w+)").unwrap())
+}
+
+fn go_type() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^(?:type\s+)?(\w+)(?:\[[^\]]*\])?\s*=?\s*(struct|interface)?").unwrap())
+}
+
+/// Anchors for a Go source (`internal/store/db.go` -> `internal/store.Store.Get`).
+pub(crate) fn go(src: &str, file: &str) -> Vec<Anchor> {
+    let (m, _) = mask(src, Syntax::Go);
+    let starts = line_starts(src);
+    let (root, rel) = split_root(file);
+    let module = match rel.rsplit_once('/') {
+        Some((dir, _)) => dir.to_string(),
+        None => src.lines().find_map(|l| l.strip_prefix("package ")).unwrap_or("main").trim().to_string(),
+    };
+    let depth = Depth::new(&m, &starts, true);
+    let mut out = Vec::new();
+    let mut l = 0;
+    // Inside `type ( .. )`: offset of the closing paren.
+    let mut group_end: Option<usize> = None;
+    while l < starts.len() {
+        let (s, e) = (starts[l], line_end(src, &starts, l));
+        let text = String::from_utf8_lossy(&m[s..e]);
+        let body = text.trim_start();
+        let at = s + (text.len() - body.len());
+        if group_end.is_some_and(|g| at >= g) {
+            group_end = None;
+        }
+        if depth.at(at) != usize::from(group_end.is_some()) || body.is_empty() {
+            l += 1;
+            continue;
+        }
+        let (kind, name, path, head_end, end) = if let Some(c) = go_func().captures(body).filter(|_| group_end.is_none()) {
+            let recv = c.get(1).map(|r| r.as_str().to_string());
+            let name = c[2].to_string();
+            let (head_end, end) = body_end(&m, at);
+            let path = match &recv {
+                Some(r) => format!("{module}.{r}.{name}"),
+                None => format!("{module}.{name}"),
+            };
+            ("fn", name, path, head_end, end)
+        } else if group_end.is_none() && body.starts_with("type") && body[4..].trim_start().starts_with('(') {
+            let open = at + body.find('(').unwrap_or(0);
+            group_end = Some(match_bracket(&m, open));
+            l += 1;
+            continue;
+        } else if let Some(c) = go_type().captures(body).filter(|_| group_end.is_some() || body.starts_with("type ")) {
+            let name = c[1].to_string();
+            let kind = c.get(2).map_or("type", |k| k.as_str());
+            let end = match body.find('{').filter(|_| kind != "type") {
+                Some(b) => match_bracket(&m, at + b),
+                None => statement_end(&m, at),
+            };
+            (kind, name.clone(), format!("{module}.{name}"), end, end)
+        } else {
+            l += 1;
+            continue;
+        };
+        let (first, doc, attrs) = lead_in(src, &starts, l, Syntax::Go);
+        let start = if first < l { indent_at(src, &starts, first) } else { at };
+        let end = trim_end(src, end.min(group_end.map_or(m.len(), |g| g - 1)));
+        let public = name.chars().next().is_some_and(char::is_uppercase);
+        let schema = Schema { doc, attrs: (!attrs.is_empty()).then_some(attrs), ..Default::default() };
+        out.extend(span_anchor(src, kind, &name, path, public, squash(part(src, at, head_end.min(end))), (start, end), schema));
+        l = line_of(&starts, end.max(at)) + 1;
+    }
+    assign_ids(&root, &mut out);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spans(anchors: &[Anchor]) -> Vec<(&str, &str, usize, usize)> {
+        anchors.iter().map(|a| (a.kind.as_str(), a.path.as_str(), a.range.start_line, a.range.end_line)).collect()
+    }
+
+    fn slice(src: &str, a: &Anchor) -> String {
+        src[a.span.0..a.span.1].to_string()
+    }
+
+    #[test]
+    fn python_defs_classes_and_methods() {
+        let src = "import os\n\n\n@dataclass\nclass Config:\n    \"\"\"Settings.\n\n    def not_this(): pass\n    \"\"\"\n    name: str = \"a:b\"  # def nope():\n\n    def load(self, path: str) -> \"Config\":\n        def helper():\n            return 1\n        return helper()\n\n    def _private(self): ...\n\n\nasync def fetch(\n    url,\n    timeout=3,\n):\n    '''Get it.'''\n    return await go(url)\n";
+        let anchors = python(src, "src/app/config.py");
+        assert_eq!(
+            spans(&anchors),
+            vec![
+                ("class", "app.config.Config", 4, 17),
+                ("fn", "app.config.Config.load", 12, 15),
+                ("fn", "app.config.Config._private", 17, 17),
+                ("fn", "app.config.fetch", 20, 25),
+            ]
+        );
+        let class = &anchors[0];
+        let schema = class.schema.as_ref().unwrap();
+        assert_eq!(schema.doc.as_deref(), Some("Settings.\n\ndef not_this(): pass"));
+        assert_eq!(schema.attrs.as_deref(), Some(&["@dataclass".to_string()][..]));
+        assert_eq!(schema.methods.as_deref(), Some(&["def load(self, path: str) -> \"Config\"".to_string(), "def _private(self)".to_string()][..]));
+        assert_eq!(anchors[2].visibility, "priv");
+        assert_eq!(anchors[3].signature.as_deref(), Some("async def fetch( url, timeout=3, )"));
+        assert_eq!(anchors[3].schema.as_ref().unwrap().doc.as_deref(), Some("Get it."));
+        assert!(slice(src, &anchors[3]).ends_with("return await go(url)"));
+        assert!(anchors.iter().all(|a| a.anchor_id.len() == 16));
+    }
+
+    #[test]
+    fn script_declarations_and_class_members() {
+        let src = "import { x } from './x';\n\n/**\n * A widget.\n */\n@Component({ selector: 'w' })\nexport class Widget<T extends { id: string }> extends Base {\n  private count = 0;\n  constructor(private readonly name: string) {\n    super();\n  }\n\n  /** Renders `}` safely. */\n  render(): string {\n    if (this.count) {\n      return `${this.name}}`;\n    }\n    return \"{\";\n  }\n\n  static async load(id: string): Promise<Widget<{ id: string }>> {\n    return new Widget(id);\n  }\n}\n\nexport interface Props {\n  id: string;\n}\n\nexport type Id = string\n  | number;\n\nfunction helper(a: { b: number }): number {\n  return a.b;\n}\n\nexport const handler = async (e: Event) => {\n  helper({ b: 1 });\n};\n\nconst local = 1;\nexport default function () {}\n";
+        let anchors = script(src, "web/src/widgets/widget.ts");
+        assert_eq!(
+            spans(&anchors),
+            vec![
+                ("class", "widgets/widget#Widget", 3, 24),
+                ("fn", "widgets/widget#Widget.constructor", 9, 11),
+                ("fn", "widgets/widget#Widget.render", 13, 19),
+                ("fn", "widgets/widget#Widget.load", 21, 23),
+                ("interface", "widgets/widget#Props", 26, 28),
+                ("type", "widgets/widget#Id", 30, 31),
+                ("fn", "widgets/widget#helper", 33, 35),
+                ("const", "widgets/widget#handler", 37, 39),
+                ("fn", "widgets/widget#default", 42, 42),
+            ]
+        );
+        let class = anchors[0].schema.as_ref().unwrap();
+        assert_eq!(class.doc.as_deref(), Some("A widget."));
+        assert_eq!(class.attrs.as_deref(), Some(&["@Component({ selector: 'w' })".to_string()][..]));
+        assert_eq!(class.methods.as_ref().map(Vec::len), Some(3));
+        assert_eq!(anchors[0].signature.as_deref(), Some("export class Widget<T extends { id: string }> extends Base"));
+        assert_eq!(anchors[2].schema.as_ref().unwrap().doc.as_deref(), Some("Renders `}` safely."));
+        assert_eq!(anchors[6].visibility, "priv");
+        assert_eq!(slice(src, &anchors[5]), "export type Id = string\n  | number;");
+    }
+
+    #[test]
+    fn go_funcs_methods_and_types() {
+        let src = "package store\n\nimport \"fmt\"\n\n// Store keeps rows.\ntype Store struct {\n\trows map[string]string // `}`\n}\n\ntype (\n\t// Key is a row key.\n\tKey string\n\tGetter interface {\n\t\tGet(k Key) (string, error)\n\t}\n)\n\n// Get returns a row.\n//go:noinline\nfunc (s *Store) Get(k Key) (string, error) {\n\tif v, ok := s.rows[string(k)]; ok {\n\t\treturn v, nil\n\t}\n\treturn \"\", fmt.Errorf(\"missing }\")\n}\n\nfunc newStore() *Store { return &Store{rows: map[string]string{}} }\n\nfunc watch() <-chan Key {\n\treturn nil\n}\n";
+        let anchors = go(src, "internal/store/store.go");
+        assert_eq!(
+            spans(&anchors),
+            vec![
+                ("struct", "internal/store.Store", 5, 8),
+                ("type", "internal/store.Key", 11, 12),
+                ("interface", "internal/store.Getter", 13, 15),
+                ("fn", "internal/store.Store.Get", 18, 25),
+                ("fn", "internal/store.newStore", 27, 27),
+                ("fn", "internal/store.watch", 29, 31),
+            ]
+        );
+        let get = anchors[3].schema.as_ref().unwrap();
+        assert_eq!(get.doc.as_deref(), Some("Get returns a row."));
+        assert_eq!(get.attrs.as_deref(), Some(&["//go:noinline".to_string()][..]));
+        assert_eq!(anchors[3].signature.as_deref(), Some("func (s *Store) Get(k Key) (string, error)"));
+        assert_eq!(anchors[4].visibility, "priv");
+        assert!(slice(src, &anchors[4]).ends_with("{}} }"));
+    }
+}
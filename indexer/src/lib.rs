@@ -3,16 +3,29 @@
 #![warn(clippy::all, clippy::pedantic)]
 
 pub mod util;
+pub mod lang;
 pub mod helpers;
 pub mod file_intent_entry;
 pub mod snippet;
 pub mod intent;
+pub mod redact;
 pub mod scan;
 pub mod chunker;
+pub mod dep_graph;
+pub mod budget_pack;
+pub mod delta_pack;
+pub mod session;
 pub mod types_view;
+pub mod symbols;
 pub mod diff;
+pub mod changelog;
+pub mod semver;
 pub mod map_view;
 pub mod commands;
 pub mod functions_view;
 pub mod custom_view;
-pub mod index_v3;
\ No newline at end of file
+pub mod index_v3;
+pub mod lang_anchors;
+pub mod merkle;
+pub mod patch;
+pub mod verify;
\ No newline at end of file
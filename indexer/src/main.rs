@@ -1,6 +1,17 @@
 // src/main.rs
-use anyhow::Result;
+use indexer::commands::{self, Verdict};
+use std::process::ExitCode;
 
-fn main() -> Result<()> {
-    indexer::commands::run_cli()
+fn main() -> ExitCode {
+    match commands::run_cli() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) if e.is::<Verdict>() => {
+            eprintln!("{e}");
+            ExitCode::from(Verdict::EXIT)
+        }
+        Err(e) => {
+            eprintln!("Error: {e:?}");
+            ExitCode::FAILURE
+        }
+    }
 }
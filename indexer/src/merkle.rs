@@ -0,0 +1,218 @@
+// indexer/src/merkle.rs
+//! Per-file merkle trees over v3 chunk hashes, with inclusion proofs.
+//!
+//! Scheme `sha256/prefixed/dup-odd/counted`:
+//! - leaf  = sha256(0x00 || chunk_sha256) — the raw 32-byte chunk digest;
+//! - node  = sha256(0x01 || left || right);
+//! - a level with an odd node count pairs its last node with itself;
+//! - root  = sha256(0x02 || `leaf_count` as u64 big-endian || top node);
+//! - no chunks (empty file): root = sha256("").
+//!
+//! The 0x00/0x01 prefixes keep leaves and inner nodes from being confused in
+//! a proof. Duplicating odd nodes makes the trees over `[a, b, c]` and
+//! `[a, b, c, c]` identical; hashing the leaf count into the root tells them
+//! apart, so a proof's `leaf_count` is checked rather than trusted.
+//! [`DUP_ODD`] packs (no count) are still readable via [`uncounted_root`].
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Scheme id recorded in the pack next to each `merkle_root`.
+pub const SCHEME: &str = "sha256/prefixed/dup-odd/counted";
+
+/// The previous scheme: as [`SCHEME`] but the root is the top node itself.
+pub const DUP_ODD: &str = "sha256/prefixed/dup-odd";
+
+pub type Hash = [u8; 32];
+
+pub fn leaf_hash(chunk_sha256: &Hash) -> Hash {
+    let mut h = Sha256::new();
+    h.update([0x00]);
+    h.update(chunk_sha256);
+    h.finalize().into()
+}
+
+fn node_hash(left: &Hash, right: &Hash) -> Hash {
+    let mut h = Sha256::new();
+    h.update([0x01]);
+    h.update(left);
+    h.update(right);
+    h.finalize().into()
+}
+
+fn seal(leaf_count: usize, top: &Hash) -> Hash {
+    let mut h = Sha256::new();
+    h.update([0x02]);
+    h.update((leaf_count as u64).to_be_bytes());
+    h.update(top);
+    h.finalize().into()
+}
+
+fn top(chunk_digests: &[Hash]) -> Option<Hash> {
+    let mut layer: Vec<Hash> = chunk_digests.iter().map(leaf_hash).collect();
+    while layer.len() > 1 {
+        layer = layer.chunks(2).map(|p| node_hash(&p[0], p.get(1).unwrap_or(&p[0]))).collect();
+    }
+    layer.first().copied()
+}
+
+/// Hex root over chunk digests (in chunk order).
+pub fn root(chunk_digests: &[Hash]) -> String {
+    top(chunk_digests).map_or_else(|| hex::encode(Sha256::digest(b"")), |t| hex::encode(seal(chunk_digests.len(), &t)))
+}
+
+/// Root under [`DUP_ODD`], which did not commit to the leaf count. Only used
+/// to check packs written before [`SCHEME`].
+pub fn uncounted_root(chunk_digests: &[Hash]) -> String {
+    top(chunk_digests).map_or_else(|| hex::encode(Sha256::digest(b"")), hex::encode)
+}
+
+/// Root as written by packs that predate [`DUP_ODD`]: no prefixes, and an odd
+/// node promoted to the next level unhashed. Only used to check old packs.
+pub fn legacy_root(chunk_digests: &[Hash]) -> String {
+    let mut layer: Vec<Vec<u8>> = chunk_digests.iter().map(|d| d.to_vec()).collect();
+    while layer.len() > 1 {
+        layer = layer
+            .chunks(2)
+            .map(|p| if p.len() == 2 { Sha256::digest([p[0].as_slice(), p[1].as_slice()].concat()).to_vec() } else { p[0].clone() })
+            .collect();
+    }
+    layer.first().map_or_else(|| hex::encode(Sha256::digest(b"")), hex::encode)
+}
+
+/// Inclusion proof for one chunk: the sibling at each level, leaf to root.
+/// Sides follow from `leaf_index` and `leaf_count`, so they are not stored.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MerkleProof {
+    pub scheme: String,
+    pub leaf_index: usize,
+    pub leaf_count: usize,
+    /// sha256 of the chunk bytes (hex).
+    pub chunk_sha256: String,
+    /// Sibling hashes (hex), lowest level first. A duplicated odd node lists itself.
+    pub siblings: Vec<String>,
+}
+
+/// Proof that chunk `index` is part of the tree over `chunk_digests`.
+pub fn prove(chunk_digests: &[Hash], index: usize) -> Option<MerkleProof> {
+    let leaf = *chunk_digests.get(index)?;
+    let mut layer: Vec<Hash> = chunk_digests.iter().map(leaf_hash).collect();
+    let mut i = index;
+    let mut siblings = Vec::new();
+    while layer.len() > 1 {
+        let sib = if i & 1 == 0 { layer.get(i + 1).unwrap_or(&layer[i]) } else { &layer[i - 1] };
+        siblings.push(hex::encode(sib));
+        layer = layer.chunks(2).map(|p| node_hash(&p[0], p.get(1).unwrap_or(&p[0]))).collect();
+        i /= 2;
+    }
+    Some(MerkleProof {
+        scheme: SCHEME.to_string(),
+        leaf_index: index,
+        leaf_count: chunk_digests.len(),
+        chunk_sha256: hex::encode(leaf),
+        siblings,
+    })
+}
+
+/// Check `proof` against a hex `root`. Fails on a foreign scheme, a path of the
+/// wrong length, a duplicated odd node whose listed sibling is not itself, or
+/// a `leaf_count` other than the one sealed into the root.
+pub fn verify_proof(proof: &MerkleProof, root: &str) -> bool {
+    if proof.scheme != SCHEME || proof.leaf_index >= proof.leaf_count {
+        return false;
+    }
+    let Some(chunk) = decode(&proof.chunk_sha256) else { return false };
+    let mut acc = leaf_hash(&chunk);
+    let (mut i, mut width) = (proof.leaf_index, proof.leaf_count);
+    let mut siblings = proof.siblings.iter();
+    while width > 1 {
+        let Some(sib) = siblings.next().and_then(|s| decode(s)) else { return false };
+        acc = if i & 1 == 1 {
+            node_hash(&sib, &acc)
+        } else if i + 1 < width {
+            node_hash(&acc, &sib)
+        } else if sib == acc {
+            node_hash(&acc, &acc)
+        } else {
+            return false;
+        };
+        i /= 2;
+        width = width.div_ceil(2);
+    }
+    siblings.next().is_none() && hex::encode(seal(proof.leaf_count, &acc)) == root
+}
+
+/// [`verify_proof`] for chunk bytes in hand: they must hash to `chunk_sha256`.
+pub fn verify_chunk(chunk: &[u8], proof: &MerkleProof, root: &str) -> bool {
+    hex::encode(Sha256::digest(chunk)) == proof.chunk_sha256 && verify_proof(proof, root)
+}
+
+fn decode(s: &str) -> Option<Hash> {
+    hex::decode(s).ok()?.try_into().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn digests(n: usize) -> Vec<Hash> {
+        (0..n).map(|i| Sha256::digest(format!("chunk {i}")).into()).collect()
+    }
+
+    #[test]
+    fn proofs_verify_for_every_leaf_and_tree_shape() {
+        for n in 1..=9 {
+            let d = digests(n);
+            let r = root(&d);
+            for i in 0..n {
+                let p = prove(&d, i).unwrap();
+                assert!(verify_chunk(format!("chunk {i}").as_bytes(), &p, &r), "n={n} i={i}");
+            }
+            assert!(prove(&d, n).is_none());
+        }
+    }
+
+    #[test]
+    fn tampered_proofs_fail() {
+        let d = digests(5);
+        let r = root(&d);
+        let p = prove(&d, 4).unwrap();
+
+        let mut wrong_leaf = p.clone();
+        wrong_leaf.chunk_sha256 = hex::encode(d[3]);
+        let mut wrong_index = p.clone();
+        wrong_index.leaf_index = 3;
+        let mut short = p.clone();
+        short.siblings.pop();
+        // Leaf 4 of 5 is an odd node: its first sibling must be itself.
+        let mut fake_dup = p.clone();
+        fake_dup.siblings[0] = hex::encode(leaf_hash(&d[0]));
+
+        for bad in [wrong_leaf, wrong_index, short, fake_dup] {
+            assert!(!verify_proof(&bad, &r), "{bad:?}");
+        }
+        assert!(!verify_chunk(b"other bytes", &p, &r));
+        assert_ne!(root(&d), legacy_root(&d));
+        assert_ne!(root(&d), uncounted_root(&d));
+    }
+
+    #[test]
+    fn duplicated_last_leaf_changes_the_root() {
+        let abc = digests(3);
+        let abcc = [abc.as_slice(), &abc[2..]].concat();
+        // Same tree shape once the odd node is duplicated...
+        assert_eq!(uncounted_root(&abc), uncounted_root(&abcc));
+        // ...but the sealed count separates them.
+        assert_ne!(root(&abc), root(&abcc));
+
+        // A proof for leaf 3 of [a, b, c, c] carries the same siblings as one
+        // claiming leaf 3 of 3 or 2 of 4; only the true count verifies.
+        let p = prove(&abcc, 3).unwrap();
+        assert!(verify_proof(&p, &root(&abcc)));
+        assert!(!verify_proof(&p, &root(&abc)));
+        let mut as_three = prove(&abc, 2).unwrap();
+        assert!(verify_proof(&as_three, &root(&abc)));
+        as_three.leaf_count = 4;
+        assert!(!verify_proof(&as_three, &root(&abc)));
+    }
+}
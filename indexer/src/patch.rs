@@ -0,0 +1,530 @@
+// indexer/src/patch.rs
+//! Enforce the v3 `patch_contract`: apply a unified diff only inside verified anchors.
+//!
+//! - Each target file must still hash to the `file_sha256` in `index_v3.json`.
+//! - Anchors count as verified when their recorded byte `span` still hashes to
+//!   `slice_sha256` on their recorded lines. A slice starts at the item's first
+//!   doc comment or outer attribute, else at its line's lead-in (`pub`,
+//!   `async`, `unsafe` ..), so an anchor's first line is the item's own.
+//!   Hunks may only remove lines inside, or insert lines strictly within, a
+//!   verified anchor.
+//! - Hunks are located by their context (nearest offset from the header line),
+//!   without fuzz, each strictly after the previous one, and spliced into the
+//!   original bytes (line endings and untouched lines are kept). Every hunk of
+//!   every file must pass, otherwise nothing is written; accepted files are
+//!   staged to temp files and renamed together.
+
+use anyhow::{bail, Context, Result};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    index_v3::{self, hex256, Anchor, FileEntry},
+    verify::verified_anchors,
+};
+
+/* ================================ Parsing ================================ */
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HunkLine {
+    Context(String),
+    Remove(String),
+    Add(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Hunk {
+    /// The `@@ -a,b +c,d @@` line, for reports.
+    pub header: String,
+    pub old_start: usize,
+    pub lines: Vec<HunkLine>,
+    /// `\ No newline at end of file` after the last added/context line.
+    pub new_missing_eol: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct FilePatch {
+    /// `None` for `/dev/null` (file creation).
+    pub old_path: Option<String>,
+    /// `None` for `/dev/null` (file deletion).
+    pub new_path: Option<String>,
+    pub hunks: Vec<Hunk>,
+}
+
+impl FilePatch {
+    /// Path the patch applies to (the old path; the new one for creations).
+    pub fn target(&self) -> &str {
+        self.old_path.as_deref().or(self.new_path.as_deref()).unwrap_or_default()
+    }
+}
+
+/// Parse a unified diff (plain `diff -u` or `git diff` output).
+///
+/// # Errors
+///
+/// Fails on text without `---`/`+++` headers, a hunk outside a file block,
+/// a malformed or truncated hunk, or a line that is not context, `-` or `+`.
+pub fn parse_unified(text: &str) -> Result<Vec<FilePatch>> {
+    let mut files: Vec<FilePatch> = Vec::new();
+    let mut lines = text.lines().peekable();
+    while let Some(line) = lines.next() {
+        if let Some(old) = line.strip_prefix("--- ") {
+            let Some(new) = lines.next().and_then(|l| l.strip_prefix("+++ ")) else {
+                bail!("`--- {old}` is not followed by a `+++` line");
+            };
+            files.push(FilePatch { old_path: diff_path(old), new_path: diff_path(new), hunks: Vec::new() });
+            continue;
+        }
+        let Some(rest) = line.strip_prefix("@@ ") else { continue };
+        let Some(file) = files.last_mut() else {
+            bail!("hunk `{line}` appears before any `---`/`+++` file header");
+        };
+        let (old_start, old_len, new_len) = parse_hunk_header(rest).with_context(|| format!("bad hunk header `{line}`"))?;
+        let mut hunk = Hunk { header: line.to_string(), old_start, lines: Vec::new(), new_missing_eol: false };
+        let (mut old_seen, mut new_seen) = (0usize, 0usize);
+        while old_seen < old_len || new_seen < new_len {
+            let Some(l) = lines.next() else { bail!("hunk `{line}` is truncated") };
+            match l.chars().next() {
+                Some('+') => {
+                    hunk.lines.push(HunkLine::Add(l[1..].to_string()));
+                    new_seen += 1;
+                }
+                Some('-') => {
+                    hunk.lines.push(HunkLine::Remove(l[1..].to_string()));
+                    old_seen += 1;
+                }
+                Some(' ') => {
+                    hunk.lines.push(HunkLine::Context(l[1..].to_string()));
+                    old_seen += 1;
+                    new_seen += 1;
+                }
+                // Some generators drop the space on empty context lines.
+                None => {
+                    hunk.lines.push(HunkLine::Context(String::new()));
+                    old_seen += 1;
+                    new_seen += 1;
+                }
+                Some('\\') => {}
+                Some(_) => bail!("unexpected line in hunk `{line}`: `{l}`"),
+            }
+            if lines.peek().is_some_and(|n| n.starts_with('\\')) {
+                lines.next();
+                if matches!(hunk.lines.last(), Some(HunkLine::Add(_) | HunkLine::Context(_))) {
+                    hunk.new_missing_eol = true;
+                }
+            }
+        }
+        file.hunks.push(hunk);
+    }
+    if files.is_empty() {
+        bail!("no file headers (`---` / `+++`) found: not a unified diff");
+    }
+    Ok(files)
+}
+
+/// `a/src/x.rs\t2024-..` -> `src/x.rs`; `/dev/null` -> None.
+fn diff_path(raw: &str) -> Option<String> {
+    let p = raw.split('\t').next().unwrap_or(raw).trim();
+    if p == "/dev/null" {
+        return None;
+    }
+    let p = p.strip_prefix("a/").or_else(|| p.strip_prefix("b/")).unwrap_or(p);
+    Some(p.trim_start_matches("./").to_string())
+}
+
+/// `-a[,b] +c[,d] @@ ...` -> (a, b, d); omitted lengths are 1.
+fn parse_hunk_header(rest: &str) -> Option<(usize, usize, usize)> {
+    let mut it = rest.split_whitespace();
+    let range = |s: &str| -> Option<(usize, usize)> {
+        match s.split_once(',') {
+            Some((a, b)) => Some((a.parse().ok()?, b.parse().ok()?)),
+            None => Some((s.parse().ok()?, 1)),
+        }
+    };
+    let (old_start, old_len) = range(it.next()?.strip_prefix('-')?)?;
+    let (_, new_len) = range(it.next()?.strip_prefix('+')?)?;
+    Some((old_start, old_len, new_len))
+}
+
+/* ================================ Checking ================================ */
+
+/// Verdict for one hunk.
+#[derive(Debug, Clone)]
+pub struct HunkReport {
+    pub header: String,
+    /// Anchors (`fn crate::m::name`) the hunk edits when accepted.
+    pub anchors: Vec<String>,
+    /// Why the hunk was refused; empty when accepted.
+    pub problems: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct FileReport {
+    pub path: String,
+    /// File-level refusal (unknown file, stale sha, creation/deletion).
+    pub problem: Option<String>,
+    pub hunks: Vec<HunkReport>,
+}
+
+impl FileReport {
+    pub fn ok(&self) -> bool {
+        self.problem.is_none() && self.hunks.iter().all(|h| h.problems.is_empty())
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ApplyReport {
+    pub files: Vec<FileReport>,
+    /// Files written (empty on refusal or dry run).
+    pub written: Vec<PathBuf>,
+}
+
+impl ApplyReport {
+    pub fn ok(&self) -> bool {
+        self.files.iter().all(FileReport::ok)
+    }
+}
+
+/// Check `patch` against `index_v3.json` and, unless `dry_run`, apply it under
+/// `root` when every hunk is in scope. Blocks that edit the same file are
+/// checked as one, so later blocks cannot overwrite earlier ones.
+///
+/// # Errors
+///
+/// Fails when `patch` is not a unified diff, when `v3_path` cannot be loaded,
+/// or when staging or renaming the patched files fails. Out-of-scope hunks
+/// are not errors: they are listed in the report and nothing is written.
+pub fn apply_patch(patch: &str, v3_path: &Path, root: &Path, dry_run: bool) -> Result<ApplyReport> {
+    let files = merge_same_file(parse_unified(patch)?);
+    let pack = index_v3::load_pack(v3_path)?;
+    let by_path: HashMap<&str, &FileEntry> = pack.files.iter().map(|f| (f.path.as_str(), f)).collect();
+
+    let mut report = ApplyReport::default();
+    let mut staged: Vec<(PathBuf, String)> = Vec::new();
+    for fp in &files {
+        let (file_report, new_text) = check_file(fp, &by_path, root);
+        if let Some(text) = new_text {
+            staged.push((root.join(fp.target()), text));
+        }
+        report.files.push(file_report);
+    }
+
+    if report.ok() && !dry_run {
+        write_all(&staged)?;
+        report.written = staged.into_iter().map(|(p, _)| p).collect();
+    }
+    Ok(report)
+}
+
+/// Folds the hunks of repeated `---`/`+++` blocks for one file into its first
+/// block. Creations, deletions and renames are left alone (they are refused).
+fn merge_same_file(files: Vec<FilePatch>) -> Vec<FilePatch> {
+    let mut out: Vec<FilePatch> = Vec::new();
+    for fp in files {
+        let same = |o: &&mut FilePatch| o.old_path.is_some() && o.old_path == o.new_path && o.old_path == fp.old_path && o.new_path == fp.new_path;
+        match out.iter_mut().find(same) {
+            Some(first) => first.hunks.extend(fp.hunks),
+            None => out.push(fp),
+        }
+    }
+    out
+}
+
+/// Report for one file plus its patched text when every hunk passed.
+fn check_file(fp: &FilePatch, by_path: &HashMap<&str, &FileEntry>, root: &Path) -> (FileReport, Option<String>) {
+    let path = fp.target().to_string();
+    let mut report = FileReport { path: path.clone(), problem: None, hunks: Vec::new() };
+    let refuse = |mut r: FileReport, why: String| {
+        r.problem = Some(why);
+        (r, None)
+    };
+
+    match (&fp.old_path, &fp.new_path) {
+        (None, _) => return refuse(report, "creates a file: no verified anchors to scope it".into()),
+        (_, None) => return refuse(report, "deletes a file: outside anchor scope".into()),
+        (Some(a), Some(b)) if a != b => return refuse(report, format!("renames to `{b}`: outside anchor scope")),
+        _ => {}
+    }
+    let Some(v3) = by_path.get(path.as_str()) else {
+        return refuse(report, "not in index_v3.json".into());
+    };
+    let Ok(src) = fs::read_to_string(root.join(&path)) else {
+        return refuse(report, "cannot read the file".into());
+    };
+    if hex256(src.as_bytes()) != v3.file_sha256 {
+        return refuse(report, "file changed since index_v3.json was built (file_sha256 mismatch; re-run `indexer v3`)".into());
+    }
+
+    let verified: Vec<&Anchor> = verified_anchors(v3, &src);
+    // Hunks are located against the original text and spliced into it by byte
+    // range, so untouched lines keep their bytes and line endings.
+    let original: Vec<&str> = src.lines().collect();
+    let mut edits: Vec<(std::ops::Range<usize>, String)> = Vec::new();
+    let mut prev_end = 0usize;
+
+    let mut hunks: Vec<&Hunk> = fp.hunks.iter().collect();
+    hunks.sort_by_key(|h| h.old_start);
+    for h in hunks {
+        let mut hr = HunkReport { header: h.header.clone(), anchors: Vec::new(), problems: Vec::new() };
+        let Some(pos) = locate(&original, h, prev_end) else {
+            let why = if locate(&original, h, 0).is_some() { "overlaps the previous hunk" } else { "context does not match the file" };
+            hr.problems.push(why.into());
+            report.hunks.push(hr);
+            continue;
+        };
+        let old_len = h.lines.iter().filter(|l| !matches!(l, HunkLine::Add(_))).count();
+
+        // Removals name old line numbers; insertions sit "after old line p".
+        // Added lines that directly follow removals replace those lines, so
+        // they share the removed line's scope (one-line items stay editable).
+        let mut cursor = pos;
+        let mut replacing: Option<usize> = None;
+        for l in &h.lines {
+            let (hit, what) = match l {
+                HunkLine::Context(_) => {
+                    cursor += 1;
+                    replacing = None;
+                    continue;
+                }
+                HunkLine::Remove(text) => {
+                    cursor += 1;
+                    replacing = Some(cursor);
+                    let line = cursor;
+                    let hit = verified.iter().find(|a| a.range.start_line <= line && line <= a.range.end_line);
+                    (hit, format!("line {line} (`-{}`)", clip(text)))
+                }
+                HunkLine::Add(text) => {
+                    let p = cursor;
+                    let hit = verified.iter().find(|a| match replacing {
+                        Some(r) => a.range.start_line <= r && r <= a.range.end_line,
+                        None => a.range.start_line <= p && p < a.range.end_line,
+                    });
+                    (hit, format!("insertion after line {p} (`+{}`)", clip(text)))
+                }
+            };
+            match hit {
+                Some(a) => {
+                    let label = format!("{} {}", a.kind, if a.path.is_empty() { &a.name } else { &a.path });
+                    if !hr.anchors.contains(&label) {
+                        hr.anchors.push(label);
+                    }
+                }
+                None => hr.problems.push(format!("{what} is outside every verified anchor")),
+            }
+        }
+
+        if hr.problems.is_empty() {
+            edits.push(splice(&src, original.len(), h, pos, old_len));
+        }
+        prev_end = pos + old_len;
+        report.hunks.push(hr);
+    }
+
+    if !report.ok() {
+        return (report, None);
+    }
+    let mut text = String::with_capacity(src.len());
+    let mut copied = 0;
+    for (range, with) in edits {
+        text.push_str(&src[copied..range.start]);
+        text.push_str(&with);
+        copied = range.end;
+    }
+    text.push_str(&src[copied..]);
+    (report, Some(text))
+}
+
+/// Byte range of old lines `pos..pos + old_len` in `src` and the text replacing
+/// it. Context lines are copied from `src` (whitespace the match ignored is
+/// kept); added lines take the file's line ending (`\r\n` if it has any).
+fn splice(src: &str, line_count: usize, h: &Hunk, pos: usize, old_len: usize) -> (std::ops::Range<usize>, String) {
+    let starts: Vec<usize> = std::iter::once(0).chain(src.match_indices('\n').map(|(i, _)| i + 1)).take(line_count).collect();
+    let line_start = |i: usize| starts.get(i).copied().unwrap_or(src.len());
+    let eol = if src.contains("\r\n") { "\r\n" } else { "\n" };
+
+    let mut with = String::new();
+    let mut cursor = pos;
+    for l in &h.lines {
+        match l {
+            HunkLine::Context(_) => {
+                with.push_str(&src[line_start(cursor)..line_start(cursor + 1)]);
+                cursor += 1;
+            }
+            HunkLine::Remove(_) => cursor += 1,
+            HunkLine::Add(t) => {
+                with.push_str(t);
+                with.push_str(eol);
+            }
+        }
+    }
+    // Only the hunk touching the last line decides the final newline.
+    if pos + old_len == line_count && !with.is_empty() {
+        let body = with.trim_end_matches(['\r', '\n']).len();
+        if h.new_missing_eol {
+            with.truncate(body);
+        } else if body == with.len() {
+            with.push_str(eol);
+        }
+    }
+    (line_start(pos)..line_start(pos + old_len), with)
+}
+
+/// 0-based index where the hunk's old lines (context + removals) start: the
+/// header position if it matches, else the nearest match at or after `floor`
+/// (the end of the previous hunk, so two hunks never claim the same lines).
+fn locate(original: &[&str], h: &Hunk, floor: usize) -> Option<usize> {
+    let old: Vec<&str> = h
+        .lines
+        .iter()
+        .filter_map(|l| match l {
+            HunkLine::Context(t) | HunkLine::Remove(t) => Some(t.as_str()),
+            HunkLine::Add(_) => None,
+        })
+        .collect();
+    // Pure insertion with no context: `@@ -p,0 ..` inserts after line p.
+    if old.is_empty() {
+        return (floor <= h.old_start && h.old_start <= original.len()).then_some(h.old_start);
+    }
+    if old.len() > original.len() {
+        return None;
+    }
+    let fits = |at: usize| original[at..at + old.len()].iter().zip(&old).all(|(a, b)| a.trim_end() == b.trim_end());
+    let last = original.len() - old.len();
+    let want = h.old_start.saturating_sub(1).min(last);
+    (0..=last)
+        .flat_map(|d| [want.checked_add(d), want.checked_sub(d)])
+        .flatten()
+        .filter(|&at| at >= floor && at <= last)
+        .find(|&at| fits(at))
+}
+
+fn clip(s: &str) -> String {
+    let t = s.trim();
+    if t.chars().count() > 40 {
+        format!("{}…", t.chars().take(40).collect::<String>())
+    } else {
+        t.to_string()
+    }
+}
+
+/// Stage every file as `<file>.indexer-apply.tmp`, then rename all; if a rename
+/// fails, already-replaced files are restored from their original contents.
+fn write_all(staged: &[(PathBuf, String)]) -> Result<()> {
+    let tmp_of = |p: &Path| p.with_file_name(format!("{}.indexer-apply.tmp", p.file_name().unwrap_or_default().to_string_lossy()));
+    let mut originals: Vec<(PathBuf, Vec<u8>)> = Vec::new();
+    for (path, text) in staged {
+        originals.push((path.clone(), fs::read(path).with_context(|| format!("reading {}", path.display()))?));
+        if let Err(e) = fs::write(tmp_of(path), text) {
+            for (p, _) in staged {
+                let _ = fs::remove_file(tmp_of(p));
+            }
+            return Err(e).with_context(|| format!("staging {}", path.display()));
+        }
+    }
+    for (i, (path, _)) in staged.iter().enumerate() {
+        if let Err(e) = fs::rename(tmp_of(path), path) {
+            for (p, bytes) in &originals[..i] {
+                let _ = fs::write(p, bytes);
+            }
+            for (p, _) in &staged[i..] {
+                let _ = fs::remove_file(tmp_of(p));
+            }
+            return Err(e).with_context(|| format!("replacing {} (earlier files restored)", path.display()));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SRC: &str = "use std::fmt;\n\npub fn add(a: u8, b: u8) -> u8 {\n    a + b\n}\n\nconst X: u8 = 1;\n";
+
+    fn setup(tag: &str, src: &str) -> (PathBuf, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("indexer-patch-{tag}-{}", std::process::id()));
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join("src/a.rs"), src).unwrap();
+        fs::write(dir.join("index.jsonl"), "{\"path\":\"src/a.rs\",\"lang\":\"rust\"}\n").unwrap();
+        let v3_path = dir.join("index_v3.json");
+        index_v3::build_index_v3(&dir.join("index.jsonl"), &dir, &v3_path).unwrap();
+        (dir, v3_path)
+    }
+
+    #[test]
+    fn applies_hunks_inside_verified_anchors() {
+        let (dir, v3) = setup("ok", SRC);
+        // Header line numbers are off by one; the context still locates it.
+        let patch = "--- a/src/a.rs\n+++ b/src/a.rs\n@@ -4,3 +4,4 @@\n pub fn add(a: u8, b: u8) -> u8 {\n-    a + b\n+    let s = a + b;\n+    s\n }\n";
+        let dry = apply_patch(patch, &v3, &dir, true).unwrap();
+        assert!(dry.ok(), "{dry:?}");
+        assert!(dry.written.is_empty());
+        assert_eq!(fs::read_to_string(dir.join("src/a.rs")).unwrap(), SRC);
+
+        let r = apply_patch(patch, &v3, &dir, false).unwrap();
+        let out = fs::read_to_string(dir.join("src/a.rs")).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+        assert_eq!(r.files[0].hunks[0].anchors, ["fn crate::a::add"]);
+        assert_eq!(out, SRC.replace("    a + b\n", "    let s = a + b;\n    s\n"));
+    }
+
+    #[test]
+    fn refuses_out_of_scope_and_stale_files() {
+        let (dir, v3) = setup("refuse", SRC);
+        let patch = "--- a/src/a.rs\n+++ b/src/a.rs\n@@ -1,1 +1,1 @@\n-use std::fmt;\n+use std::io;\n";
+        let r = apply_patch(patch, &v3, &dir, false).unwrap();
+        assert!(!r.ok());
+        assert!(r.files[0].hunks[0].problems[0].contains("line 1"), "{r:?}");
+        assert_eq!(fs::read_to_string(dir.join("src/a.rs")).unwrap(), SRC);
+
+        fs::write(dir.join("src/a.rs"), SRC.replace("a + b", "b + a")).unwrap();
+        let stale = apply_patch(patch, &v3, &dir, false).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+        assert!(stale.files[0].problem.as_deref().unwrap().contains("file_sha256"));
+    }
+
+    #[test]
+    fn splices_bytes_and_keeps_crlf_and_missing_newline() {
+        // CRLF, trailing spaces on a context line, no final newline.
+        let src = "pub fn add(a: u8, b: u8) -> u8 {  \r\n    a + b\r\n}\r\n\r\npub fn one() -> u8 {\r\n    1\r\n}";
+        let (dir, v3) = setup("crlf", src);
+        let patch = "--- a/src/a.rs\n+++ b/src/a.rs\n@@ -1,3 +1,3 @@\n pub fn add(a: u8, b: u8) -> u8 {\n-    a + b\n+    b + a\n }\n\
+                     @@ -5,3 +5,3 @@\n pub fn one() -> u8 {\n-    1\n+    2\n }\n\\ No newline at end of file\n";
+        let r = apply_patch(patch, &v3, &dir, false).unwrap();
+        let out = fs::read_to_string(dir.join("src/a.rs")).unwrap();
+        assert!(r.ok(), "{r:?}");
+        assert_eq!(out, src.replace("a + b", "b + a").replace("    1", "    2"));
+
+        // Two hunks claiming the same lines: the second one is refused.
+        let (dir2, v3) = setup("overlap", SRC);
+        let hunk = "@@ -3,3 +3,3 @@\n pub fn add(a: u8, b: u8) -> u8 {\n-    a + b\n+    b + a\n }\n";
+        let twice = format!("--- a/src/a.rs\n+++ b/src/a.rs\n{hunk}{hunk}");
+        let r = apply_patch(&twice, &v3, &dir2, false).unwrap();
+        let _ = (fs::remove_dir_all(&dir), fs::remove_dir_all(&dir2));
+        assert!(r.files[0].hunks[0].problems.is_empty());
+        assert_eq!(r.files[0].hunks[1].problems, ["overlaps the previous hunk"]);
+    }
+
+    #[test]
+    fn repeated_blocks_for_one_file_are_applied_together() {
+        let src = "pub fn add(a: u8, b: u8) -> u8 {\n    a + b\n}\n\npub fn one() -> u8 {\n    1\n}\n";
+        let (dir, v3) = setup("dup", src);
+        let first = "--- a/src/a.rs\n+++ b/src/a.rs\n@@ -1,3 +1,3 @@\n pub fn add(a: u8, b: u8) -> u8 {\n-    a + b\n+    b + a\n }\n";
+        let second = "--- a/src/a.rs\n+++ b/src/a.rs\n@@ -5,3 +5,3 @@\n pub fn one() -> u8 {\n-    1\n+    2\n }\n";
+        let r = apply_patch(&format!("{first}{second}"), &v3, &dir, false).unwrap();
+        let out = fs::read_to_string(dir.join("src/a.rs")).unwrap();
+        assert!(r.ok(), "{r:?}");
+        assert_eq!((r.files.len(), r.written.len()), (1, 1));
+        assert_eq!(out, src.replace("a + b", "b + a").replace("    1", "    2"));
+
+        // The same hunk sent in two blocks overlaps itself and is refused.
+        let (dir2, v3) = setup("dup-overlap", src);
+        let r = apply_patch(&format!("{first}{first}"), &v3, &dir2, false).unwrap();
+        let _ = (fs::remove_dir_all(&dir), fs::remove_dir_all(&dir2));
+        assert!(!r.ok());
+        assert_eq!(r.files[0].hunks[1].problems, ["overlaps the previous hunk"]);
+    }
+}
@@ -9,6 +9,9 @@
 //! - Deterministic sorting; safer writing via util::safe_write.
 //! - Tunable limits via ScanOptions.
 //! - Extra tags (role/module/imports/exports) preserved.
+//! - Rust files carry a symbol table (fns/methods/structs/enums) for symbol-level diffs.
+//! - Secrets are redacted from snippets (see `redact`); a `<index>.redactions.json`
+//!   report lists file + line of each hit.
 
 use anyhow::{Context, Result};
 use ignore::{gitignore::GitignoreBuilder, WalkBuilder};
@@ -23,7 +26,9 @@ use crate::{
     file_intent_entry::FileIntentEntry,
     helpers::{infer_module_id, infer_role, skim_symbols},
     intent,
+    redact::{Redaction, Redactor},
     snippet,
+    symbols,
     util, // ext_to_lang, is_probably_binary, infer_tags, to_unix_epoch, safe_write
 };
 
@@ -44,6 +49,8 @@ pub struct ScanOptions {
     pub include_docs_and_configs: bool,
     /// If true, split HTML into sub-entries for <script>/<style>.
     pub split_html_embeds: bool,
+    /// Redact secrets (built-ins + `.gptredact`) from snippets.
+    pub redact: bool,
 }
 
 impl Default for ScanOptions {
@@ -55,15 +62,17 @@ impl Default for ScanOptions {
             follow_symlinks: false,
             include_docs_and_configs: true,
             split_html_embeds: true,
+            redact: true,
         }
     }
 }
 
 /* ============================= Public API ============================== */
 
-/// Scan repo and write JSONL index file at `out`.
+/// Scan repo and write JSONL index file at `out`, plus the redaction report
+/// next to it (`<out stem>.redactions.json`).
 pub fn scan_and_write_index(root: &Path, out: &Path) -> Result<Vec<FileIntentEntry>> {
-    let mut entries = index_project_with_opts(root, &ScanOptions::default())?;
+    let (mut entries, redactions) = index_project_with_report(root, &ScanOptions::default())?;
     // Deterministic output: sort by path, then lang, then module-ish fields
     entries.sort_by(|a, b| {
         a.path
@@ -78,9 +87,26 @@ pub fn scan_and_write_index(root: &Path, out: &Path) -> Result<Vec<FileIntentEnt
         writeln!(&mut buf, "{}", serde_json::to_string(entry)?)?;
     }
     util::safe_write(out, buf)?;
+
+    let report_path = redaction_report_path(out);
+    crate::redact::write_report(&report_path, &redactions)?;
+    if !redactions.is_empty() {
+        let files: std::collections::BTreeSet<&str> = redactions.iter().map(|r| r.path.as_str()).collect();
+        eprintln!(
+            "[scan] redacted {} secret(s) in {} file(s); see {}",
+            redactions.len(),
+            files.len(),
+            report_path.display()
+        );
+    }
     Ok(entries)
 }
 
+/// `.../<slug>.jsonl` -> `.../<slug>.redactions.json`.
+pub fn redaction_report_path(index_file: &Path) -> std::path::PathBuf {
+    index_file.with_extension("redactions.json")
+}
+
 /// Default indexer with sane options.
 pub fn index_project(root: &Path) -> Result<Vec<FileIntentEntry>> {
     index_project_with_opts(root, &ScanOptions::default())
@@ -88,8 +114,24 @@ pub fn index_project(root: &Path) -> Result<Vec<FileIntentEntry>> {
 
 /// Full-control indexer.
 pub fn index_project_with_opts(root: &Path, opts: &ScanOptions) -> Result<Vec<FileIntentEntry>> {
+    index_project_with_report(root, opts).map(|(entries, _)| entries)
+}
+
+/// Full-control indexer that also returns the redaction report rows.
+pub fn index_project_with_report(
+    root: &Path,
+    opts: &ScanOptions,
+) -> Result<(Vec<FileIntentEntry>, Vec<Redaction>)> {
     let root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
     let matcher = build_ignore_matcher(&root)?;
+    let redactor = if opts.redact { Some(Redactor::load(&root)?) } else { None };
+    let mut redactions = Vec::<Redaction>::new();
+    // Snippets are redacted on their own text; placeholders hash the secret,
+    // so they match the ones in the full-file report and in v3 anchors.
+    let scrub = |s: String| match &redactor {
+        Some(r) => r.redact(&s).0,
+        None => s,
+    };
     let walker = {
         let mut w = WalkBuilder::new(&root);
         w.standard_filters(true);
@@ -147,9 +189,13 @@ pub fn index_project_with_opts(root: &Path, opts: &ScanOptions) -> Result<Vec<Fi
         // SHA1 across the full content buffer
         let sha1 = sha1_hex(content.as_bytes());
 
+        if let Some(r) = &redactor {
+            redactions.extend(r.report(&rel_path, &content));
+        }
+
         // Snippet window
         let snip_src = slice_prefix(&content, opts.snippet_bytes);
-        let mut base_snip = snippet::extract_relevant_snippet(snip_src, &lang);
+        let mut base_snip = scrub(snippet::extract_relevant_snippet(snip_src, &lang));
 
         // HTML splitting: produce sub-entries for <script> / <style>
         if opts.split_html_embeds && (lang == "html" || lang == "htm") {
@@ -158,10 +204,10 @@ pub fn index_project_with_opts(root: &Path, opts: &ScanOptions) -> Result<Vec<Fi
                 any_split = true;
                 let virt_path = format!("{}#{}", &rel_path, block.id_for_path(i + 1));
                 let virt_lang = block.lang.to_string();
-                let snip = snippet::extract_relevant_snippet(
+                let snip = scrub(snippet::extract_relevant_snippet(
                     slice_prefix(block.body, opts.snippet_bytes),
                     &virt_lang,
-                );
+                ));
 
                 let (entry, _) = build_entry(
                     &virt_path,
@@ -179,12 +225,12 @@ pub fn index_project_with_opts(root: &Path, opts: &ScanOptions) -> Result<Vec<Fi
 
             // Also include a parent HTML entry with a compact summary/snippet of structure
             if any_split {
-                base_snip = html_structure_preview(&content, opts.snippet_bytes);
+                base_snip = scrub(html_structure_preview(&content, opts.snippet_bytes));
             }
         }
 
         // Base file entry (for non-html or additionally for html)
-        let (entry, skip_base) = build_entry(
+        let (mut entry, skip_base) = build_entry(
             &rel_path,
             &lang,
             &sha1,
@@ -194,12 +240,15 @@ pub fn index_project_with_opts(root: &Path, opts: &ScanOptions) -> Result<Vec<Fi
             &content,
             None,
         );
+        if lang == "rust" {
+            entry.symbols = symbols::rust_symbols(&content);
+        }
         if !skip_base {
             out.push(entry);
         }
     }
 
-    Ok(out)
+    Ok((out, redactions))
 }
 
 /* ============================== Core helpers =============================== */
@@ -280,7 +329,7 @@ fn detect_lang(path: &Path) -> Result<String> {
     Ok(ext_lang.to_string()) // likely "txt"
 }
 
-fn lang_is_doc_or_config(lang: &str) -> bool {
+pub(crate) fn lang_is_doc_or_config(lang: &str) -> bool {
     matches!(lang, "md" | "json" | "toml" | "yaml" | "yml")
 }
 
@@ -336,6 +385,7 @@ fn build_entry(
             lines_nonblank,
             rel_dir,
             noise,
+            symbols: None,
         },
         skip_base,
     )
@@ -0,0 +1,560 @@
+// indexer/src/semver.rs
+//! Public API semver check between two versions of a library crate.
+//!
+//! - Versions are index snapshots (JSONL with symbol tables), git revisions
+//!   (sources read via `git show`), or `.` for the working tree.
+//! - The API surface is every `pub` item (fn, struct, enum, union, trait,
+//!   const, static, type alias) reachable from `src/lib.rs` through `pub mod`
+//!   chains, file or inline, and `pub use` re-exports (globs included);
+//!   `pub(crate)` items, items under private modules and `#[cfg(test)]`
+//!   modules never count. Public methods attach to their type's public paths,
+//!   matched by defining module, not bare name.
+//! - Differences are classified major / minor / patch with a reason each.
+
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    fs,
+    path::Path,
+    process::Command,
+};
+
+use crate::{
+    dep_graph::crate_rel_module,
+    scan,
+    symbols::{rust_symbols, Symbol},
+};
+
+/* ================================= Model ================================= */
+
+/// One reachable public item, keyed in [`ApiSurface`] by its public path.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ApiItem {
+    /// `fn` | `method` | `struct` | `enum` | `union` | `trait` | `const` |
+    /// `static` | `type` | `reexport` (external path)
+    pub kind: String,
+    pub sig: Option<String>,
+    pub members: Vec<String>,
+    pub non_exhaustive: bool,
+    /// Name at the definition site (differs from the path tail for `as` renames).
+    pub origin: String,
+    /// Module defining it (`inner::deep`, `crate`); empty for `reexport`.
+    pub module: String,
+}
+
+/// Kinds that are API items in their own right.
+const ITEM_KINDS: &[&str] = &["struct", "enum", "union", "trait", "fn", "const", "static", "type"];
+
+/// Public path (`scan::ScanOptions`, `Widget::new`) -> item.
+pub type ApiSurface = BTreeMap<String, ApiItem>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Patch,
+    Minor,
+    Major,
+}
+
+impl Level {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Level::Patch => "patch",
+            Level::Minor => "minor",
+            Level::Major => "major",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ApiChange {
+    pub level: Level,
+    pub path: String,
+    pub kind: String,
+    pub reason: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct SemverReport {
+    /// Required bump: the highest level among `changes` (patch when empty).
+    pub level: Level,
+    pub old_items: usize,
+    pub new_items: usize,
+    pub changes: Vec<ApiChange>,
+}
+
+/* ============================= Public API ============================== */
+
+/// Load (path, symbols) for every Rust file of `spec`: a snapshot file,
+/// `.` (working tree under `root`) or a git revision.
+pub fn load_symbols(spec: &str, root: &Path) -> Result<Vec<(String, Vec<Symbol>)>> {
+    if spec == "." {
+        return worktree_symbols(root);
+    }
+    let as_path = root.join(spec);
+    if as_path.is_file() {
+        let entries = scan::read_index(&as_path)?;
+        let files: Vec<(String, Vec<Symbol>)> = entries
+            .into_iter()
+            .filter_map(|e| e.symbols.map(|s| (e.path, s)))
+            .collect();
+        if files.is_empty() {
+            bail!("{spec}: snapshot has no symbol tables (written before symbols were indexed; re-run `indexer reindex`)");
+        }
+        return Ok(files);
+    }
+    git_symbols(spec, root)
+}
+
+/// Public API reachable from the crate's `src/lib.rs`.
+pub fn api_surface(files: &[(String, Vec<Symbol>)]) -> Result<ApiSurface> {
+    let lib = files
+        .iter()
+        .map(|(p, _)| p.as_str())
+        .filter(|p| *p == "src/lib.rs" || p.ends_with("/src/lib.rs"))
+        .min_by_key(|p| p.len())
+        .context("no src/lib.rs found: semver checks apply to library crates")?;
+    let prefix = &lib[..lib.len() - "src/lib.rs".len()];
+
+    // File modules, plus the inline `mod { .. }` blocks inside them.
+    let mut modules: BTreeMap<String, Vec<&Symbol>> = BTreeMap::new();
+    for (p, syms) in files {
+        let Some(rel) = p.strip_prefix(prefix).filter(|r| r.starts_with("src/")) else { continue };
+        let id = crate_rel_module(rel);
+        if id == "bin" || id.starts_with("bin::") {
+            continue;
+        }
+        modules.entry(id.clone()).or_default();
+        for s in syms {
+            let at = if s.module.is_empty() { id.clone() } else { child(&id, &s.module) };
+            modules.entry(at).or_default().push(s);
+        }
+    }
+
+    let mut surface = ApiSurface::new();
+    let mut queue: VecDeque<(String, String)> = VecDeque::from([("crate".to_string(), String::new())]);
+    let mut seen: BTreeSet<(String, String)> = BTreeSet::new();
+    while let Some((module, public)) = queue.pop_front() {
+        if !seen.insert((module.clone(), public.clone())) {
+            continue;
+        }
+        let Some(syms) = modules.get(&module) else { continue };
+        for s in syms {
+            match s.kind.as_str() {
+                "mod" if s.public => queue.push_back((child(&module, &s.name), join(&public, &s.name))),
+                k if s.public && ITEM_KINDS.contains(&k) => {
+                    surface.insert(join(&public, &s.name), item_of(s, &module));
+                }
+                "use" => {
+                    let Some(target) = &s.sig else { continue };
+                    reexport(&modules, &module, &public, &s.name, target, &mut surface, &mut queue);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // Public methods hang off whichever public paths expose their type,
+    // identified by defining module + name.
+    let types: Vec<(String, String, String)> = surface
+        .iter()
+        .filter(|(_, it)| matches!(it.kind.as_str(), "struct" | "enum" | "union"))
+        .map(|(path, it)| (path.clone(), it.module.clone(), it.origin.clone()))
+        .collect();
+    for (module, syms) in &modules {
+        for m in syms.iter().filter(|s| s.kind == "method" && s.public) {
+            let Some((ty, method)) = m.name.split_once("::") else { continue };
+            let owner = m.owner.as_deref().unwrap_or(ty);
+            let segs: Vec<&str> = owner.split("::").collect();
+            let (name, init) = segs.split_last().map_or((ty, &[][..]), |(l, i)| (*l, i));
+            let Some(defined_in) = resolve_module(&modules, module, init) else { continue };
+            for (path, _, _) in types.iter().filter(|(_, m, o)| *m == defined_in && o == name) {
+                surface.insert(format!("{path}::{method}"), item_of(m, module));
+            }
+        }
+    }
+    Ok(surface)
+}
+
+/// Classify every difference between two surfaces.
+pub fn compare(old: &ApiSurface, new: &ApiSurface) -> SemverReport {
+    let mut changes = Vec::new();
+    let mut push = |level: Level, path: &str, kind: &str, reason: String| {
+        changes.push(ApiChange { level, path: path.to_string(), kind: kind.to_string(), reason });
+    };
+
+    for (path, o) in old {
+        let Some(n) = new.get(path) else {
+            push(Level::Major, path, &o.kind, format!("removed pub {}", o.kind));
+            continue;
+        };
+        if o.kind != n.kind {
+            push(Level::Major, path, &n.kind, format!("kind changed: {} -> {}", o.kind, n.kind));
+            continue;
+        }
+        match o.kind.as_str() {
+            "struct" | "union" => compare_struct(o, n, |l, r| push(l, path, &o.kind, r)),
+            "enum" => compare_enum(o, n, |l, r| push(l, path, "enum", r)),
+            "trait" => compare_trait(o, n, |l, r| push(l, path, "trait", r)),
+            _ => {
+                if o.sig != n.sig {
+                    push(
+                        Level::Major,
+                        path,
+                        &o.kind,
+                        format!(
+                            "signature changed: `{}` -> `{}`",
+                            o.sig.as_deref().unwrap_or_default(),
+                            n.sig.as_deref().unwrap_or_default()
+                        ),
+                    );
+                }
+            }
+        }
+    }
+    for (path, n) in new {
+        if !old.contains_key(path) {
+            push(Level::Minor, path, &n.kind, format!("added pub {}", n.kind));
+        }
+    }
+
+    changes.sort_by(|a, b| b.level.cmp(&a.level).then_with(|| a.path.cmp(&b.path)));
+    SemverReport {
+        level: changes.iter().map(|c| c.level).max().unwrap_or(Level::Patch),
+        old_items: old.len(),
+        new_items: new.len(),
+        changes,
+    }
+}
+
+/* ============================ Classification ============================ */
+
+/// (public?, name, type) of a `pub name: Ty` member.
+fn field(m: &str) -> (bool, String, String) {
+    let (public, rest) = match m.strip_prefix("pub ") {
+        Some(r) => (true, r),
+        None => (false, m),
+    };
+    let (name, ty) = rest.split_once(':').unwrap_or((rest, ""));
+    (public, name.trim().to_string(), ty.trim().to_string())
+}
+
+fn compare_struct(o: &ApiItem, n: &ApiItem, mut push: impl FnMut(Level, String)) {
+    let of: BTreeMap<String, (bool, String)> =
+        o.members.iter().map(|m| field(m)).map(|(p, name, ty)| (name, (p, ty))).collect();
+    let nf: BTreeMap<String, (bool, String)> =
+        n.members.iter().map(|m| field(m)).map(|(p, name, ty)| (name, (p, ty))).collect();
+    // All-pub fields and not #[non_exhaustive]: users may build it with a literal.
+    let constructible = !o.non_exhaustive && of.values().all(|(p, _)| *p);
+
+    for (name, (was_pub, ty)) in &of {
+        match nf.get(name) {
+            None if *was_pub => push(Level::Major, format!("removed pub field `{name}`")),
+            Some((now_pub, _)) if *was_pub && !now_pub => {
+                push(Level::Major, format!("field `{name}` is no longer pub"));
+            }
+            Some((true, new_ty)) if *was_pub && new_ty != ty => {
+                push(Level::Major, format!("field `{name}` changed type: `{ty}` -> `{new_ty}`"));
+            }
+            _ => {}
+        }
+    }
+    for (name, (is_pub, _)) in nf.iter().filter(|(k, _)| !of.contains_key(*k)) {
+        if constructible {
+            push(Level::Major, format!("new field `{name}` on a struct constructible with a literal"));
+        } else if *is_pub {
+            push(Level::Minor, format!("added pub field `{name}`"));
+        }
+    }
+    if !o.non_exhaustive && n.non_exhaustive {
+        push(Level::Major, "became #[non_exhaustive]".into());
+    } else if o.non_exhaustive && !n.non_exhaustive {
+        push(Level::Minor, "no longer #[non_exhaustive]".into());
+    }
+}
+
+fn compare_enum(o: &ApiItem, n: &ApiItem, mut push: impl FnMut(Level, String)) {
+    for v in o.members.iter().filter(|v| !n.members.contains(v)) {
+        push(Level::Major, format!("removed variant `{v}`"));
+    }
+    for v in n.members.iter().filter(|v| !o.members.contains(v)) {
+        if o.non_exhaustive {
+            push(Level::Minor, format!("added variant `{v}` (enum is #[non_exhaustive])"));
+        } else {
+            push(Level::Major, format!("added variant `{v}` to an exhaustive enum"));
+        }
+    }
+    if !o.non_exhaustive && n.non_exhaustive {
+        push(Level::Major, "became #[non_exhaustive]".into());
+    } else if o.non_exhaustive && !n.non_exhaustive {
+        push(Level::Minor, "no longer #[non_exhaustive]".into());
+    }
+}
+
+/// Trait members: removing or changing one breaks callers, adding a required
+/// one breaks implementors, adding a provided one does not.
+fn compare_trait(o: &ApiItem, n: &ApiItem, mut push: impl FnMut(Level, String)) {
+    // Member -> has a default.
+    let items = |it: &ApiItem| -> BTreeMap<String, bool> {
+        it.members.iter().map(|m| m.strip_prefix("provided ").map_or((m.clone(), false), |d| (d.to_string(), true))).collect()
+    };
+    let (oi, ni) = (items(o), items(n));
+    for (m, had_default) in &oi {
+        match ni.get(m) {
+            None => push(Level::Major, format!("removed or changed trait item `{m}`")),
+            Some(false) if *had_default => push(Level::Major, format!("trait item `{m}` lost its default")),
+            _ => {}
+        }
+    }
+    for (m, provided) in ni.iter().filter(|(m, _)| !oi.contains_key(*m)) {
+        if *provided {
+            push(Level::Minor, format!("added provided trait item `{m}`"));
+        } else {
+            push(Level::Major, format!("added required trait item `{m}` (breaks implementors)"));
+        }
+    }
+}
+
+/* =============================== Resolution =============================== */
+
+fn item_of(s: &Symbol, module: &str) -> ApiItem {
+    ApiItem {
+        kind: s.kind.clone(),
+        sig: s.sig.clone(),
+        members: s.members.clone(),
+        non_exhaustive: s.attrs.iter().any(|a| a == "non_exhaustive"),
+        origin: s.name.rsplit("::").next().unwrap_or(&s.name).to_string(),
+        module: module.to_string(),
+    }
+}
+
+fn join(prefix: &str, name: &str) -> String {
+    if prefix.is_empty() { name.to_string() } else { format!("{prefix}::{name}") }
+}
+
+fn child(module: &str, name: &str) -> String {
+    if module == "crate" { name.to_string() } else { format!("{module}::{name}") }
+}
+
+fn parent(module: &str) -> String {
+    match module.rsplit_once("::") {
+        Some((p, _)) => p.to_string(),
+        None => "crate".to_string(),
+    }
+}
+
+/// Resolve `use` path segments (minus the last) to a crate module id.
+/// `None` for paths into other crates.
+fn resolve_module(modules: &BTreeMap<String, Vec<&Symbol>>, from: &str, segs: &[&str]) -> Option<String> {
+    let mut cur;
+    let mut rest = segs;
+    match segs.first().copied() {
+        Some("crate") => {
+            cur = "crate".to_string();
+            rest = &segs[1..];
+        }
+        Some("self") => {
+            cur = from.to_string();
+            rest = &segs[1..];
+        }
+        Some("super") => {
+            cur = from.to_string();
+            while rest.first() == Some(&"super") {
+                cur = parent(&cur);
+                rest = &rest[1..];
+            }
+        }
+        // 2018 uniform paths: relative to the current module first.
+        Some(first) if modules.contains_key(&child(from, first)) => cur = from.to_string(),
+        Some(_) => return None,
+        None => return Some(from.to_string()),
+    }
+    for seg in rest {
+        cur = child(&cur, seg);
+        if !modules.contains_key(&cur) {
+            return None;
+        }
+    }
+    Some(cur)
+}
+
+fn reexport(
+    modules: &BTreeMap<String, Vec<&Symbol>>,
+    module: &str,
+    public: &str,
+    alias: &str,
+    target: &str,
+    surface: &mut ApiSurface,
+    queue: &mut VecDeque<(String, String)>,
+) {
+    let segs: Vec<&str> = target.split("::").collect();
+    if alias == "*" {
+        // Glob: every pub item (and pub mod) of the target module appears here.
+        if let Some(src) = resolve_module(modules, module, &segs) {
+            queue.push_back((src, public.to_string()));
+        }
+        return;
+    }
+    let (last, init) = segs.split_last().map_or(("", &[][..]), |(l, i)| (*l, i));
+    let Some(src) = resolve_module(modules, module, init) else {
+        // Re-export from another crate: tracked by path only.
+        surface.insert(
+            join(public, alias),
+            ApiItem {
+                kind: "reexport".into(),
+                sig: Some(target.to_string()),
+                members: Vec::new(),
+                non_exhaustive: false,
+                origin: alias.to_string(),
+                module: String::new(),
+            },
+        );
+        return;
+    };
+    // `pub use self::inner;` exposes a whole module, even a private one.
+    let as_module = child(&src, last);
+    if modules.contains_key(&as_module) {
+        queue.push_back((as_module, join(public, alias)));
+        return;
+    }
+    let Some(syms) = modules.get(&src) else { return };
+    if let Some(s) = syms.iter().find(|s| s.name == last && ITEM_KINDS.contains(&s.kind.as_str())) {
+        let mut it = item_of(s, &src);
+        it.origin = last.to_string();
+        surface.insert(join(public, alias), it);
+    } else if let Some(next) = syms.iter().find(|s| s.kind == "use" && s.name == last).and_then(|s| s.sig.clone()) {
+        // Re-export of a re-export: follow the chain from the intermediate module.
+        if next != target {
+            reexport(modules, &src, public, alias, &next, surface, queue);
+        }
+    }
+}
+
+/* ================================ Sources ================================ */
+
+fn worktree_symbols(root: &Path) -> Result<Vec<(String, Vec<Symbol>)>> {
+    let mut out = Vec::new();
+    for dent in ignore::WalkBuilder::new(root).build().filter_map(Result::ok) {
+        let path = dent.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("rs") || !path.is_file() {
+            continue;
+        }
+        let rel = path.strip_prefix(root).unwrap_or(path).to_string_lossy().replace('\\', "/");
+        let src = fs::read_to_string(path).with_context(|| format!("read {}", path.display()))?;
+        if let Some(syms) = rust_symbols(&src) {
+            out.push((rel, syms));
+        }
+    }
+    Ok(out)
+}
+
+fn git(root: &Path, args: &[&str]) -> Result<String> {
+    let out = Command::new("git")
+        .args(args)
+        .current_dir(root)
+        .output()
+        .with_context(|| format!("running git {}", args.join(" ")))?;
+    if !out.status.success() {
+        bail!("git {} failed: {}", args.join(" "), String::from_utf8_lossy(&out.stderr).trim());
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).into_owned())
+}
+
+/// Rust files of `rev` under `root` (paths relative to `root`).
+fn git_symbols(rev: &str, root: &Path) -> Result<Vec<(String, Vec<Symbol>)>> {
+    git(root, &["rev-parse", "--verify", "--quiet", &format!("{rev}^{{commit}}")])
+        .with_context(|| format!("`{rev}` is neither a snapshot file nor a git revision"))?;
+    let listing = git(root, &["ls-tree", "-r", "--name-only", rev, "--", "."])?;
+    let mut out = Vec::new();
+    for rel in listing.lines().filter(|p| p.ends_with(".rs")) {
+        let src = git(root, &["show", &format!("{rev}:./{rel}")])?;
+        if let Some(syms) = rust_symbols(&src) {
+            out.push((rel.to_string(), syms));
+        }
+    }
+    Ok(out)
+}
+
+/* ================================== Tests ================================== */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn surface(files: &[(&str, &str)]) -> ApiSurface {
+        let files: Vec<(String, Vec<Symbol>)> =
+            files.iter().map(|(p, src)| ((*p).to_string(), rust_symbols(src).unwrap())).collect();
+        api_surface(&files).unwrap()
+    }
+
+    #[test]
+    fn surface_follows_pub_mods_and_reexports() {
+        let s = surface(&[
+            ("src/lib.rs", "pub mod api;\nmod inner;\npub use inner::Hidden as Shown;\npub use inner::deep::*;\npub use serde::Serialize;\n"),
+            ("src/api.rs", "pub fn go() {}\npub(crate) fn internal() {}\npub struct W { pub a: u8 }\nimpl W { pub fn new() -> Self { todo!() } fn private(&self) {} }\n"),
+            ("src/inner/mod.rs", "pub mod deep;\npub struct Hidden;\npub struct NotReexported;\n"),
+            ("src/inner/deep.rs", "pub enum Mode { A }\n"),
+        ]);
+        let keys: Vec<&str> = s.keys().map(String::as_str).collect();
+        assert_eq!(keys, ["Mode", "Serialize", "Shown", "api::W", "api::W::new", "api::go"]);
+        assert_eq!(s["Shown"].origin, "Hidden");
+        assert_eq!(s["Serialize"].kind, "reexport");
+    }
+
+    #[test]
+    fn classification_rules() {
+        let old = surface(&[(
+            "src/lib.rs",
+            "pub fn a(x: u8) {}\npub fn gone() {}\npub struct Lit { pub x: u8 }\npub struct Opaque { pub x: u8, y: u8 }\npub enum E { A }\n#[non_exhaustive]\npub enum F { A }\n",
+        )]);
+        let new = surface(&[(
+            "src/lib.rs",
+            "pub fn a(x: u16) {}\npub fn fresh() {}\npub struct Lit { pub x: u8, pub z: u8 }\npub struct Opaque { pub x: u8, y: u8, pub w: u8 }\npub enum E { A, B }\n#[non_exhaustive]\npub enum F { A, B }\n",
+        )]);
+        let r = compare(&old, &new);
+        let level_of = |path: &str| -> Vec<Level> {
+            r.changes.iter().filter(|c| c.path == path).map(|c| c.level).collect()
+        };
+        assert_eq!(r.level, Level::Major);
+        assert_eq!(level_of("a"), [Level::Major]);
+        assert_eq!(level_of("gone"), [Level::Major]);
+        assert_eq!(level_of("fresh"), [Level::Minor]);
+        assert_eq!(level_of("Lit"), [Level::Major]);
+        assert_eq!(level_of("Opaque"), [Level::Minor]);
+        assert_eq!(level_of("E"), [Level::Major]);
+        assert_eq!(level_of("F"), [Level::Minor]);
+    }
+
+    #[test]
+    fn surface_tracks_inline_modules_visibility_and_method_owners() {
+        let s = surface(&[
+            (
+                "src/lib.rs",
+                "pub mod a;\nmod hidden { pub fn leak() {} pub struct Secret; }\n\
+                 pub mod open { pub struct W; impl W { pub fn m() {} } pub trait T { fn r(&self); }\n\
+                 pub const C: u8 = 1; pub static S: u8 = 1; pub type A = u8; pub union U { pub x: u8 } }\n\
+                 #[cfg(test)]\nmod tests { pub fn helper() {} }\nfn private() {}\n",
+            ),
+            ("src/a.rs", "pub struct W;\nimpl W { pub fn a_only() {} }\nmod sub { impl super::W { pub fn via_super() {} } }\n"),
+        ]);
+        let keys: Vec<&str> = s.keys().map(String::as_str).collect();
+        assert_eq!(
+            keys,
+            ["a::W", "a::W::a_only", "a::W::via_super", "open::A", "open::C", "open::S", "open::T", "open::U", "open::W", "open::W::m"]
+        );
+
+        let t = |body: &str| surface(&[("src/lib.rs", &format!("pub trait T {{ fn r(&self); {body} }}\n"))]);
+        let level = |new: &str| compare(&t(""), &t(new)).level;
+        assert_eq!(level("fn p(&self) {}"), Level::Minor);
+        assert_eq!(level("fn q(&self);"), Level::Major);
+    }
+
+    #[test]
+    fn identical_surfaces_are_patch() {
+        let s = surface(&[("src/lib.rs", "pub fn a() {}\n")]);
+        let r = compare(&s, &s);
+        assert_eq!(r.level, Level::Patch);
+        assert!(r.changes.is_empty());
+    }
+}
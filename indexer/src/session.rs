@@ -0,0 +1,247 @@
+// indexer/src/session.rs
+//! Delivery tracking per LLM conversation ("session").
+//!
+//! - `.gpt_index/sessions/<name>.json` maps each delivered path to the sha1 it
+//!   had when delivered, plus which of its chunk parts were seen.
+//! - A file counts as seen while its current sha1 matches and every part was
+//!   delivered; `next` packs one chunk of everything else.
+//! - Chunks resolve to (path, sha1, part) through `chunks/manifest.json`.
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    chunker::{self, ChunkManifest, ChunkOptions, ManifestPart},
+    util,
+};
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    pub name: String,
+    pub created: String,
+    /// Number of `next` chunks emitted so far (names the next chunk file).
+    pub issued: usize,
+    /// Path -> what the conversation has of it.
+    pub delivered: BTreeMap<String, Delivered>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Delivered {
+    /// sha1 of the file when (the parts below were) delivered.
+    pub sha1: String,
+    /// 1-based part numbers seen; the file is complete when `parts.len() == of`.
+    pub parts: BTreeSet<usize>,
+    pub of: usize,
+    pub at: String,
+}
+
+impl Delivered {
+    fn complete(&self) -> bool {
+        self.parts.len() >= self.of.max(1)
+    }
+}
+
+impl Session {
+    /// Sessions dir under an index dir (`.gpt_index/sessions`).
+    pub fn dir(index_dir: &Path) -> PathBuf {
+        index_dir.join("sessions")
+    }
+
+    pub fn path(index_dir: &Path, name: &str) -> PathBuf {
+        Self::dir(index_dir).join(format!("{name}.json"))
+    }
+
+    /// Create a new, empty session; fails if it already exists.
+    pub fn create(index_dir: &Path, name: &str) -> Result<Self> {
+        check_name(name)?;
+        let path = Self::path(index_dir, name);
+        if path.exists() {
+            bail!("session `{name}` already exists ({})", path.display());
+        }
+        let s = Session { name: name.to_string(), created: util::now_timestamp(), ..Default::default() };
+        s.save(index_dir)?;
+        Ok(s)
+    }
+
+    pub fn load(index_dir: &Path, name: &str) -> Result<Self> {
+        check_name(name)?;
+        let path = Self::path(index_dir, name);
+        let raw = fs::read_to_string(&path)
+            .with_context(|| format!("session `{name}` not found (create it with `indexer session new {name}`)"))?;
+        serde_json::from_str(&raw).with_context(|| format!("parsing {}", path.display()))
+    }
+
+    pub fn save(&self, index_dir: &Path) -> Result<()> {
+        let path = Self::path(index_dir, &self.name);
+        util::safe_write(&path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("writing {}", path.display()))
+    }
+
+    /// True when the conversation already has this exact version of `path`.
+    pub fn has_seen(&self, path: &str, sha1: &str) -> bool {
+        self.delivered.get(path).is_some_and(|d| d.sha1 == sha1 && d.complete())
+    }
+
+    /// Record delivered parts. A part of a newer sha1 restarts that file's record.
+    pub fn mark_parts<'a>(&mut self, parts: impl IntoIterator<Item = &'a ManifestPart>) -> usize {
+        let now = util::now_timestamp();
+        let mut n = 0;
+        for p in parts {
+            let d = self.delivered.entry(p.path.clone()).or_default();
+            if d.sha1 != p.sha1 {
+                *d = Delivered { sha1: p.sha1.clone(), ..Default::default() };
+            }
+            d.parts.insert(p.part);
+            d.of = p.of;
+            d.at.clone_from(&now);
+            n += 1;
+        }
+        n
+    }
+}
+
+/// Session names become file names: keep them to `[A-Za-z0-9_.-]`.
+fn check_name(name: &str) -> Result<()> {
+    let ok = !name.is_empty()
+        && !name.starts_with('.')
+        && name.bytes().all(|b| b.is_ascii_alphanumeric() || matches!(b, b'_' | b'-' | b'.'));
+    if ok {
+        Ok(())
+    } else {
+        Err(anyhow!("bad session name `{name}` (use letters, digits, `_`, `-`, `.`)"))
+    }
+}
+
+/// Parts a `mark` target stands for: a chunk file (path or bare name) known to
+/// `chunks/manifest.json`, else an indexed path marked whole at `sha1_of(path)`.
+pub fn resolve_target(
+    target: &str,
+    chunks_dir: &Path,
+    sha1_of: &dyn Fn(&str) -> Option<String>,
+) -> Result<Vec<ManifestPart>> {
+    let manifest = ChunkManifest::load(chunks_dir);
+    let file_name = Path::new(target).file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    if let Some(parts) = manifest.chunks.get(&file_name) {
+        return Ok(parts.clone());
+    }
+    let rel = target.trim_start_matches("./").replace('\\', "/");
+    match sha1_of(&rel) {
+        Some(sha1) => Ok(vec![ManifestPart { path: rel, sha1, part: 1, of: 1 }]),
+        None => bail!(
+            "`{target}` is neither a chunk listed in {} nor an indexed path",
+            chunks_dir.join(chunker::MANIFEST_FILE).display()
+        ),
+    }
+}
+
+/// Outcome of [`next_chunk`].
+#[derive(Debug)]
+pub struct NextChunk {
+    /// Written chunk file; `None` when everything was already delivered.
+    pub file: Option<PathBuf>,
+    pub parts: Vec<ManifestPart>,
+    /// Unseen files still waiting after this chunk.
+    pub remaining_files: usize,
+}
+
+/// Write the next chunk of content `session` has not seen (or whose sha1
+/// changed) to `<chunks_dir>/<slug>_session_<name>_<N>.<ext>` and record it in
+/// the chunk manifest. The session itself is only updated by `mark`.
+pub fn next_chunk(
+    session: &mut Session,
+    index_file: &Path,
+    chunks_dir: &Path,
+    slug: &str,
+    opts: &ChunkOptions,
+) -> Result<NextChunk> {
+    let plan = chunker::plan_chunks_filtered(index_file, opts, &|path, sha1| !session.has_seen(path, sha1))?;
+    let Some(first) = plan.first() else {
+        return Ok(NextChunk { file: None, parts: Vec::new(), remaining_files: 0 });
+    };
+
+    // Files split across chunks 1 and 2 still count as waiting.
+    let remaining_files = plan.iter().skip(1).flatten().map(|p| p.path.as_str()).collect::<BTreeSet<_>>().len();
+
+    fs::create_dir_all(chunks_dir).with_context(|| format!("creating {}", chunks_dir.display()))?;
+    let n = session.issued + 1;
+    let prefix = chunks_dir.join(format!("{slug}_session_{}_", session.name));
+    let prefix = prefix.to_str().ok_or_else(|| anyhow!("non-utf8 path: {}", prefix.display()))?;
+    chunker::write_chunk(prefix, n, first, opts.format, None)?;
+    chunker::record_chunks_from(prefix, n, opts.format, std::slice::from_ref(first), false)?;
+    session.issued = n;
+
+    Ok(NextChunk {
+        file: Some(chunks_dir.join(format!("{slug}_session_{}_{n}.{}", session.name, opts.format.ext()))),
+        parts: first
+            .iter()
+            .map(|p| ManifestPart { path: p.path.clone(), sha1: p.sha1.clone(), part: p.part_idx, of: p.part_total })
+            .collect(),
+        remaining_files,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_intent_entry::FileIntentEntry;
+
+    fn write_index(path: &Path, files: &[(&str, &str)]) {
+        let lines: Vec<String> = files
+            .iter()
+            .map(|(p, sha1)| {
+                let e = FileIntentEntry {
+                    path: (*p).into(),
+                    lang: "rust".into(),
+                    sha1: (*sha1).into(),
+                    snippet: format!("fn in_{}() {{}}", sha1),
+                    ..Default::default()
+                };
+                serde_json::to_string(&e).unwrap()
+            })
+            .collect();
+        fs::write(path, lines.join("\n")).unwrap();
+    }
+
+    #[test]
+    fn next_skips_seen_files_until_their_sha_changes() {
+        let dir = std::env::temp_dir().join(format!("indexer-session-test-{}", std::process::id()));
+        let chunks = dir.join("chunks");
+        fs::create_dir_all(&chunks).unwrap();
+        let index = dir.join("index.jsonl");
+        write_index(&index, &[("src/a.rs", "a1"), ("src/b.rs", "b1")]);
+
+        let mut s = Session::create(&dir, "review").unwrap();
+        assert!(Session::create(&dir, "review").is_err());
+        assert!(Session::create(&dir, "../x").is_err());
+
+        let sha1_of = |p: &str| (p == "src/a.rs").then(|| "a1".to_string());
+        let parts = resolve_target("./src/a.rs", &chunks, &sha1_of).unwrap();
+        s.mark_parts(&parts);
+        s.save(&dir).unwrap();
+
+        let opts = ChunkOptions::default();
+        let next = next_chunk(&mut s, &index, &chunks, "proj", &opts).unwrap();
+        let file = next.file.unwrap();
+        assert!(file.ends_with("proj_session_review_1.md"));
+        assert_eq!(next.parts.iter().map(|p| p.path.as_str()).collect::<Vec<_>>(), ["src/b.rs"]);
+
+        // Marking the emitted chunk by file name resolves through the manifest.
+        let parts = resolve_target(file.to_str().unwrap(), &chunks, &|_| None).unwrap();
+        s.mark_parts(&parts);
+        assert!(next_chunk(&mut s, &index, &chunks, "proj", &opts).unwrap().file.is_none());
+
+        write_index(&index, &[("src/a.rs", "a2"), ("src/b.rs", "b1")]);
+        let again = next_chunk(&mut s, &index, &chunks, "proj", &opts).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+        assert_eq!(again.parts[0].path, "src/a.rs");
+        assert_eq!(again.parts[0].sha1, "a2");
+        assert!(again.file.unwrap().ends_with("proj_session_review_2.md"));
+    }
+}
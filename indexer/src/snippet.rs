@@ -1,5 +1,7 @@
 // indexer/src/snippet.rs
 
+use crate::lang::LanguageAnalyzer;
+
 // Scan/keep limits — tuned for high signal without dragging the whole file.
 const MAX_SCAN_BYTES: usize = 32 * 1024;  // only read the head window
 const MAX_SCAN_LINES: usize = 800;        // and line-cap within that window
@@ -22,8 +24,10 @@ pub fn extract_relevant_snippet(content: &str, lang: &str) -> String {
     };
 
     // Try a leading doc/comment block first.
+    let analyzer = crate::lang::analyzer(lang);
+    let analyzer = analyzer.as_deref();
     let mut out: Vec<String> = Vec::with_capacity(MAX_KEEP_LINES);
-    if let Some(doc) = leading_doc_block(head, lang) {
+    if let Some(doc) = leading_doc_block(head, analyzer) {
         push_lines(&mut out, doc);
         if out.len() >= MAX_KEEP_LINES {
             return join(&out);
@@ -40,7 +44,7 @@ pub fn extract_relevant_snippet(content: &str, lang: &str) -> String {
         let raw = lines[i];
         let line = raw.trim();
         if !line.is_empty() {
-            let score = score_line(line, lang);
+            let score = score_line(line, analyzer);
             if score > 0 {
                 // Keep this line (original order + trimmed end to keep indentation)
                 kept.push((i, raw.trim_end().to_string()));
@@ -82,20 +86,16 @@ pub fn extract_relevant_snippet(content: &str, lang: &str) -> String {
 
 /* ------------------------- scoring + helpers ------------------------- */
 
-fn score_line(l: &str, lang: &str) -> u8 {
-    let ll = l.to_ascii_lowercase();
-    match lang.to_ascii_lowercase().as_str() {
-        "rust" => score_rust(l, &ll),
-        "python" => score_python(l, &ll),
-        "typescript" | "ts" | "javascript" | "js" => score_js_ts(l, &ll),
-        "go" => score_go(l, &ll),
-        "toml" | "yaml" | "yml" | "json" => score_config(l, &ll),
-        "markdown" | "md" => score_md(l, &ll),
-        _ => score_generic(l, &ll),
+/// Per-language scorers live behind `lang::LanguageAnalyzer`; unknown
+/// languages get the generic one.
+fn score_line(l: &str, analyzer: Option<&dyn LanguageAnalyzer>) -> u8 {
+    match analyzer {
+        Some(a) => a.score_line(l),
+        None => score_generic(l, &l.to_ascii_lowercase()),
     }
 }
 
-fn score_rust(l: &str, ll: &str) -> u8 {
+pub(crate) fn score_rust(l: &str, ll: &str) -> u8 {
     if l.starts_with("///") || l.starts_with("//!") { return 9; }                     // docs
     if l.starts_with("pub ") {
         if l.starts_with("pub use ") { return 5; }                                    // re-export
@@ -111,7 +111,7 @@ fn score_rust(l: &str, ll: &str) -> u8 {
     0
 }
 
-fn score_python(l: &str, ll: &str) -> u8 {
+pub(crate) fn score_python(l: &str, ll: &str) -> u8 {
     if l.starts_with("\"\"\"") || l.starts_with("'''") || l.starts_with("#!") || l.starts_with("# ") { return 9; } // docs/shebang
     if l.starts_with("def ") || l.starts_with("class ") { return 8; }                         // API surface
     if l.starts_with("import ") || l.starts_with("from ") { return 3; }                       // imports
@@ -120,7 +120,7 @@ fn score_python(l: &str, ll: &str) -> u8 {
     0
 }
 
-fn score_js_ts(l: &str, _ll: &str) -> u8 {
+pub(crate) fn score_js_ts(l: &str, _ll: &str) -> u8 {
     if l.starts_with("/**") || l.starts_with("* ") || l.starts_with("//") { return 8; }       // docs/comments
     if l.starts_with("export ") { return 8; }                                                 // public surface
     if l.starts_with("import ") { return 3; }                                                 // imports
@@ -128,7 +128,7 @@ fn score_js_ts(l: &str, _ll: &str) -> u8 {
     0
 }
 
-fn score_go(l: &str, _ll: &str) -> u8 {
+pub(crate) fn score_go(l: &str, _ll: &str) -> u8 {
     if l.starts_with("//") { return 7; }
     if l.starts_with("package ") { return 5; }
     if l.starts_with("import ") { return 3; }
@@ -136,17 +136,17 @@ fn score_go(l: &str, _ll: &str) -> u8 {
     0
 }
 
-fn score_config(l: &str, _ll: &str) -> u8 {
+pub(crate) fn score_config(l: &str, _ll: &str) -> u8 {
     if l.starts_with('[') || l.contains(": ") || l.contains(" = ") { return 5; }
     0
 }
 
-fn score_md(l: &str, _ll: &str) -> u8 {
+pub(crate) fn score_md(l: &str, _ll: &str) -> u8 {
     if l.starts_with("# ") || l.starts_with("## ") { return 8; }
     0
 }
 
-fn score_generic(l: &str, _ll: &str) -> u8 {
+pub(crate) fn score_generic(l: &str, _ll: &str) -> u8 {
     if l.starts_with("//") || l.starts_with("#") || l.starts_with("--") { return 6; }         // comments
     if l.contains("class ") || l.starts_with("def ") || l.starts_with("fn ") { return 5; }    // API-ish
     if l.starts_with("import ") || l.starts_with("using ") { return 3; }                      // imports
@@ -155,17 +155,14 @@ fn score_generic(l: &str, _ll: &str) -> u8 {
 
 /* ------------------------ leading doc-block detection ------------------------ */
 
-fn leading_doc_block(s: &str, lang: &str) -> Option<Vec<String>> {
-    match lang.to_ascii_lowercase().as_str() {
-        "rust"   => leading_rust_docs(s),
-        "python" => leading_python_docs(s),
-        "typescript" | "ts" | "javascript" | "js" => leading_js_docs(s),
-        "markdown" | "md" => leading_md_head(s),
-        _ => leading_generic_head(s),
+fn leading_doc_block(s: &str, analyzer: Option<&dyn LanguageAnalyzer>) -> Option<Vec<String>> {
+    match analyzer {
+        Some(a) => a.leading_doc(s),
+        None => leading_generic_head(s),
     }
 }
 
-fn leading_rust_docs(s: &str) -> Option<Vec<String>> {
+pub(crate) fn leading_rust_docs(s: &str) -> Option<Vec<String>> {
     let mut out = Vec::new();
     let mut started = false;
     for line in s.lines().take(120) {
@@ -190,7 +187,7 @@ fn leading_rust_docs(s: &str) -> Option<Vec<String>> {
     normalize_doc_opt(out)
 }
 
-fn leading_python_docs(s: &str) -> Option<Vec<String>> {
+pub(crate) fn leading_python_docs(s: &str) -> Option<Vec<String>> {
     let mut out = Vec::new();
     let mut in_triple = false;
     let mut triple_quote: Option<&str> = None;
@@ -234,7 +231,7 @@ fn leading_python_docs(s: &str) -> Option<Vec<String>> {
     normalize_doc_opt(out)
 }
 
-fn leading_js_docs(s: &str) -> Option<Vec<String>> {
+pub(crate) fn leading_js_docs(s: &str) -> Option<Vec<String>> {
     let mut out = Vec::new();
     let mut in_block = false;
     for line in s.lines().take(160) {
@@ -272,7 +269,7 @@ fn leading_js_docs(s: &str) -> Option<Vec<String>> {
     normalize_doc_opt(out)
 }
 
-fn leading_md_head(s: &str) -> Option<Vec<String>> {
+pub(crate) fn leading_md_head(s: &str) -> Option<Vec<String>> {
     let mut out = Vec::new();
     for line in s.lines().take(60) {
         let t = line.trim();
@@ -292,7 +289,7 @@ fn leading_md_head(s: &str) -> Option<Vec<String>> {
     normalize_doc_opt(out)
 }
 
-fn leading_generic_head(s: &str) -> Option<Vec<String>> {
+pub(crate) fn leading_generic_head(s: &str) -> Option<Vec<String>> {
     let mut out = Vec::new();
     for line in s.lines().take(40) {
         let t = line.trim();
@@ -0,0 +1,550 @@
+// indexer/src/symbols.rs
+//! Per-file Rust symbol table stored in the JSONL index, and symbol-level deltas.
+//!
+//! - Collected at scan time with the `types_view` / `functions_view` syn collectors,
+//!   item by item, descending into inline `mod { .. }` blocks (`module` records
+//!   the path); test fns and `#[cfg(test)]` modules are skipped.
+//! - Kinds: `fn`, `method` (`Type::name`), `struct` / `union` (members =
+//!   fields), `enum` (members = variants), `trait` (members = items), `const`,
+//!   `static`, `type` (sig = declaration), plus `mod` declarations and `pub use`
+//!   re-exports (sig = imported path) so the public API can be rebuilt offline.
+//! - `diff_symbols` reports added/removed items, changed signatures and
+//!   field/variant changes; `diff::diff_indexes` attaches it to modified files.
+
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use std::collections::{BTreeMap, BTreeSet};
+use syn::visit::Visit;
+
+use crate::{
+    functions_view::{norm_sig, FnCollector, Kind},
+    types_view::{norm_tokens, Decl, TypeCollector},
+};
+
+/* ================================= Model ================================= */
+
+/// One top-level item (or method) of a Rust file.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct Symbol {
+    /// `fn` | `method` | `struct` | `enum` | `union` | `trait` | `const` |
+    /// `static` | `type` | `mod` | `use`
+    pub kind: String,
+    /// Item name; methods are qualified as `Type::name`.
+    pub name: String,
+    pub public: bool,
+    /// Inline module the item sits in, relative to the file (`inner::deep`);
+    /// empty at file level.
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub module: String,
+    /// Methods only: the impl's self type when written as a path
+    /// (`super::Widget`); otherwise the type is `name`'s prefix in `module`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    /// Methods of trait impls: the trait as written (`fmt::Display`).
+    #[serde(rename = "trait", skip_serializing_if = "Option::is_none")]
+    pub trait_: Option<String>,
+    /// Normalized signature (fns/methods); imported path for `use`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sig: Option<String>,
+    /// Struct fields (`name: Ty`, tuple fields `0: Ty`) or enum variant names.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub members: Vec<String>,
+    /// API-relevant attributes (currently `non_exhaustive`).
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub attrs: Vec<String>,
+    /// Short sha1 of the item's source lines (docs/attrs included) for
+    /// structs, enums, fns and methods; spots body-only edits. Not part of
+    /// `diff_symbols`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+    /// 1-based inclusive line span (docs/attrs included) in the parsed source.
+    #[serde(skip)]
+    pub(crate) lines: Option<(usize, usize)>,
+}
+
+impl Symbol {
+    /// `name`, prefixed with the inline module path when there is one.
+    pub fn qualified(&self) -> String {
+        if self.module.is_empty() { self.name.clone() } else { format!("{}::{}", self.module, self.name) }
+    }
+
+    /// `kind` + qualified path, trait methods as `<Type as Trait>::name`
+    /// (`method <inner::A as fmt::Display>::fmt`). Not unique on its own: see [`keyed`].
+    pub(crate) fn key(&self) -> String {
+        let path = self.qualified();
+        match (&self.trait_, path.rsplit_once("::")) {
+            (Some(t), Some((ty, name))) => format!("{} <{ty} as {t}>::{name}", self.kind),
+            _ => format!("{} {path}", self.kind),
+        }
+    }
+}
+
+/// Symbols with unique keys: [`Symbol::key`], plus `#2`, `#3` .. in source
+/// order when it repeats (cfg-gated twins of one item). Item hashes and
+/// spans are matched by these keys.
+pub(crate) fn keyed(symbols: &[Symbol]) -> Vec<(String, &Symbol)> {
+    let mut seen: BTreeMap<String, usize> = BTreeMap::new();
+    symbols
+        .iter()
+        .map(|s| {
+            let key = s.key();
+            let n = seen.entry(key.clone()).or_default();
+            *n += 1;
+            (if *n == 1 { key } else { format!("{key}#{n}") }, s)
+        })
+        .collect()
+}
+
+/// One symbol-level change inside a modified file.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct SymbolChange {
+    /// `added` | `removed` | `signature` | `fields` | `variants`
+    pub change: &'static str,
+    pub kind: String,
+    pub name: String,
+    pub public: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub before: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after: Option<String>,
+    /// Fields/variants added (for `fields` / `variants`).
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub added: Vec<String>,
+    /// Fields/variants removed (for `fields` / `variants`).
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub removed: Vec<String>,
+    /// Fields whose type/visibility changed: `before -> after`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub retyped: Vec<String>,
+}
+
+/* =============================== Collection =============================== */
+
+/// Symbols of a Rust source file; `None` when it does not parse.
+pub fn rust_symbols(src: &str) -> Option<Vec<Symbol>> {
+    let ast = syn::parse_file(src).ok()?;
+    let mut out = Vec::new();
+    collect(&ast.items, "", &mut out);
+
+    // Each item hashes its own span, so same-named items never share a hash.
+    let lines: Vec<&str> = src.lines().collect();
+    for s in out.iter_mut().filter(|s| hashed(s)) {
+        let Some((a, b)) = s.lines else { continue };
+        let body = lines.get(a.saturating_sub(1)..b.min(lines.len())).unwrap_or_default().join("\n");
+        s.hash = Some(hex::encode(&Sha1::digest(body.as_bytes())[..6]));
+    }
+    Some(out)
+}
+
+/// Kinds that carry an item hash.
+pub(crate) fn hashed(s: &Symbol) -> bool {
+    matches!(s.kind.as_str(), "struct" | "enum" | "fn" | "method")
+}
+
+/// Symbols of `items`, which sit in inline module `module` ("" for the file).
+fn collect(items: &[syn::Item], module: &str, out: &mut Vec<Symbol>) {
+    let sym = |kind: &str, name: String, public: bool| Symbol {
+        kind: kind.into(),
+        name,
+        public,
+        module: module.to_string(),
+        ..Symbol::default()
+    };
+    let is_pub = |vis: &syn::Visibility| matches!(vis, syn::Visibility::Public(_));
+    for item in items {
+        match item {
+            syn::Item::Struct(syn::ItemStruct { attrs, .. }) | syn::Item::Enum(syn::ItemEnum { attrs, .. }) => {
+                let mut types = TypeCollector::default();
+                types.visit_item(item);
+                for d in types.out {
+                    out.push(match d {
+                        Decl::Struct(s) => Symbol {
+                            members: s.fields.iter().enumerate().map(|(i, f)| member(f.public, f.name.as_deref(), i, &f.ty)).collect(),
+                            lines: Some(line_span(item)),
+                            ..sym("struct", s.name, s.public)
+                        },
+                        Decl::Enum(e) => Symbol { members: e.variants, lines: Some(line_span(item)), ..sym("enum", e.name, e.public) },
+                    });
+                }
+                if let Some(s) = out.last_mut().filter(|_| attrs.iter().any(|a| a.path().is_ident("non_exhaustive"))) {
+                    s.attrs.push("non_exhaustive".into());
+                }
+            }
+            syn::Item::Union(u) => out.push(Symbol {
+                members: u
+                    .fields
+                    .named
+                    .iter()
+                    .enumerate()
+                    .map(|(i, f)| member(is_pub(&f.vis), f.ident.as_ref().map(ToString::to_string).as_deref(), i, &norm_tokens(&f.ty)))
+                    .collect(),
+                ..sym("union", u.ident.to_string(), is_pub(&u.vis))
+            }),
+            syn::Item::Fn(_) | syn::Item::Impl(_) => fn_symbols(item, module, out),
+            syn::Item::Trait(t) => out.push(Symbol {
+                members: t.items.iter().filter_map(trait_member).collect(),
+                ..sym("trait", t.ident.to_string(), is_pub(&t.vis))
+            }),
+            syn::Item::Const(c) => out.push(Symbol {
+                sig: Some(format!("const {}: {}", c.ident, norm_tokens(&c.ty))),
+                ..sym("const", c.ident.to_string(), is_pub(&c.vis))
+            }),
+            syn::Item::Static(st) => out.push(Symbol {
+                sig: Some(format!(
+                    "static {}{}: {}",
+                    if matches!(st.mutability, syn::StaticMutability::Mut(_)) { "mut " } else { "" },
+                    st.ident,
+                    norm_tokens(&st.ty)
+                )),
+                ..sym("static", st.ident.to_string(), is_pub(&st.vis))
+            }),
+            syn::Item::Type(t) => out.push(Symbol {
+                sig: Some(format!("type {}{} = {}", t.ident, norm_tokens(&t.generics), norm_tokens(&t.ty))),
+                ..sym("type", t.ident.to_string(), is_pub(&t.vis))
+            }),
+            syn::Item::Mod(m) if !is_cfg_test(&m.attrs) => {
+                out.push(sym("mod", m.ident.to_string(), is_pub(&m.vis)));
+                if let Some((_, inner)) = &m.content {
+                    let path = if module.is_empty() { m.ident.to_string() } else { format!("{module}::{}", m.ident) };
+                    collect(inner, &path, out);
+                }
+            }
+            syn::Item::Use(u) if is_pub(&u.vis) => {
+                let mut leaves = Vec::new();
+                flatten_use(&u.tree, String::new(), &mut leaves);
+                out.extend(leaves.into_iter().map(|(name, path)| Symbol { sig: Some(path), ..sym("use", name, true) }));
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Free fns and methods of one `fn` / `impl` item (tests skipped).
+fn fn_symbols(item: &syn::Item, module: &str, out: &mut Vec<Symbol>) {
+    // The impl's self type as written, when it is more than a bare name.
+    let (owner, trait_, spans) = match item {
+        syn::Item::Impl(imp) => (
+            match &*imp.self_ty {
+                syn::Type::Path(p) if p.path.segments.len() > 1 => {
+                    Some(p.path.segments.iter().map(|s| s.ident.to_string()).collect::<Vec<_>>().join("::"))
+                }
+                _ => None,
+            },
+            imp.trait_.as_ref().map(|(bang, path, _)| format!("{}{}", if bang.is_some() { "!" } else { "" }, norm_tokens(path))),
+            imp.items.iter().filter(|i| matches!(i, syn::ImplItem::Fn(_))).map(line_span).collect(),
+        ),
+        _ => (None, None, vec![line_span(item)]),
+    };
+    // The collector yields one entry per fn, in source order.
+    let mut fns = FnCollector::default();
+    fns.visit_item(item);
+    for ((kind, rendered), lines) in fns.out.into_iter().zip(spans) {
+        if matches!(kind, Kind::Test) {
+            continue;
+        }
+        let sig = rendered.trim_end_matches(" {").to_string();
+        let Some(name) = fn_name(&sig) else { continue };
+        // Methods come back as `Type::fn ..` / `Type::pub fn ..`.
+        let ty = sig.split_once("::").filter(|(head, _)| !head.contains(' ')).map(|(t, _)| t);
+        let (kind_s, name, owner, trait_) = match ty {
+            Some(t) if sig.starts_with(&format!("{t}::fn")) || sig.starts_with(&format!("{t}::pub fn")) => {
+                ("method", format!("{t}::{name}"), owner.clone(), trait_.clone())
+            }
+            _ => ("fn", name, None, None),
+        };
+        out.push(Symbol {
+            kind: kind_s.into(),
+            name,
+            public: matches!(kind, Kind::Public),
+            module: module.to_string(),
+            owner,
+            trait_,
+            sig: Some(sig),
+            lines: Some(lines),
+            ..Symbol::default()
+        });
+    }
+}
+
+/// `pub name: Ty`, or `i: Ty` for tuple fields.
+fn member(public: bool, name: Option<&str>, i: usize, ty: &str) -> String {
+    let vis = if public { "pub " } else { "" };
+    match name {
+        Some(n) => format!("{vis}{n}: {ty}"),
+        None => format!("{vis}{i}: {ty}"),
+    }
+}
+
+/// Trait item as a member: its signature, prefixed `provided ` when it has a
+/// default (adding one of those does not break implementors).
+fn trait_member(item: &syn::TraitItem) -> Option<String> {
+    let (decl, provided) = match item {
+        syn::TraitItem::Fn(f) => (norm_sig(&f.sig), f.default.is_some()),
+        syn::TraitItem::Type(t) => (format!("type {}", t.ident), t.default.is_some()),
+        syn::TraitItem::Const(c) => (format!("const {}: {}", c.ident, norm_tokens(&c.ty)), c.default.is_some()),
+        _ => return None,
+    };
+    Some(if provided { format!("provided {decl}") } else { decl })
+}
+
+/// `a::{b::C, D as E, f::*}` -> [(C, a::b::C), (E, a::D), (*, a::f)].
+fn flatten_use(tree: &syn::UseTree, prefix: String, out: &mut Vec<(String, String)>) {
+    let join = |seg: &str| if prefix.is_empty() { seg.to_string() } else { format!("{prefix}::{seg}") };
+    match tree {
+        syn::UseTree::Path(p) => flatten_use(&p.tree, join(&p.ident.to_string()), out),
+        syn::UseTree::Name(n) => {
+            let name = n.ident.to_string();
+            // `use a::b::{self}` re-exports module `b` itself.
+            if name == "self" {
+                let last = prefix.rsplit("::").next().unwrap_or(&prefix).to_string();
+                out.push((last, prefix));
+            } else {
+                out.push((name.clone(), join(&name)));
+            }
+        }
+        syn::UseTree::Rename(r) => out.push((r.rename.to_string(), join(&r.ident.to_string()))),
+        syn::UseTree::Glob(_) => out.push(("*".into(), prefix)),
+        syn::UseTree::Group(g) => {
+            for t in &g.items {
+                flatten_use(t, prefix.clone(), out);
+            }
+        }
+    }
+}
+
+fn is_cfg_test(attrs: &[syn::Attribute]) -> bool {
+    attrs.iter().any(|a| {
+        a.path().is_ident("cfg")
+            && a.parse_args::<syn::Ident>().is_ok_and(|i| i == "test")
+    })
+}
+
+/// `... fn name<T>(..)` -> `name`.
+fn fn_name(sig: &str) -> Option<String> {
+    let at = sig.find("fn ")?;
+    let rest = sig[at + 3..].trim_start();
+    let end = rest.find(|c: char| !(c.is_alphanumeric() || c == '_')).unwrap_or(rest.len());
+    (end > 0).then(|| rest[..end].to_string())
+}
+
+/// 1-based inclusive line span (doc comments and attributes included) of each
+/// struct, enum, fn and method, under its [`keyed`] key (`method A::new`).
+/// Inline non-test modules are searched too. `None` when the source does not parse.
+pub fn rust_item_lines(src: &str) -> Option<Vec<(String, usize, usize)>> {
+    let syms = rust_symbols(src)?;
+    Some(keyed(&syms).into_iter().filter(|(_, s)| hashed(s)).filter_map(|(k, s)| s.lines.map(|(a, b)| (k, a, b))).collect())
+}
+
+fn line_span(node: &impl syn::spanned::Spanned) -> (usize, usize) {
+    let span = node.span();
+    (span.start().line, span.end().line)
+}
+
+/* ================================== Diff ================================== */
+
+/// Symbol-level delta between two versions of one file, sorted by (name, kind).
+pub fn diff_symbols(old: &[Symbol], new: &[Symbol]) -> Vec<SymbolChange> {
+    let group = |syms: &'_ [Symbol]| {
+        let mut m: BTreeMap<(String, String), Vec<Symbol>> = BTreeMap::new();
+        for s in syms {
+            m.entry((s.qualified(), s.kind.clone())).or_default().push(s.clone());
+        }
+        m
+    };
+    let old_g = group(old);
+    let new_g = group(new);
+    let keys: BTreeSet<&(String, String)> = old_g.keys().chain(new_g.keys()).collect();
+
+    let mut out = Vec::new();
+    for key in keys {
+        let before = old_g.get(key).map(Vec::as_slice).unwrap_or_default();
+        let after = new_g.get(key).map(Vec::as_slice).unwrap_or_default();
+        match (before, after) {
+            ([], added) => out.extend(added.iter().map(|s| whole(s, "added"))),
+            (removed, []) => out.extend(removed.iter().map(|s| whole(s, "removed"))),
+            ([b], [a]) => out.extend(compare_one(b, a)),
+            // Same name several times (trait impls): diff by signature set.
+            (bs, as_) => {
+                let bsigs: BTreeSet<&Option<String>> = bs.iter().map(|s| &s.sig).collect();
+                let asigs: BTreeSet<&Option<String>> = as_.iter().map(|s| &s.sig).collect();
+                out.extend(bs.iter().filter(|s| !asigs.contains(&s.sig)).map(|s| whole(s, "removed")));
+                out.extend(as_.iter().filter(|s| !bsigs.contains(&s.sig)).map(|s| whole(s, "added")));
+            }
+        }
+    }
+    out
+}
+
+fn whole(s: &Symbol, change: &'static str) -> SymbolChange {
+    let (before, after) = match change {
+        "removed" => (s.sig.clone(), None),
+        _ => (None, s.sig.clone()),
+    };
+    SymbolChange {
+        change,
+        kind: s.kind.clone(),
+        name: s.qualified(),
+        public: s.public,
+        before,
+        after,
+        added: Vec::new(),
+        removed: Vec::new(),
+        retyped: Vec::new(),
+    }
+}
+
+fn compare_one(b: &Symbol, a: &Symbol) -> Option<SymbolChange> {
+    let mut c = whole(a, "signature");
+    c.after = None;
+    match a.kind.as_str() {
+        "struct" => {
+            let field_map = |s: &Symbol| -> BTreeMap<String, String> {
+                s.members.iter().map(|m| (member_key(m), m.clone())).collect()
+            };
+            let (bm, am) = (field_map(b), field_map(a));
+            c.change = "fields";
+            c.added = am.iter().filter(|(k, _)| !bm.contains_key(*k)).map(|(_, v)| v.clone()).collect();
+            c.removed = bm.iter().filter(|(k, _)| !am.contains_key(*k)).map(|(_, v)| v.clone()).collect();
+            c.retyped = bm
+                .iter()
+                .filter_map(|(k, bv)| am.get(k).filter(|av| *av != bv).map(|av| format!("{bv} -> {av}")))
+                .collect();
+        }
+        "enum" => {
+            c.change = "variants";
+            c.added = a.members.iter().filter(|v| !b.members.contains(v)).cloned().collect();
+            c.removed = b.members.iter().filter(|v| !a.members.contains(v)).cloned().collect();
+        }
+        _ => {
+            if b.sig != a.sig {
+                c.before = b.sig.clone();
+                c.after = a.sig.clone();
+            }
+        }
+    }
+    let vis_changed = b.public != a.public;
+    let touched = c.after.is_some() || !c.added.is_empty() || !c.removed.is_empty() || !c.retyped.is_empty();
+    if vis_changed && !touched {
+        // Visibility flip only: report as a signature change on the item itself.
+        c.change = "signature";
+        c.before = Some(if b.public { "pub" } else { "private" }.into());
+        c.after = Some(if a.public { "pub" } else { "private" }.into());
+        return Some(c);
+    }
+    touched.then_some(c)
+}
+
+/// Field name of a `pub name: Ty` member (`name`), tuple index for `0: Ty`.
+fn member_key(m: &str) -> String {
+    let m = m.strip_prefix("pub ").unwrap_or(m);
+    m.split(':').next().unwrap_or(m).trim().to_string()
+}
+
+/* ================================== Tests ================================== */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn syms(src: &str) -> Vec<Symbol> {
+        rust_symbols(src).unwrap()
+    }
+
+    #[test]
+    fn collects_fns_methods_and_types() {
+        let s = syms(
+            "pub struct A { pub x: u8, y: String }\nenum E { One, Two }\npub fn go(a: u8) {}\nimpl A { pub fn new() -> Self { todo!() } }\n#[cfg(test)] mod tests { #[test] fn t() {} }\n",
+        );
+        let names: Vec<(&str, &str)> = s.iter().map(|s| (s.kind.as_str(), s.name.as_str())).collect();
+        assert_eq!(names, [("struct", "A"), ("enum", "E"), ("fn", "go"), ("method", "A::new")]);
+        assert_eq!(s[0].members, ["pub x: u8", "y: String"]);
+        assert!(s[3].public);
+    }
+
+    #[test]
+    fn collects_mods_reexports_and_non_exhaustive() {
+        let s = syms(
+            "pub mod a;
+mod b;
+#[cfg(test)] mod tests {}
+pub use a::{X, y::Z as W, q::*};
+use b::Hidden;
+#[non_exhaustive]
+pub enum E { A }
+",
+        );
+        let view: Vec<(&str, &str, Option<&str>)> = s
+            .iter()
+            .filter(|s| matches!(s.kind.as_str(), "mod" | "use"))
+            .map(|s| (s.kind.as_str(), s.name.as_str(), s.sig.as_deref()))
+            .collect();
+        assert_eq!(
+            view,
+            [
+                ("mod", "a", None),
+                ("mod", "b", None),
+                ("use", "X", Some("a::X")),
+                ("use", "W", Some("a::y::Z")),
+                ("use", "*", Some("a::q")),
+            ]
+        );
+        assert_eq!(s.iter().find(|s| s.name == "E").unwrap().attrs, ["non_exhaustive"]);
+    }
+
+    #[test]
+    fn diff_reports_signature_field_and_variant_changes() {
+        let old = syms("pub struct A { pub x: u8 }\npub enum E { One }\npub fn go(a: u8) {}\nfn gone() {}\n");
+        let new = syms("pub struct A { pub x: u16, pub z: bool }\npub enum E { One, Two }\npub fn go(a: u8, b: u8) {}\npub fn fresh() {}\n");
+        let d = diff_symbols(&old, &new);
+        let by = |name: &str| d.iter().find(|c| c.name == name).unwrap();
+
+        assert_eq!(by("A").change, "fields");
+        assert_eq!(by("A").added, ["pub z: bool"]);
+        assert_eq!(by("A").retyped, ["pub x: u8 -> pub x: u16"]);
+        assert_eq!(by("E").added, ["Two"]);
+        assert_eq!(by("go").change, "signature");
+        assert!(by("go").after.as_deref().unwrap().contains("b: u8"));
+        assert_eq!(by("gone").change, "removed");
+        assert_eq!(by("fresh").change, "added");
+        assert_eq!(d.len(), 5);
+    }
+
+    #[test]
+    fn item_lines_and_hashes_cover_docs_and_bodies() {
+        let src = "/// Doc\n#[derive(Debug)]\npub struct A {\n    x: u8,\n}\n\nimpl A {\n    pub fn new() -> Self {\n        todo!()\n    }\n}\n";
+        let spans = rust_item_lines(src).unwrap();
+        assert_eq!(spans, [("struct A".to_string(), 1, 5), ("method A::new".to_string(), 8, 10)]);
+
+        let hash_of = |src: &str, name: &str| syms(src).into_iter().find(|s| s.name == name).and_then(|s| s.hash);
+        let body_edit = src.replace("todo!()", "Self { x: 0 }");
+        assert_eq!(hash_of(src, "A"), hash_of(&body_edit, "A"));
+        assert_ne!(hash_of(src, "A::new"), hash_of(&body_edit, "A::new"));
+        assert!(diff_symbols(&syms(src), &syms(&body_edit)).is_empty());
+    }
+
+    #[test]
+    fn same_named_items_get_distinct_keys_and_hashes() {
+        let src = "mod a { pub fn go() { 1; } }\nmod b { pub fn go() { 2; } }\n\
+                   #[cfg(unix)]\nfn os() { 3; }\n#[cfg(windows)]\nfn os() { 4; }\n\
+                   struct X;\nimpl X { fn fmt(&self) {} }\nimpl std::fmt::Debug for X { fn fmt(&self) {} }\n";
+        let s = syms(src);
+        let keys: Vec<String> = keyed(&s).into_iter().map(|(k, _)| k).collect();
+        for k in ["fn a::go", "fn b::go", "fn os", "fn os#2", "method X::fmt", "method <X as std::fmt::Debug>::fmt"] {
+            assert!(keys.iter().any(|x| x == k), "{k} missing from {keys:?}");
+        }
+        let hashes: BTreeSet<_> = s.iter().filter(|s| s.kind == "fn").map(|s| s.hash.clone().unwrap()).collect();
+        assert_eq!(hashes.len(), 4);
+
+        // Editing the second twin changes only its own hash.
+        let edited = syms(&src.replace("4;", "5;"));
+        let changed: Vec<_> = keyed(&s).into_iter().zip(keyed(&edited)).filter(|((_, a), (_, b))| a.hash != b.hash).map(|((k, _), _)| k).collect();
+        assert_eq!(changed, ["fn os#2"]);
+    }
+
+    #[test]
+    fn unchanged_files_have_no_delta() {
+        let s = syms("pub fn go() {}\nimpl std::fmt::Debug for X { fn fmt(&self) {} }\n");
+        assert!(diff_symbols(&s, &s).is_empty());
+    }
+}
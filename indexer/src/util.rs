@@ -139,35 +139,10 @@ pub fn is_probably_binary(bytes: &[u8]) -> bool {
     }
 }
 
-/// Map extension or filename to a canonical language label used by the indexer.
-/// Keep this set small & stable for predictable tags.
+/// Map extension or filename to a canonical language label used by the indexer:
+/// the first registered `lang::LanguageAnalyzer` that claims the path, else `txt`.
 pub fn ext_to_lang(path: &Path) -> &'static str {
-    let fname = path.file_name().and_then(|s| s.to_str()).unwrap_or("").to_ascii_lowercase();
-    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_ascii_lowercase();
-
-    match (fname.as_str(), ext.as_str()) {
-        (_, "rs") => "rust",
-        (_, "rs.in") => "rust",
-        ("makefile", _) | (_, "mk") => "make",
-        (_, "toml") => "toml",
-        (_, "json") | (_, "jsonl") => "json",
-        (_, "yml") | (_, "yaml") => "yaml",
-        (_, "md") => "md",
-        (_, "sh") => "sh",
-        (_, "py") => "python",
-        (_, "ts") => "ts",
-        (_, "tsx") => "tsx",
-        (_, "js") => "js",
-        (_, "jsx") => "jsx",
-        (_, "go") => "go",
-        (_, "java") => "java",
-        (_, "kt") => "kotlin",
-        (_, "cpp") | (_, "cc") | (_, "cxx") => "cpp",
-        (_, "c") => "c",
-        (_, "h") | (_, "hpp") => "c_header",
-        (_, "sql") => "sql",
-        _ => "txt",
-    }
+    crate::lang::analyzer_for_path(path).map_or("txt", |a| a.name())
 }
 
 /* ================================== Tagging ======================================= */
@@ -0,0 +1,398 @@
+// indexer/src/verify.rs
+//! Check an `index_v3.json` pack against the working tree.
+//!
+//! - Files: `file_sha256`, every chunk hash (fixed or `FastCDC`, as recorded)
+//!   and the merkle root are recomputed; the recorded chunk list must also hash to the recorded root.
+//! - Anchors: `verbatim_b64` must decode to the slice `slice_sha256` names
+//!   (unless redacted), and the recorded byte span must still hold that slice
+//!   on the recorded lines. Slices run from the item's leading docs/attributes
+//!   (or its `pub` / `async` / `unsafe` lead-in) to its last non-blank byte.
+//!   Otherwise the anchor is `moved` (same bytes elsewhere), `stale` (same
+//!   item, new bytes) or `missing`.
+//! - The report is JSON-serialisable; `safe` is true only when nothing drifted.
+//! - `prove_in_pack` turns the recorded chunk list into merkle inclusion proofs.
+
+use anyhow::{anyhow, bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD as B64, Engine as _};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::{collections::HashSet, fs, path::Path};
+
+use crate::{
+    index_v3::{self, hex256, Anchor, FileEntry, PackReader},
+    merkle,
+};
+
+/// Does `a`'s recorded byte span of `src` still hash to its `slice_sha256`
+/// and sit on its recorded lines? The span starts at the first doc comment or
+/// attribute (else the `pub` / `async` / `unsafe` lead-in), not mid-line. Older packs carry no span and never match
+/// here; `relocate` settles them by re-extracting the file.
+pub(crate) fn slice_at(src: &str, a: &Anchor) -> bool {
+    let (from, to) = a.span;
+    let Some(slice) = src.get(from..to).filter(|s| !s.is_empty()) else {
+        return false;
+    };
+    let line_of = |at: usize| memchr::memchr_iter(b'\n', &src.as_bytes()[..at]).count() + 1;
+    hex256(slice) == a.slice_sha256 && line_of(from) == a.range.start_line && line_of(to - 1) == a.range.end_line
+}
+
+/// Anchors of `f` verified against `src` (see `slice_at`).
+pub(crate) fn verified_anchors<'a>(f: &'a FileEntry, src: &str) -> Vec<&'a Anchor> {
+    let mut now = None;
+    f.anchors
+        .iter()
+        .filter(|a| {
+            slice_at(src, a) || (a.span == (0, 0) && relocate(a, now.get_or_insert_with(|| current_slices(f, src))).0 == "ok")
+        })
+        .collect()
+}
+
+fn current_slices(f: &FileEntry, src: &str) -> Vec<index_v3::AnchorSlice> {
+    index_v3::anchor_slices(&f.language, src, &f.path).unwrap_or_default()
+}
+
+/// Find `a`'s item among the re-extracted slices: `ok` (same bytes, same
+/// lines), `moved` (same bytes elsewhere), `stale` (new bytes) or `missing`.
+fn relocate<'s>(a: &Anchor, now: &'s [index_v3::AnchorSlice]) -> (&'static str, Option<&'s index_v3::AnchorSlice>) {
+    // Older packs have no anchor ids: fall back to kind + name.
+    let same_item = |s: &&index_v3::AnchorSlice| {
+        if a.anchor_id.is_empty() { s.kind == a.kind && s.name == a.name } else { s.anchor_id == a.anchor_id }
+    };
+    match now.iter().filter(same_item).find(|s| s.slice_sha256 == a.slice_sha256) {
+        Some(s) if s.start_line == a.range.start_line && s.end_line == a.range.end_line => ("ok", Some(s)),
+        Some(s) => ("moved", Some(s)),
+        None => match now.iter().find(same_item) {
+            Some(s) => ("stale", Some(s)),
+            None => ("missing", None),
+        },
+    }
+}
+
+/* ================================ Report ================================ */
+
+#[derive(Debug, Default, Serialize)]
+pub struct VerifyReport {
+    pub pack: String,
+    /// True when every file and anchor still matches: safe to hand to an agent.
+    pub safe: bool,
+    pub summary: Summary,
+    /// Files with any finding; clean files are only counted.
+    pub files: Vec<FileCheck>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct Summary {
+    pub files: usize,
+    pub files_ok: usize,
+    pub files_modified: usize,
+    pub files_missing: usize,
+    /// Pack entries inconsistent with themselves (chunks vs root, bad verbatim).
+    pub pack_errors: usize,
+    pub anchors: usize,
+    pub anchors_ok: usize,
+    pub anchors_moved: usize,
+    pub anchors_stale: usize,
+    pub anchors_missing: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FileCheck {
+    pub path: String,
+    /// `ok` | `modified` | `missing`.
+    pub status: &'static str,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub problems: Vec<String>,
+    /// Recorded chunk indexes whose sha256 no longer occurs in the file.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub bad_chunks: Vec<usize>,
+    /// Anchors that are not `ok`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub anchors: Vec<AnchorCheck>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AnchorCheck {
+    pub kind: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub anchor_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// `moved` | `stale` | `missing`.
+    pub status: &'static str,
+    /// Recorded `[start_line, end_line]`.
+    pub range: [usize; 2],
+    /// Where the item is now (`moved` / `stale`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub now: Option<[usize; 2]>,
+}
+
+/* ================================ Checking ================================ */
+
+/// Verify the pack at `pack_path` against files under `root`. Drift is
+/// reported in the result (`safe` is false), not as an error.
+///
+/// # Errors
+///
+/// Fails when the pack cannot be read or parsed.
+pub fn verify_pack(pack_path: &Path, root: &Path) -> Result<VerifyReport> {
+    let pack = index_v3::load_pack(pack_path)?;
+    let mut report = VerifyReport { pack: pack_path.display().to_string(), ..Default::default() };
+    for f in &pack.files {
+        let check = check_file(f, root, &mut report.summary);
+        report.summary.files += 1;
+        match check.status {
+            "missing" => report.summary.files_missing += 1,
+            "modified" => report.summary.files_modified += 1,
+            _ => report.summary.files_ok += 1,
+        }
+        if !check.problems.is_empty() || !check.anchors.is_empty() || check.status != "ok" {
+            report.files.push(check);
+        }
+    }
+    let s = &report.summary;
+    report.safe = s.files_ok == s.files && s.pack_errors == 0 && s.anchors_ok == s.anchors;
+    Ok(report)
+}
+
+fn check_file(f: &FileEntry, root: &Path, summary: &mut Summary) -> FileCheck {
+    let mut check = FileCheck {
+        path: f.path.clone(),
+        status: "ok",
+        problems: Vec::new(),
+        bad_chunks: Vec::new(),
+        anchors: Vec::new(),
+    };
+    summary.anchors += f.anchors.len();
+
+    // Pack-internal consistency holds regardless of the working tree.
+    let c = &f.chunks;
+    if c.digests().and_then(|d| c.root_of(&d)).is_none_or(|r| r != c.merkle_root) {
+        check.problems.push("recorded chunk hashes do not produce the recorded merkle_root".into());
+        summary.pack_errors += 1;
+    }
+    for a in &f.anchors {
+        if a.redacted.is_some() {
+            continue;
+        }
+        let ok = B64.decode(&a.verbatim_b64).is_ok_and(|raw| hex256(raw) == a.slice_sha256);
+        if !ok {
+            check.problems.push(format!("{} {}: verbatim_b64 does not hash to slice_sha256", a.kind, a.name));
+            summary.pack_errors += 1;
+        }
+    }
+
+    let Ok(bytes) = fs::read(root.join(&f.path)) else {
+        check.status = "missing";
+        summary.anchors_missing += f.anchors.len();
+        check.anchors = f.anchors.iter().map(|a| anchor_check(a, "missing", None)).collect();
+        return check;
+    };
+    // Files packed with a `parse_error` may not be UTF-8; hashes cover the raw bytes.
+    let src = String::from_utf8_lossy(&bytes);
+
+    if hex256(&bytes) != f.file_sha256 {
+        check.status = "modified";
+        check.problems.push("file_sha256 mismatch".into());
+    }
+    let Some(chunking) = c.chunking() else {
+        check.problems.push(format!("unknown chunking `{}`: chunks not checked", c.algo));
+        summary.pack_errors += 1;
+        return finish_anchors(f, &src, check, summary);
+    };
+    let now: Vec<merkle::Hash> = chunking.split(&bytes).into_iter().map(|(o, l)| Sha256::digest(&bytes[o..o + l]).into()).collect();
+    let now_hex: HashSet<String> = now.iter().map(hex::encode).collect();
+    check.bad_chunks = c.list.iter().filter(|ch| !now_hex.contains(&ch.sha256)).map(|ch| ch.index).collect();
+    if c.root_of(&now).is_none_or(|r| r != c.merkle_root) {
+        check.status = "modified";
+        check.problems.push("merkle_root mismatch".into());
+    }
+    finish_anchors(f, &src, check, summary)
+}
+
+/// Classify each anchor of `f` against the current `src`.
+fn finish_anchors(f: &FileEntry, src: &str, mut check: FileCheck, summary: &mut Summary) -> FileCheck {
+    let mut current: Option<Vec<index_v3::AnchorSlice>> = None;
+    for a in &f.anchors {
+        if slice_at(src, a) {
+            summary.anchors_ok += 1;
+            continue;
+        }
+        let (status, at) = relocate(a, current.get_or_insert_with(|| current_slices(f, src)));
+        match status {
+            "ok" => {
+                summary.anchors_ok += 1;
+                continue;
+            }
+            "moved" => summary.anchors_moved += 1,
+            "stale" => summary.anchors_stale += 1,
+            _ => summary.anchors_missing += 1,
+        }
+        check.anchors.push(anchor_check(a, status, at.map(|s| [s.start_line, s.end_line])));
+    }
+    check
+}
+
+fn anchor_check(a: &Anchor, status: &'static str, now: Option<[usize; 2]>) -> AnchorCheck {
+    let present = |s: &String| (!s.is_empty()).then(|| s.clone());
+    AnchorCheck {
+        kind: a.kind.clone(),
+        name: a.name.clone(),
+        anchor_id: present(&a.anchor_id),
+        path: present(&a.path),
+        status,
+        range: [a.range.start_line, a.range.end_line],
+        now,
+    }
+}
+
+/* ================================ Proofs ================================ */
+
+/// Which chunks of a file to prove.
+pub enum ProofTarget<'a> {
+    Chunk(usize),
+    /// Every chunk overlapping an anchor's line range. Matches the anchor's
+    /// `anchor_id`, qualified `path`, `name`, or `kind name`.
+    Anchor(&'a str),
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChunkProof {
+    pub index: usize,
+    pub offset: usize,
+    pub length: usize,
+    pub proof: merkle::MerkleProof,
+    /// The chunk bytes, when requested (checked against the proof first).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chunk_b64: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProofBundle {
+    pub path: String,
+    pub merkle_root: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub anchor: Option<String>,
+    pub chunks: Vec<ChunkProof>,
+}
+
+/// Inclusion proofs for `target` in `path`, from the pack's recorded chunk
+/// hashes. Anchors and `with_chunk` read the file under `root`, which must
+/// still match `file_sha256`.
+///
+/// # Errors
+///
+/// Fails when the pack cannot be read, `path` is not in it, its chunks
+/// predate [`merkle::SCHEME`] or do not produce the recorded root, the chunk
+/// index or anchor is unknown, or the file under `root` is unreadable or has
+/// changed since the pack was built.
+pub fn prove_in_pack(pack_path: &Path, root: &Path, path: &str, target: &ProofTarget, with_chunk: bool) -> Result<ProofBundle> {
+    let f = PackReader::open(pack_path)?.file(path)?.ok_or_else(|| anyhow!("`{path}` is not in {}", pack_path.display()))?;
+    let c = &f.chunks;
+    if c.merkle != merkle::SCHEME {
+        bail!("pack predates merkle scheme `{}`: re-run `indexer v3`", merkle::SCHEME);
+    }
+    let digests = c.digests().ok_or_else(|| anyhow!("`{path}`: malformed chunk sha256"))?;
+    if c.root_of(&digests).as_deref() != Some(c.merkle_root.as_str()) {
+        bail!("`{path}`: recorded chunks do not produce the recorded merkle_root");
+    }
+
+    let needs_src = with_chunk || matches!(target, ProofTarget::Anchor(_));
+    let src = if needs_src {
+        let bytes = fs::read(root.join(path)).with_context(|| format!("reading {path}"))?;
+        if hex256(&bytes) != f.file_sha256 {
+            bail!("`{path}` changed since the pack was built: re-run `indexer v3`");
+        }
+        bytes
+    } else {
+        Vec::new()
+    };
+
+    let (indexes, anchor): (Vec<usize>, Option<String>) = match target {
+        ProofTarget::Chunk(i) => (vec![*i], None),
+        ProofTarget::Anchor(name) => {
+            let a = f
+                .anchors
+                .iter()
+                .find(|a| {
+                    a.anchor_id == *name
+                        || a.path == *name
+                        || a.name == *name
+                        || format!("{} {}", a.kind, a.name) == *name
+                })
+                .ok_or_else(|| anyhow!("no anchor `{name}` in `{path}`"))?;
+            let starts: Vec<usize> = std::iter::once(0).chain(memchr::memchr_iter(b'\n', &src).map(|i| i + 1)).collect();
+            let from = starts.get(a.range.start_line.saturating_sub(1)).copied().unwrap_or(src.len());
+            let to = starts.get(a.range.end_line).copied().unwrap_or(src.len());
+            let hits = c.list.iter().filter(|ch| ch.offset < to && from < ch.offset + ch.length).map(|ch| ch.index);
+            let label = if a.path.is_empty() { &a.name } else { &a.path };
+            (hits.collect(), Some(format!("{} {label} (lines {}-{})", a.kind, a.range.start_line, a.range.end_line)))
+        }
+    };
+
+    let mut chunks = Vec::new();
+    for i in indexes {
+        let ch = c.list.iter().find(|ch| ch.index == i).ok_or_else(|| anyhow!("`{path}` has {} chunk(s); no chunk {i}", c.list.len()))?;
+        let proof = merkle::prove(&digests, i).ok_or_else(|| anyhow!("no chunk {i}"))?;
+        let chunk_b64 = if with_chunk {
+            let bytes = src.get(ch.offset..ch.offset + ch.length).ok_or_else(|| anyhow!("chunk {i} is out of bounds"))?;
+            if !merkle::verify_chunk(bytes, &proof, &c.merkle_root) {
+                bail!("chunk {i} of `{path}` does not verify against merkle_root");
+            }
+            Some(B64.encode(bytes))
+        } else {
+            None
+        };
+        chunks.push(ChunkProof { index: i, offset: ch.offset, length: ch.length, proof, chunk_b64 });
+    }
+    Ok(ProofBundle { path: path.to_string(), merkle_root: c.merkle_root.clone(), anchor, chunks })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_moved_stale_and_missing_anchors() {
+        let dir = std::env::temp_dir().join(format!("indexer-verify-test-{}", std::process::id()));
+        fs::create_dir_all(dir.join("src")).unwrap();
+        let src = "pub fn a() -> u8 {\n    1\n}\n\npub fn b() {}\n\nfn c() {}\n";
+        fs::write(dir.join("src/lib.rs"), src).unwrap();
+        fs::write(dir.join("src/gone.rs"), "fn g() {}\n").unwrap();
+        fs::write(dir.join("index.jsonl"), "{\"path\":\"src/lib.rs\",\"lang\":\"rust\"}\n{\"path\":\"src/gone.rs\",\"lang\":\"rust\"}\n").unwrap();
+        let pack = dir.join("index_v3.json");
+        index_v3::build_index_v3(&dir.join("index.jsonl"), &dir, &pack).unwrap();
+
+        let clean = verify_pack(&pack, &dir).unwrap();
+        assert!(clean.safe, "{clean:?}");
+        assert_eq!(clean.summary.anchors_ok, 4);
+
+        // Older packs record no byte span: anchors are settled by re-extraction.
+        let mut legacy = index_v3::load_pack(&pack).unwrap();
+        legacy.files.iter_mut().flat_map(|f| &mut f.anchors).for_each(|a| a.span = (0, 0));
+        let legacy_path = dir.join("legacy_v3.json");
+        fs::write(&legacy_path, serde_json::to_string(&legacy).unwrap()).unwrap();
+        let old = verify_pack(&legacy_path, &dir).unwrap();
+        assert!(old.safe && old.summary.anchors_ok == 4, "{old:?}");
+
+        let bundle = prove_in_pack(&pack, &dir, "src/lib.rs", &ProofTarget::Anchor("fn b"), true).unwrap();
+        let p = &bundle.chunks[0];
+        let bytes = B64.decode(p.chunk_b64.as_deref().unwrap()).unwrap();
+        assert!(merkle::verify_chunk(&bytes, &p.proof, &bundle.merkle_root));
+
+        // a() moves down two lines, b() changes, c() disappears, gone.rs is removed.
+        fs::write(dir.join("src/lib.rs"), "// x\n// y\npub fn a() -> u8 {\n    1\n}\n\npub fn b() { () }\n").unwrap();
+        fs::remove_file(dir.join("src/gone.rs")).unwrap();
+        let r = verify_pack(&pack, &dir).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+
+        assert!(!r.safe);
+        let s = &r.summary;
+        assert_eq!((s.files_modified, s.files_missing, s.pack_errors), (1, 1, 0));
+        assert_eq!((s.anchors_moved, s.anchors_stale, s.anchors_missing), (1, 1, 2));
+        let lib = r.files.iter().find(|f| f.path == "src/lib.rs").unwrap();
+        let moved = lib.anchors.iter().find(|a| a.name == "a").unwrap();
+        assert_eq!((moved.status, moved.range, moved.now), ("moved", [1, 3], Some([3, 5])));
+        assert_eq!(lib.bad_chunks, [0]);
+    }
+}